@@ -0,0 +1,123 @@
+use crate::{error::ApiError, profiles, rankings, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use datamodels::{
+    geo::{GeoPoint, Latitude, Longitude},
+    rollup::{self, RegionDay},
+    units::{UnitLabels, Units},
+    Area,
+};
+use serde::{Deserialize, Serialize};
+use storage::NearbyArea;
+
+const DEFAULT_NEARBY_LIMIT: usize = 50;
+const MAX_RADIUS_KM: f64 = 500.0;
+
+#[derive(Deserialize, Debug)]
+pub struct NearbyParams {
+    lat: Latitude,
+    lng: Longitude,
+    radius_km: f64,
+    limit: Option<usize>,
+}
+
+pub async fn get_nearby_areas(
+    State(state): State<AppState>,
+    Query(params): Query<NearbyParams>,
+) -> Result<Json<Vec<NearbyArea>>, ApiError> {
+    let radius_km = rankings::check_distance_km("radius_km", params.radius_km, MAX_RADIUS_KM)?;
+
+    let mut nearby = state
+        .areas
+        .areas_near(
+            GeoPoint {
+                lat: params.lat,
+                lng: params.lng,
+            },
+            radius_km,
+        )
+        .await?;
+    nearby.truncate(params.limit.unwrap_or(DEFAULT_NEARBY_LIMIT));
+    Ok(Json(nearby))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RegionForecastParams {
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    // Rescore for a stored profile, and or inline preferences as a json object
+    profile_id: Option<String>,
+    preferences: Option<String>,
+    // The profile's units, or metric, when not given
+    units: Option<Units>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RegionForecast {
+    pub units: UnitLabels,
+    pub area: Area,
+    pub path: String,
+    pub crags: usize,
+    pub days: Vec<RegionDay>,
+}
+
+// Stored crag forecasts rolled up to any area above them
+pub async fn get_region_forecast(
+    State(state): State<AppState>,
+    Path(uuid): Path<String>,
+    Query(params): Query<RegionForecastParams>,
+) -> Result<Json<RegionForecast>, ApiError> {
+    let from = params.from.unwrap_or_else(|| Utc::now().date_naive());
+    let to = params
+        .to
+        .unwrap_or(from + Duration::days(state.forecast_days as i64 - 1));
+    if from > to {
+        return Err(ApiError::Validation(
+            "from must not be after to".to_string(),
+        ));
+    }
+
+    let area = state
+        .areas
+        .area_by_uuid(&uuid)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No area {}", uuid)))?;
+    let preferences = profiles::resolve_preferences(
+        &state,
+        params.profile_id.as_deref(),
+        params.preferences.as_deref(),
+    )
+    .await?;
+    let mut crags = state.areas.crags_within(&uuid).await?;
+    if let Some(preferences) = &preferences {
+        crags.retain(|crag| preferences.allows(crag));
+    }
+
+    let mut by_area = rankings::stored_by_area(state.weather.as_ref(), from, to).await?;
+    let mut weather = Vec::new();
+    for crag in &crags {
+        let Some(mut days) = by_area.remove(&rankings::area_key(crag)) else {
+            continue;
+        };
+        for day in &mut days {
+            // Days stored before the uuid was kept still belong to this crag
+            day.uuid = crag.uuid.clone();
+            if let Some(preferences) = &preferences {
+                day.rescore(&preferences.model_for(crag));
+            }
+        }
+        weather.extend(days);
+    }
+
+    let units = profiles::units_for(params.units, preferences.as_ref());
+    Ok(Json(RegionForecast {
+        units: units.labels(),
+        path: area.path(),
+        area,
+        crags: crags.len(),
+        days: rollup::roll_up(&weather, units),
+    }))
+}
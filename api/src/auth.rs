@@ -0,0 +1,397 @@
+use crate::{error::ApiError, AppState};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use datamodels::api_key::{ApiKey, KeyUsage};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const KEY_PREFIX: &str = "gl_";
+const KEY_BYTES: usize = 32;
+// Characters of a key kept in the clear so it can be recognised
+const SHOWN_PREFIX_LEN: usize = 10;
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 60;
+const DEFAULT_BURST: u32 = 20;
+
+#[derive(Clone, Debug)]
+pub struct AuthConfig {
+    pub require_api_key: bool,
+    // Hash of ADMIN_TOKEN, admin endpoints are off without one
+    admin_token_hash: Option<String>,
+    pub requests_per_minute: u32,
+    pub burst: u32,
+}
+
+fn env_u32(name: &str, default: u32) -> u32 {
+    std::env::var(name)
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(default)
+}
+
+impl AuthConfig {
+    // Only the hash of the admin token is kept, an empty token disables the admin endpoints
+    pub fn new(
+        require_api_key: bool,
+        admin_token: Option<&str>,
+        requests_per_minute: u32,
+        burst: u32,
+    ) -> Self {
+        Self {
+            require_api_key,
+            admin_token_hash: admin_token.filter(|token| !token.is_empty()).map(hash_key),
+            requests_per_minute,
+            burst,
+        }
+    }
+
+    // Reads REQUIRE_API_KEY (default true), ADMIN_TOKEN, RATE_LIMIT_PER_MINUTE and
+    // RATE_LIMIT_BURST
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("REQUIRE_API_KEY")
+                .map(|val| val != "false" && val != "0")
+                .unwrap_or(true),
+            std::env::var("ADMIN_TOKEN").ok().as_deref(),
+            env_u32("RATE_LIMIT_PER_MINUTE", DEFAULT_REQUESTS_PER_MINUTE),
+            env_u32("RATE_LIMIT_BURST", DEFAULT_BURST),
+        )
+    }
+}
+
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+fn generate_key() -> String {
+    let mut bytes = [0u8; KEY_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", KEY_PREFIX, hex::encode(bytes))
+}
+
+// Compares without stopping at the first difference, so timing does not leak a prefix
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// One token bucket per key, refilled continuously at the key's rate
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    // Takes a token from the key's bucket, or returns how long until one is available
+    pub fn take(&self, key_id: &str, per_minute: u32, burst: u32) -> Result<(), Duration> {
+        let capacity = burst.max(1) as f64;
+        let per_second = per_minute.max(1) as f64 / 60.0;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        let bucket = buckets.entry(key_id.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+pub struct Auth {
+    pub config: AuthConfig,
+    pub limiter: RateLimiter,
+}
+
+impl Auth {
+    pub fn new(config: AuthConfig) -> Self {
+        Self {
+            config,
+            limiter: RateLimiter::default(),
+        }
+    }
+}
+
+// The key from an X-Api-Key header or an Authorization bearer token
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-api-key")
+        .and_then(|val| val.to_str().ok())
+        .or_else(|| {
+            headers
+                .get(header::AUTHORIZATION)
+                .and_then(|val| val.to_str().ok())
+                .and_then(|val| val.strip_prefix("Bearer "))
+        })
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+fn unauthorized(msg: &str) -> Response {
+    ApiError::Unauthorized(msg.to_string()).into_response()
+}
+
+fn record_usage(state: &AppState, key: &ApiKey, requests: u64, rate_limited: u64) {
+    let api_keys = state.api_keys.clone();
+    let id = key.id.clone();
+    // Counting must not slow the request down
+    tokio::spawn(async move {
+        if let Err(err) = api_keys
+            .record_usage(&id, requests, rate_limited, Utc::now())
+            .await
+        {
+            tracing::warn!("could not record usage for key {}: {}", id, err);
+        }
+    });
+}
+
+// Lets a request through with an active key that still has tokens. The key is added
+// to the request extensions for the handlers.
+pub async fn require_api_key<B>(
+    State(state): State<AppState>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if !state.auth.config.require_api_key {
+        return next.run(req).await;
+    }
+    let Some(presented) = presented_key(req.headers()) else {
+        return unauthorized("missing api key, send it in the X-Api-Key header");
+    };
+    let key = match state.api_keys.api_key_by_hash(&hash_key(presented)).await {
+        Ok(Some(key)) if key.is_active() => key,
+        Ok(Some(_)) => return unauthorized("api key has been revoked"),
+        Ok(None) => return unauthorized("invalid api key"),
+        Err(err) => return ApiError::from(err).into_response(),
+    };
+
+    let config = &state.auth.config;
+    let limited = state.auth.limiter.take(
+        &key.id,
+        key.requests_per_minute
+            .unwrap_or(config.requests_per_minute),
+        key.burst.unwrap_or(config.burst),
+    );
+    if let Err(retry_after) = limited {
+        record_usage(&state, &key, 0, 1);
+        return ApiError::RateLimited { retry_after }.into_response();
+    }
+
+    record_usage(&state, &key, 1, 0);
+    req.extensions_mut().insert(key);
+    next.run(req).await
+}
+
+pub async fn require_admin<B>(
+    State(state): State<AppState>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(admin_hash) = &state.auth.config.admin_token_hash else {
+        return ApiError::Forbidden(
+            "admin endpoints are disabled, set ADMIN_TOKEN to enable them".to_string(),
+        )
+        .into_response();
+    };
+    match presented_key(req.headers()) {
+        Some(token) if constant_time_eq(&hash_key(token), admin_hash) => next.run(req).await,
+        _ => unauthorized("invalid admin token"),
+    }
+}
+
+// An api key as shown to admins, without its hash
+#[derive(Serialize, Debug)]
+pub struct KeySummary {
+    pub id: String,
+    pub name: String,
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub requests_per_minute: Option<u32>,
+    pub burst: Option<u32>,
+    pub usage: KeyUsage,
+}
+
+impl From<ApiKey> for KeySummary {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            id: key.id,
+            name: key.name,
+            prefix: key.prefix,
+            created_at: key.created_at,
+            revoked_at: key.revoked_at,
+            requests_per_minute: key.requests_per_minute,
+            burst: key.burst,
+            usage: key.usage,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct IssuedKey {
+    // Shown only here, only its hash is stored
+    pub key: String,
+    #[serde(flatten)]
+    pub summary: KeySummary,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct IssueKeyBody {
+    name: String,
+    requests_per_minute: Option<u32>,
+    burst: Option<u32>,
+}
+
+pub async fn issue_key(
+    State(state): State<AppState>,
+    Json(body): Json<IssueKeyBody>,
+) -> Result<(StatusCode, Json<IssuedKey>), ApiError> {
+    if body.name.trim().is_empty() {
+        return Err(ApiError::Validation("name must not be empty".to_string()));
+    }
+    if body.requests_per_minute == Some(0) || body.burst == Some(0) {
+        return Err(ApiError::Validation(
+            "requests_per_minute and burst must be positive".to_string(),
+        ));
+    }
+    let key = generate_key();
+    let api_key = ApiKey {
+        id: storage::new_api_key_id(),
+        name: body.name,
+        key_hash: hash_key(&key),
+        prefix: key[..SHOWN_PREFIX_LEN].to_string(),
+        created_at: Utc::now(),
+        revoked_at: None,
+        requests_per_minute: body.requests_per_minute,
+        burst: body.burst,
+        usage: KeyUsage::default(),
+    };
+    state.api_keys.insert_api_key(&api_key).await?;
+    Ok((
+        StatusCode::CREATED,
+        Json(IssuedKey {
+            key,
+            summary: api_key.into(),
+        }),
+    ))
+}
+
+pub async fn list_keys(State(state): State<AppState>) -> Result<Json<Vec<KeySummary>>, ApiError> {
+    let keys = state.api_keys.all_api_keys().await?;
+    Ok(Json(keys.into_iter().map(KeySummary::from).collect()))
+}
+
+pub async fn revoke_key(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<KeySummary>, ApiError> {
+    state
+        .api_keys
+        .revoke_api_key(&id, Utc::now())
+        .await?
+        .map(|key| Json(key.into()))
+        .ok_or_else(|| ApiError::NotFound(format!("No api key {}", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_is_spent_then_limited() {
+        let limiter = RateLimiter::default();
+        for _ in 0..3 {
+            assert!(limiter.take("key", 60, 3).is_ok());
+        }
+        let retry_after = limiter.take("key", 60, 3).unwrap_err();
+        // One token a second at 60 a minute
+        assert!(retry_after <= Duration::from_secs(1));
+        assert!(retry_after > Duration::from_millis(900));
+        // Other keys have their own bucket
+        assert!(limiter.take("other", 60, 3).is_ok());
+    }
+
+    #[test]
+    fn buckets_refill_over_time_up_to_the_burst() {
+        let limiter = RateLimiter::default();
+        for _ in 0..2 {
+            limiter.take("key", 60, 2).unwrap();
+        }
+        assert!(limiter.take("key", 60, 2).is_err());
+
+        // Pretend a minute has gone by, far more than needed to fill the bucket
+        limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .get_mut("key")
+            .unwrap()
+            .updated -= Duration::from_secs(60);
+        assert!(limiter.take("key", 60, 2).is_ok());
+        assert!(limiter.take("key", 60, 2).is_ok());
+        assert!(limiter.take("key", 60, 2).is_err());
+    }
+
+    #[test]
+    fn keys_hash_to_hex_sha256() {
+        assert_eq!(
+            hash_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(hash_key("gl_a"), hash_key("gl_b"));
+    }
+
+    #[test]
+    fn generated_keys_are_prefixed_and_unique() {
+        let key = generate_key();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + KEY_BYTES * 2);
+        assert_ne!(key, generate_key());
+    }
+
+    #[test]
+    fn keys_are_read_from_either_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(presented_key(&headers), None);
+        headers.insert(header::AUTHORIZATION, "Bearer gl_bearer".parse().unwrap());
+        assert_eq!(presented_key(&headers), Some("gl_bearer"));
+        headers.insert("x-api-key", " gl_header ".parse().unwrap());
+        assert_eq!(presented_key(&headers), Some("gl_header"));
+    }
+
+    #[test]
+    fn empty_admin_token_disables_admin() {
+        assert!(AuthConfig::new(true, Some(""), 60, 20)
+            .admin_token_hash
+            .is_none());
+        assert_eq!(
+            AuthConfig::new(true, Some("secret"), 60, 20).admin_token_hash,
+            Some(hash_key("secret"))
+        );
+    }
+}
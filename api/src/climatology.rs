@@ -0,0 +1,156 @@
+use crate::{error::ApiError, profiles, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use datamodels::{
+    climate::{Climatology, MonthClimate},
+    units::{UnitLabels, Units},
+    Area,
+};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_CLIMATOLOGY_YEARS: i32 = 10;
+const DEFAULT_CLIMATOLOGY_MAX_AGE_DAYS: i64 = 30;
+// The archive's reanalysis lags real time by about five days
+const ARCHIVE_LAG_DAYS: i64 = 7;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(default)
+}
+
+// The stored climatology for an area, rebuilt from the history provider once it is older
+// than CLIMATOLOGY_MAX_AGE_DAYS. CLIMATOLOGY_YEARS sets how much history it covers.
+pub async fn climatology_for(
+    state: &AppState,
+    area: &Area,
+    refresh: bool,
+) -> Result<Climatology, ApiError> {
+    let now = Utc::now();
+    let max_age = Duration::days(env_or(
+        "CLIMATOLOGY_MAX_AGE_DAYS",
+        DEFAULT_CLIMATOLOGY_MAX_AGE_DAYS,
+    ));
+    if !refresh {
+        // One that can't be read, e.g. written before a format change, is rebuilt
+        let cached = state
+            .climatology
+            .climatology_for_area(area)
+            .await
+            .unwrap_or_else(|err| {
+                tracing::warn!("could not read climatology for {}: {}", area.area_name, err);
+                None
+            });
+        if let Some(cached) = cached.filter(|cached| now - cached.computed_at < max_age) {
+            return Ok(cached);
+        }
+    }
+
+    let years = env_or("CLIMATOLOGY_YEARS", DEFAULT_CLIMATOLOGY_YEARS).max(1);
+    let end = now.date_naive() - Duration::days(ARCHIVE_LAG_DAYS);
+    let start = end
+        .with_year(end.year() - years)
+        .unwrap_or(end - Duration::days(365 * years as i64))
+        + Duration::days(1);
+    let history = state
+        .history
+        .daily_history(area.metadata.lat, area.metadata.lng, start, end)
+        .await?;
+    let climatology = Climatology::from_history(area, &history, now);
+    if climatology.months.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "{} has no full month of history for {}",
+            state.history.name(),
+            area.area_name
+        )));
+    }
+    state.climatology.save_climatology(&climatology).await?;
+    Ok(climatology)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClimatologyParams {
+    #[serde(default)]
+    refresh: bool,
+    // The profile's units, or metric, when not given
+    units: Option<Units>,
+    profile_id: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct MonthSummary {
+    pub month: u32,
+    pub avg_high: f64,
+    pub avg_low: f64,
+    // Total for the month in a typical year
+    pub monthly_precipitation: f64,
+    pub rainy_days: f64,
+    pub avg_humidity: Option<f64>,
+    pub avg_max_wind_speed: Option<f64>,
+    pub good_days: f64,
+    pub score: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ClimatologySummary {
+    pub units: UnitLabels,
+    pub area_uuid: Option<String>,
+    pub area_name: String,
+    pub lat: f64,
+    pub lng: f64,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub computed_at: DateTime<Utc>,
+    pub months: Vec<MonthSummary>,
+}
+
+fn month_summary(month: &MonthClimate, units: Units) -> MonthSummary {
+    MonthSummary {
+        month: month.month,
+        avg_high: units.temperature(month.avg_high),
+        avg_low: units.temperature(month.avg_low),
+        monthly_precipitation: units.precipitation(month.monthly_precip),
+        rainy_days: month.rainy_days,
+        avg_humidity: month.avg_humidity,
+        avg_max_wind_speed: month.avg_max_wind.map(|wind| units.speed(wind)),
+        good_days: month.good_days,
+        score: month.score,
+    }
+}
+
+// Typical weather and goldilocks score for each month of the year
+pub async fn get_climatology(
+    State(state): State<AppState>,
+    Path(uuid): Path<String>,
+    Query(params): Query<ClimatologyParams>,
+) -> Result<Json<ClimatologySummary>, ApiError> {
+    let area = state
+        .areas
+        .area_by_uuid(&uuid)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No area {}", uuid)))?;
+
+    let preferences =
+        profiles::resolve_preferences(&state, params.profile_id.as_deref(), None).await?;
+    let units = profiles::units_for(params.units, preferences.as_ref());
+    let climatology = climatology_for(&state, &area, params.refresh).await?;
+    Ok(Json(ClimatologySummary {
+        units: units.labels(),
+        months: climatology
+            .months
+            .iter()
+            .map(|month| month_summary(month, units))
+            .collect(),
+        area_uuid: climatology.area_uuid,
+        area_name: climatology.area_name,
+        lat: climatology.lat,
+        lng: climatology.lng,
+        from: climatology.from,
+        to: climatology.to,
+        computed_at: climatology.computed_at,
+    }))
+}
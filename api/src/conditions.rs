@@ -0,0 +1,233 @@
+use crate::{error::ApiError, profiles, rankings::MAX_FORECAST_DAYS, weather_data_model, AppState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use datamodels::{
+    geo::{GeoPoint, Latitude, Longitude},
+    scoring::FactorScore,
+    units::{Length, UnitLabels, Units},
+    Area, AreaForecast, AreaWeather, Observation,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug)]
+pub struct ConditionsParams {
+    lat: Latitude,
+    lng: Longitude,
+    // Days of forecast including today, FORECAST_DAYS when not given
+    days: Option<u8>,
+    // The profile's units, or metric, when not given
+    units: Option<Units>,
+    // Score for a stored profile, and or inline preferences as a json object
+    profile_id: Option<String>,
+    preferences: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConditionsArea {
+    pub uuid: Option<String>,
+    pub area_name: String,
+    pub path: String,
+    pub lat: f64,
+    pub lng: f64,
+    // From the requested coordinates
+    pub distance: f64,
+    pub elevation: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CurrentConditions {
+    pub time: NaiveDateTime,
+    pub temperature: f64,
+    pub feels_like: Option<f64>,
+    pub humidity: f64,
+    pub wind_speed: f64,
+    pub gust_speed: Option<f64>,
+    pub wind_degree: Option<f64>,
+    pub precipitation: f64,
+    pub snow: Option<f64>,
+    pub cloud: f64,
+    pub uv: Option<f64>,
+    pub is_day: Option<bool>,
+    pub condition: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConditionsDay {
+    pub date: NaiveDate,
+    pub score: f64,
+    pub max_temperature: f64,
+    pub min_temperature: f64,
+    pub max_wind_speed: f64,
+    pub precipitation: f64,
+    pub snow: f64,
+    pub humidity: Option<f64>,
+    pub chance_of_rain: Option<f64>,
+    pub uv: Option<f64>,
+    pub condition: Option<String>,
+    pub sunrise: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+    pub best_window_start: Option<NaiveDateTime>,
+    pub wet_until: Option<NaiveDateTime>,
+    pub do_not_climb: bool,
+    // What kept the day from being just right, worst first
+    pub limiting_factors: Vec<FactorScore>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Conditions {
+    pub units: UnitLabels,
+    pub area: ConditionsArea,
+    pub provider: String,
+    pub fetched_at: DateTime<Utc>,
+    pub current: Option<CurrentConditions>,
+    // Average over the forecast days, 0 to 100
+    pub score: f64,
+    pub days: Vec<ConditionsDay>,
+}
+
+fn current_conditions(current: &Observation, units: Units) -> CurrentConditions {
+    CurrentConditions {
+        time: current.time,
+        temperature: units.temperature(current.temp),
+        feels_like: current.feels_like.map(|temp| units.temperature(temp)),
+        humidity: current.humidity,
+        wind_speed: units.speed(current.wind),
+        gust_speed: current.gust.map(|gust| units.speed(gust)),
+        wind_degree: current.wind_degree,
+        precipitation: units.precipitation(current.precip),
+        snow: current.snow.map(|snow| units.snow(snow)),
+        cloud: current.cloud,
+        uv: current.uv,
+        is_day: current.is_day,
+        condition: current.condition.clone(),
+    }
+}
+
+fn conditions_day(day: AreaWeather, units: Units) -> ConditionsDay {
+    let forecast = day.forecast;
+    ConditionsDay {
+        date: day.date,
+        score: day.score.score,
+        max_temperature: units.temperature(forecast.max_temp),
+        min_temperature: units.temperature(forecast.min_temp),
+        max_wind_speed: units.speed(forecast.max_wind),
+        precipitation: units.precipitation(forecast.total_precip),
+        snow: units.snow(forecast.total_snow),
+        humidity: forecast.avg_humidity,
+        chance_of_rain: forecast.chance_of_rain,
+        uv: forecast.uv,
+        condition: forecast.condition,
+        sunrise: forecast.sunrise,
+        sunset: forecast.sunset,
+        best_window_start: day.score.best_window_start,
+        wet_until: day.rock.as_ref().and_then(|rock| rock.wet_until),
+        do_not_climb: day.rock.as_ref().is_some_and(|rock| rock.do_not_climb),
+        limiting_factors: day
+            .score
+            .limiting_factors()
+            .into_iter()
+            .map(|factor| factor.in_units(units))
+            .collect(),
+    }
+}
+
+fn conditions_area(area: &Area, point: GeoPoint, units: Units) -> ConditionsArea {
+    ConditionsArea {
+        uuid: area.uuid.clone(),
+        area_name: area.area_name.clone(),
+        path: area.path(),
+        lat: area.metadata.lat,
+        lng: area.metadata.lng,
+        distance: units.distance(Length::from_km(
+            point.distance_km(area.metadata.lat, area.metadata.lng),
+        )),
+        elevation: area
+            .elevation_m()
+            .map(|elevation| units.elevation(Length::from_metres(elevation))),
+    }
+}
+
+// Forecast and goldilocks score for the stored crag nearest the coordinates. The
+// scored days are also stored, as they always have been, with the crag's own model
+// and only rescored for the climber's preferences in the response.
+pub async fn get_conditions(
+    State(state): State<AppState>,
+    Query(params): Query<ConditionsParams>,
+) -> Result<Json<Conditions>, ApiError> {
+    let days = params.days.unwrap_or(state.forecast_days);
+    if days == 0 || days as i64 > MAX_FORECAST_DAYS {
+        return Err(ApiError::Validation(format!(
+            "days must be between 1 and {}",
+            MAX_FORECAST_DAYS
+        )));
+    }
+    let point = GeoPoint {
+        lat: params.lat,
+        lng: params.lng,
+    };
+    let preferences = profiles::resolve_preferences(
+        &state,
+        params.profile_id.as_deref(),
+        params.preferences.as_deref(),
+    )
+    .await?;
+
+    // No quota is spent on points without a crag, and the forecast stored for the crag
+    // is for the crag rather than wherever the request was made
+    let area = weather_data_model::nearest_crag(state.areas.as_ref(), point).await?;
+    let forecast = weather_data_model::get_weather_from_api(
+        state.provider.as_ref(),
+        area.metadata.lat,
+        area.metadata.lng,
+        days,
+    )
+    .await?;
+    // Only the current hour is needed once the days have been stored
+    let now = AreaForecast {
+        days: Vec::new(),
+        ..forecast.clone()
+    };
+    let mut stored = weather_data_model::store_forecast(
+        state.weather.as_ref(),
+        state.observations.as_ref(),
+        &area,
+        forecast,
+    )
+    .await?;
+
+    // Days are scored at the crag's elevation, the current hour should match them
+    let now = match area.elevation_m() {
+        Some(elevation_m) => now.adjusted_to_elevation(elevation_m),
+        None => now,
+    };
+
+    if let Some(preferences) = &preferences {
+        let model = preferences.model_for(&area);
+        stored.iter_mut().for_each(|day| day.rescore(&model));
+    }
+
+    let units = profiles::units_for(params.units, preferences.as_ref());
+    let score = if stored.is_empty() {
+        0.0
+    } else {
+        stored.iter().map(|day| day.score.score).sum::<f64>() / stored.len() as f64
+    };
+    Ok(Json(Conditions {
+        units: units.labels(),
+        area: conditions_area(&area, point, units),
+        current: now
+            .current
+            .as_ref()
+            .map(|current| current_conditions(current, units)),
+        provider: now.provider,
+        fetched_at: now.fetched_at,
+        score,
+        days: stored
+            .into_iter()
+            .map(|day| conditions_day(day, units))
+            .collect(),
+    }))
+}
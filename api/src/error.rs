@@ -0,0 +1,353 @@
+use crate::{providers::ProviderError, request_id};
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::{fmt, time::Duration};
+use storage::StorageError;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+// Keys go in the X-Api-Key header, a bearer token is accepted too
+const API_KEY_CHALLENGE: &str = "ApiKey header=\"X-Api-Key\", Bearer";
+
+#[derive(Debug)]
+pub enum ApiError {
+    // The weather provider failed or turned the request down
+    Upstream(ProviderError),
+    // The weather provider answered with something that could not be used
+    Parse(String),
+    NotFound(String),
+    // The request was understood but its values are not acceptable
+    Validation(String),
+    Storage(StorageError),
+    Unauthorized(String),
+    Forbidden(String),
+    RateLimited { retry_after: Duration },
+    // Something the request depends on is not ready yet, e.g. no stored forecasts
+    Unavailable(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upstream(err) => write!(f, "{}", err),
+            Self::Parse(msg)
+            | Self::NotFound(msg)
+            | Self::Validation(msg)
+            | Self::Unauthorized(msg)
+            | Self::Forbidden(msg)
+            | Self::Unavailable(msg) => write!(f, "{}", msg),
+            Self::Storage(err) => write!(f, "{}", err),
+            Self::RateLimited { retry_after } => write!(
+                f,
+                "rate limit exceeded, retry in {} seconds",
+                retry_after.as_secs().max(1)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<ProviderError> for ApiError {
+    fn from(err: ProviderError) -> Self {
+        match err {
+            err @ ProviderError::Parse(_) => Self::Parse(err.to_string()),
+            err => Self::Upstream(err),
+        }
+    }
+}
+
+impl From<StorageError> for ApiError {
+    fn from(err: StorageError) -> Self {
+        Self::Storage(err)
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::Upstream(ProviderError::Config(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            // The provider could not place the coordinates it was given
+            Self::Upstream(ProviderError::BadRequest(_)) => StatusCode::BAD_REQUEST,
+            Self::Upstream(ProviderError::Request(err)) if err.is_timeout() => {
+                StatusCode::GATEWAY_TIMEOUT
+            }
+            Self::Upstream(ProviderError::Status(status, _))
+                if status.as_u16() == StatusCode::TOO_MANY_REQUESTS.as_u16() =>
+            {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            Self::Upstream(_) | Self::Parse(_) => StatusCode::BAD_GATEWAY,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    // Stable identifier clients can match on, the detail text may change
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Upstream(_) => "upstream_error",
+            Self::Parse(_) => "upstream_parse_error",
+            Self::NotFound(_) => "not_found",
+            Self::Validation(_) => "validation_error",
+            Self::Storage(_) => "storage_error",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Unavailable(_) => "unavailable",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            // Provider keys and database details stay in the logs
+            Self::Upstream(ProviderError::Config(_)) => {
+                "the weather provider is not configured correctly".to_string()
+            }
+            // Request urls can carry the provider key
+            Self::Upstream(ProviderError::Request(_)) => {
+                "could not reach the weather provider".to_string()
+            }
+            Self::Upstream(ProviderError::Unauthorized(_) | ProviderError::Forbidden(_)) => {
+                "the weather provider refused our credentials".to_string()
+            }
+            Self::Storage(_) => "could not reach storage".to_string(),
+            err => err.to_string(),
+        }
+    }
+}
+
+// RFC 7807 problem details
+#[derive(Serialize, Debug)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub title: String,
+    pub status: u16,
+    pub code: &'static str,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &'static str, detail: String) -> Self {
+        Self {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            code,
+            detail,
+            request_id: request_id::current(),
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (status, Json(self)).into_response();
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        if status.is_server_error() {
+            tracing::error!("{}", self);
+        } else {
+            tracing::debug!("{}", self);
+        }
+        let mut response = Problem::new(status, self.code(), self.detail()).into_response();
+        match &self {
+            Self::Unauthorized(_) => {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static(API_KEY_CHALLENGE),
+                );
+            }
+            Self::RateLimited { retry_after } => {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, retry_after.as_secs().max(1).into());
+            }
+            _ => {}
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    async fn problem(err: ApiError) -> (Response, Value) {
+        let response = err.into_response();
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        (
+            Response::from_parts(parts, axum::body::boxed(axum::body::Empty::new())),
+            serde_json::from_slice(&body).unwrap(),
+        )
+    }
+
+    fn upstream_status(status: u16) -> ApiError {
+        ApiError::Upstream(ProviderError::Status(
+            reqwest::StatusCode::from_u16(status).unwrap(),
+            None,
+        ))
+    }
+
+    #[test]
+    fn every_error_has_a_status_and_code() {
+        let cases = [
+            (
+                ApiError::Upstream(ProviderError::Config("no key".to_string())),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "upstream_error",
+            ),
+            (
+                ApiError::Upstream(ProviderError::BadRequest("no location".to_string())),
+                StatusCode::BAD_REQUEST,
+                "upstream_error",
+            ),
+            (
+                ApiError::Upstream(ProviderError::Forbidden("quota".to_string())),
+                StatusCode::BAD_GATEWAY,
+                "upstream_error",
+            ),
+            (
+                upstream_status(500),
+                StatusCode::BAD_GATEWAY,
+                "upstream_error",
+            ),
+            (
+                ApiError::Parse("bad json".to_string()),
+                StatusCode::BAD_GATEWAY,
+                "upstream_parse_error",
+            ),
+            (
+                ApiError::NotFound("no area".to_string()),
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+            (
+                ApiError::Validation("bad dates".to_string()),
+                StatusCode::BAD_REQUEST,
+                "validation_error",
+            ),
+            (
+                ApiError::Storage(StorageError::Config("down".to_string())),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "storage_error",
+            ),
+            (
+                ApiError::Unauthorized("no key".to_string()),
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (
+                ApiError::Forbidden("admin only".to_string()),
+                StatusCode::FORBIDDEN,
+                "forbidden",
+            ),
+            (
+                ApiError::RateLimited {
+                    retry_after: Duration::from_secs(3),
+                },
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+            ),
+            (
+                ApiError::Unavailable("not yet".to_string()),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "unavailable",
+            ),
+        ];
+        for (err, status, code) in cases {
+            assert_eq!(err.status(), status, "{:?}", err);
+            assert_eq!(err.code(), code, "{:?}", err);
+        }
+    }
+
+    // The provider's status comes from reqwest's http crate, ours from axum's
+    #[test]
+    fn a_rate_limited_provider_is_unavailable_rather_than_a_bad_gateway() {
+        assert_eq!(
+            upstream_status(429).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(upstream_status(503).status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn provider_parse_errors_are_parse_errors() {
+        let err = ApiError::from(ProviderError::Parse("missing field".to_string()));
+        assert_eq!(err.code(), "upstream_parse_error");
+    }
+
+    #[tokio::test]
+    async fn errors_are_problem_details() {
+        let (response, body) = problem(ApiError::NotFound("No area murrin".to_string())).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        assert_eq!(body["type"], "about:blank");
+        assert_eq!(body["title"], "Not Found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["detail"], "No area murrin");
+        // Only set while a request is being handled
+        assert!(body.get("request_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn internal_details_stay_out_of_the_body() {
+        let (_, body) = problem(ApiError::Storage(StorageError::Config(
+            "mongodb://user:secret@db".to_string(),
+        )))
+        .await;
+        assert_eq!(body["detail"], "could not reach storage");
+        let (_, body) = problem(ApiError::Upstream(ProviderError::Config(
+            "WEATHER_API_KEY is empty".to_string(),
+        )))
+        .await;
+        assert_eq!(
+            body["detail"],
+            "the weather provider is not configured correctly"
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_names_the_api_key_header() {
+        let (response, _) = problem(ApiError::Unauthorized("missing api key".to_string())).await;
+        let challenge = response.headers()[header::WWW_AUTHENTICATE]
+            .to_str()
+            .unwrap();
+        assert!(challenge.starts_with("ApiKey header=\"X-Api-Key\""));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_says_when_to_retry() {
+        let (response, body) = problem(ApiError::RateLimited {
+            retry_after: Duration::from_millis(200),
+        })
+        .await;
+        // Never tells a client to retry straight away
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+        assert_eq!(body["detail"], "rate limit exceeded, retry in 1 seconds");
+    }
+}
@@ -1,17 +1,56 @@
+mod areas;
+mod auth;
+mod climatology;
+mod conditions;
+mod error;
+mod profiles;
+mod providers;
+mod rankings;
+mod refresh;
+mod request_id;
+mod trips;
 mod weather_data_model;
-use axum::{extract::Query, routing::get, Router};
+use axum::{
+    extract::State,
+    middleware,
+    routing::{delete, get, post},
+    Json, Router,
+};
 use dotenv::dotenv;
-use serde::Deserialize;
 
+use providers::{
+    CacheMetricsSnapshot, CachedProvider, ForecastCacheConfig, HistoryProvider, WeatherProvider,
+};
+use std::sync::Arc;
+use storage::{
+    ApiKeyRepository, AreaRepository, ClimatologyRepository, ObservationRepository,
+    ProfileRepository, Repositories, WeatherRepository,
+};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+const DEFAULT_FORECAST_DAYS: u8 = 3;
+
+#[derive(Clone)]
+struct AppState {
+    provider: Arc<dyn WeatherProvider>,
+    forecast_cache: Arc<CachedProvider>,
+    history: Arc<dyn HistoryProvider>,
+    forecast_days: u8,
+    areas: Arc<dyn AreaRepository>,
+    weather: Arc<dyn WeatherRepository>,
+    observations: Arc<dyn ObservationRepository>,
+    climatology: Arc<dyn ClimatologyRepository>,
+    profiles: Arc<dyn ProfileRepository>,
+    api_keys: Arc<dyn ApiKeyRepository>,
+    auth: Arc<auth::Auth>,
+}
+
 #[tokio::main]
 async fn main() {
     // Get env vars
     dotenv().ok();
 
-    let app = Router::new().route("/", get(process_weather));
-
+    // Before anything that logs, the first refresh runs as soon as it is spawned
     tracing_subscriber::registry()
         .with(tracing_subscriber::EnvFilter::new(
             std::env::var("RUST_LOG").unwrap_or_else(|_| "axum_api=debug".into()),
@@ -19,6 +58,48 @@ async fn main() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    let forecast_days = std::env::var("FORECAST_DAYS")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_FORECAST_DAYS);
+
+    let repositories = Repositories::from_env()
+        .await
+        .expect("Failed to set up storage");
+
+    // Every forecast goes through the cache so repeated requests do not spend quota
+    let forecast_cache = Arc::new(CachedProvider::new(
+        providers::provider_from_env().expect("Invalid weather provider config"),
+        ForecastCacheConfig::from_env(),
+        repositories.forecast_cache.clone(),
+    ));
+
+    let state = AppState {
+        provider: forecast_cache.clone(),
+        forecast_cache,
+        history: providers::history_provider_from_env(),
+        forecast_days,
+        areas: repositories.areas,
+        weather: repositories.weather,
+        observations: repositories.observations,
+        climatology: repositories.climatology,
+        profiles: repositories.profiles,
+        api_keys: repositories.api_keys,
+        auth: Arc::new(auth::Auth::new(auth::AuthConfig::from_env())),
+    };
+
+    match refresh::RefreshConfig::from_env() {
+        Some(config) => {
+            refresh::spawn(state.clone(), config);
+        }
+        None => tracing::warn!(
+            "REFRESH_INTERVAL_MINUTES is 0, stored forecasts will not be refreshed and \
+             rankings without a region or origin will be unavailable"
+        ),
+    }
+
+    let app = router(state);
+
     let addr_str = std::env::var("AXUM_HOST").unwrap();
 
     axum::Server::bind(&addr_str.parse().unwrap())
@@ -27,25 +108,665 @@ async fn main() {
         .expect("Failed to start server");
 }
 
-#[derive(Deserialize, Debug)]
-struct LatLngParams {
-    lat: f64,
-    lng: f64,
+fn router(state: AppState) -> Router {
+    let admin = Router::new()
+        .route("/admin/keys", post(auth::issue_key).get(auth::list_keys))
+        .route("/admin/keys/:id", delete(auth::revoke_key))
+        .route("/admin/metrics/cache", get(cache_metrics))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin,
+        ));
+
+    Router::new()
+        .route("/", get(conditions::get_conditions))
+        .route("/rankings", get(rankings::get_rankings))
+        .route("/trips/plan", get(trips::plan_trip))
+        .route("/profiles", post(profiles::create_profile))
+        .route(
+            "/profiles/:id",
+            get(profiles::get_profile).put(profiles::update_profile),
+        )
+        .route("/areas/nearby", get(areas::get_nearby_areas))
+        .route("/areas/:uuid/forecast", get(areas::get_region_forecast))
+        .route(
+            "/areas/:uuid/climatology",
+            get(climatology::get_climatology),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ))
+        .merge(admin)
+        .with_state(state)
+        .layer(middleware::from_fn(request_id::assign_request_id))
+}
+
+async fn cache_metrics(State(state): State<AppState>) -> Json<CacheMetricsSnapshot> {
+    Json(state.forecast_cache.metrics())
 }
 
-async fn process_weather(Query(latlng): Query<LatLngParams>) -> Result<(), String> {
-    // Early exit code for debug purposes
-    // return Err(format!("{:?}", latlng));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use chrono::{Duration, NaiveDate, Utc};
+    use datamodels::{
+        api_key::{ApiKey, KeyUsage},
+        climate::{Climatology, HistoricalDay},
+        profile::Profile,
+        scoring::ScoringModel,
+        units::{Length, Speed, Temperature},
+        Area, AreaForecast, AreaWeather, DailyForecast,
+    };
+    use providers::ProviderError;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use storage::MemoryStorage;
+    use tower::ServiceExt;
+
+    const KEY: &str = "gl_test";
+    const REVOKED_KEY: &str = "gl_revoked";
 
-    let weather_raw = match weather_data_model::get_weather_from_api(latlng.lat, latlng.lng).await {
-        Ok(val) => Ok(val),
-        Err(err) => {
-            println!("{}", err);
-            Err("Error parsing json".to_string())
+    // Forecasts the same mild dry days everywhere and counts how often it was asked
+    #[derive(Default)]
+    struct StubProvider {
+        calls: AtomicUsize,
+    }
+
+    fn stub_day(date: NaiveDate) -> DailyForecast {
+        DailyForecast {
+            date,
+            max_temp: Temperature::from_celsius(16.0),
+            min_temp: Temperature::from_celsius(9.0),
+            avg_temp: Temperature::from_celsius(13.0),
+            max_wind: Speed::from_kph(8.0),
+            total_precip: Length::default(),
+            total_snow: Length::default(),
+            avg_humidity: Some(40.0),
+            chance_of_rain: Some(0.0),
+            uv: None,
+            condition: Some("Sunny".to_string()),
+            us_epa_index: None,
+            sunrise: None,
+            sunset: None,
+            hours: Vec::new(),
         }
-    }?;
-    match weather_data_model::add_weather_to_db(weather_raw).await {
-        Ok(_) => Ok(()),
-        Err(_) => Err("Error writing to db".to_string()),
+    }
+
+    #[async_trait]
+    impl WeatherProvider for StubProvider {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn forecast(
+            &self,
+            lat: f64,
+            lng: f64,
+            days: u8,
+        ) -> Result<AreaForecast, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let today = Utc::now().date_naive();
+            Ok(AreaForecast {
+                provider: "stub".to_string(),
+                lat,
+                lng,
+                utc_offset_seconds: 0,
+                fetched_at: Utc::now(),
+                elevation_m: None,
+                current: None,
+                days: (0..days as i64)
+                    .map(|day| stub_day(today + Duration::days(day)))
+                    .collect(),
+            })
+        }
+    }
+
+    struct NoHistory;
+
+    #[async_trait]
+    impl HistoryProvider for NoHistory {
+        fn name(&self) -> &'static str {
+            "none"
+        }
+
+        async fn daily_history(
+            &self,
+            _lat: f64,
+            _lng: f64,
+            _start: NaiveDate,
+            _end: NaiveDate,
+        ) -> Result<Vec<HistoricalDay>, ProviderError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn area(value: Value) -> Area {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn api_key(id: &str, key: &str, revoked: bool) -> ApiKey {
+        ApiKey {
+            id: id.to_string(),
+            name: id.to_string(),
+            key_hash: auth::hash_key(key),
+            prefix: key.to_string(),
+            created_at: Utc::now(),
+            revoked_at: revoked.then(Utc::now),
+            requests_per_minute: None,
+            burst: None,
+            usage: KeyUsage::default(),
+        }
+    }
+
+    struct TestApp {
+        state: AppState,
+        storage: MemoryStorage,
+        provider: Arc<StubProvider>,
+    }
+
+    // Squamish with two crags and a wall, on the memory backend
+    async fn test_app(config: auth::AuthConfig) -> TestApp {
+        let storage = MemoryStorage::with_areas(vec![
+            area(json!({
+                "uuid": "squamish",
+                "area_name": "Squamish",
+                "metadata": {"lat": 49.70, "lng": -123.15},
+                "is_crag": false,
+                "is_leaf": false,
+            })),
+            area(json!({
+                "uuid": "smoke-bluffs",
+                "area_name": "Smoke Bluffs",
+                "metadata": {"lat": 49.71, "lng": -123.14},
+                "ancestors": ["squamish"],
+            })),
+            area(json!({
+                "uuid": "murrin",
+                "area_name": "Murrin Park",
+                "metadata": {"lat": 49.65, "lng": -123.20},
+                "ancestors": ["squamish"],
+            })),
+        ])
+        .unwrap();
+        let repositories = storage::Repositories::memory(storage.clone());
+        repositories
+            .api_keys
+            .insert_api_key(&api_key("active", KEY, false))
+            .await
+            .unwrap();
+        repositories
+            .api_keys
+            .insert_api_key(&api_key("revoked", REVOKED_KEY, true))
+            .await
+            .unwrap();
+
+        let provider = Arc::new(StubProvider::default());
+        let forecast_cache = Arc::new(CachedProvider::new(
+            provider.clone(),
+            ForecastCacheConfig {
+                ttl: None,
+                max_entries: 100,
+                shared: false,
+            },
+            None,
+        ));
+        let state = AppState {
+            provider: forecast_cache.clone(),
+            forecast_cache,
+            history: Arc::new(NoHistory),
+            forecast_days: 3,
+            areas: repositories.areas,
+            weather: repositories.weather,
+            observations: repositories.observations,
+            climatology: repositories.climatology,
+            profiles: repositories.profiles,
+            api_keys: repositories.api_keys,
+            auth: Arc::new(auth::Auth::new(config)),
+        };
+        TestApp {
+            state,
+            storage,
+            provider,
+        }
+    }
+
+    fn keyed() -> auth::AuthConfig {
+        auth::AuthConfig::new(true, Some("admin-secret"), 60, 20)
+    }
+
+    async fn get(state: &AppState, uri: &str, key: Option<&str>) -> (StatusCode, Value) {
+        let mut req = Request::builder().uri(uri);
+        if let Some(key) = key {
+            req = req.header("x-api-key", key);
+        }
+        let res = router(state.clone())
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn conditions_come_from_the_nearest_crag() {
+        let app = test_app(keyed()).await;
+        let (status, body) = get(&app.state, "/?lat=49.709&lng=-123.141&days=2", Some(KEY)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["area"]["uuid"], "smoke-bluffs");
+        assert_eq!(body["provider"], "stub");
+        assert_eq!(body["days"].as_array().unwrap().len(), 2);
+        assert_eq!(body["units"]["temperature"], "°C");
+
+        // The forecast is fetched and stored for the crag, not the requested point
+        let crag = app
+            .storage
+            .area_by_uuid("smoke-bluffs")
+            .await
+            .unwrap()
+            .unwrap();
+        let today = Utc::now().date_naive();
+        let stored = app
+            .state
+            .weather
+            .weather_for_area(&crag, today, today + Duration::days(1))
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(
+            app.storage
+                .weather_between(today, today)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn conditions_far_from_any_crag_are_not_found() {
+        let app = test_app(keyed()).await;
+        let (status, body) = get(&app.state, "/?lat=10.0&lng=10.0", Some(KEY)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["status"], 404);
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn nearby_areas_are_nearest_first() {
+        let app = test_app(keyed()).await;
+        let (status, body) = get(
+            &app.state,
+            "/areas/nearby?lat=49.71&lng=-123.14&radius_km=20",
+            Some(KEY),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let uuids: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|area| area["uuid"].as_str().unwrap())
+            .collect();
+        assert_eq!(uuids, vec!["smoke-bluffs", "squamish", "murrin"]);
+        assert!(body[0]["distance_km"].as_f64().unwrap() < 0.01);
+    }
+
+    #[tokio::test]
+    async fn nearby_areas_need_a_real_radius_within_bounds() {
+        let app = test_app(keyed()).await;
+        for radius in ["0", "-5", "NaN", "inf", "100000"] {
+            let uri = format!("/areas/nearby?lat=49.71&lng=-123.14&radius_km={}", radius);
+            let (status, _) = get(&app.state, &uri, Some(KEY)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "radius_km={}", radius);
+        }
+    }
+
+    #[tokio::test]
+    async fn rankings_and_trips_need_real_distances_within_bounds() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        for distance in ["0", "NaN", "inf", "5000"] {
+            let uri = format!(
+                "/rankings?from={}&to={}&lat=49.7&lng=-123.15&max_distance_km={}",
+                today, today, distance
+            );
+            let (status, _) = get(&app.state, &uri, Some(KEY)).await;
+            assert_eq!(
+                status,
+                StatusCode::BAD_REQUEST,
+                "max_distance_km={}",
+                distance
+            );
+            let uri = format!(
+                "/trips/plan?from={}&to={}&lat=49.7&lng=-123.15&max_daily_km={}",
+                today, today, distance
+            );
+            let (status, _) = get(&app.state, &uri, Some(KEY)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "max_daily_km={}", distance);
+        }
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn rankings_in_a_region_fetch_each_crag() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        let uri = format!("/rankings?from={}&to={}&region=squamish", today, today);
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let mut uuids: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|area| area["uuid"].as_str().unwrap())
+            .collect();
+        uuids.sort();
+        assert_eq!(uuids, vec!["murrin", "smoke-bluffs"]);
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn rankings_without_a_region_use_stored_scores() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        // Rain at the nearer crag so the two rank differently
+        for (uuid, precip_mm) in [("smoke-bluffs", 8.0), ("murrin", 0.0)] {
+            let crag = app.storage.area_by_uuid(uuid).await.unwrap().unwrap();
+            let mut forecast = stub_day(today);
+            forecast.total_precip = Length::from_mm(precip_mm);
+            let day = AreaWeather {
+                uuid: crag.uuid.clone(),
+                area_name: crag.area_name.clone(),
+                lat: crag.metadata.lat,
+                lng: crag.metadata.lng,
+                date: today,
+                provider: "stub".to_string(),
+                fetched_at: Utc::now(),
+                score: ScoringModel::default().score_day(&forecast),
+                forecast,
+                rock: None,
+                site: None,
+            };
+            app.state.weather.upsert_weather(&day).await.unwrap();
+        }
+        let uri = format!("/rankings?from={}&to={}&limit=1", today, today);
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let ranked = body.as_array().unwrap();
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0]["uuid"], "murrin");
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn imperial_rankings_explain_in_imperial_units() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        let crag = app.storage.area_by_uuid("murrin").await.unwrap().unwrap();
+        let mut forecast = stub_day(today);
+        forecast.max_temp = Temperature::from_celsius(35.0);
+        forecast.total_precip = Length::from_mm(25.4);
+        let day = AreaWeather {
+            uuid: crag.uuid.clone(),
+            area_name: crag.area_name.clone(),
+            lat: crag.metadata.lat,
+            lng: crag.metadata.lng,
+            date: today,
+            provider: "stub".to_string(),
+            fetched_at: Utc::now(),
+            score: ScoringModel::default().score_day(&forecast),
+            forecast,
+            rock: None,
+            site: None,
+        };
+        app.state.weather.upsert_weather(&day).await.unwrap();
+        let uri = format!("/rankings?from={}&to={}&units=imperial", today, today);
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let reasons: Vec<&str> = body[0]["top_factors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|factor| factor["reason"].as_str().unwrap())
+            .collect();
+        assert!(reasons.contains(&"1.00in of precipitation expected"));
+        assert!(reasons.iter().any(|reason| reason.starts_with("95°F")));
+        assert!(reasons
+            .iter()
+            .all(|reason| !reason.contains("mm") && !reason.contains("°C")));
+    }
+
+    #[tokio::test]
+    async fn region_forecasts_name_the_best_crag_by_uuid() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        for (uuid, precip_mm) in [("smoke-bluffs", 8.0), ("murrin", 0.0)] {
+            let crag = app.storage.area_by_uuid(uuid).await.unwrap().unwrap();
+            let mut forecast = stub_day(today);
+            forecast.total_precip = Length::from_mm(precip_mm);
+            let day = AreaWeather {
+                // Stored before the uuid was kept
+                uuid: None,
+                area_name: crag.area_name.clone(),
+                lat: crag.metadata.lat,
+                lng: crag.metadata.lng,
+                date: today,
+                provider: "stub".to_string(),
+                fetched_at: Utc::now(),
+                score: ScoringModel::default().score_day(&forecast),
+                forecast,
+                rock: None,
+                site: None,
+            };
+            app.state.weather.upsert_weather(&day).await.unwrap();
+        }
+        let uri = format!("/areas/squamish/forecast?from={}&to={}", today, today);
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["days"][0]["crags"], 2);
+        assert_eq!(body["days"][0]["best_crag_uuid"], "murrin");
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn catalog_rankings_past_the_stored_days_are_rejected() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        let uri = format!("/rankings?from={}&to={}", today, today + Duration::days(5));
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["detail"]
+            .as_str()
+            .unwrap()
+            .contains("stored forecasts"));
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn catalog_rankings_without_stored_days_are_unavailable() {
+        let app = test_app(keyed()).await;
+        let today = Utc::now().date_naive();
+        let uri = format!("/rankings?from={}&to={}", today, today);
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["code"], "unavailable");
+    }
+
+    #[tokio::test]
+    async fn rankings_reject_dates_in_the_past() {
+        let app = test_app(keyed()).await;
+        let yesterday = Utc::now().date_naive() - Duration::days(1);
+        let uri = format!("/rankings?from={}&to={}", yesterday, yesterday);
+        let (status, _) = get(&app.state, &uri, Some(KEY)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn trips_after_the_forecast_window_skip_the_provider() {
+        let app = test_app(keyed()).await;
+        let from = Utc::now().date_naive() + Duration::days(20);
+        let uri = format!(
+            "/trips/plan?from={}&to={}&lat=49.7&lng=-123.15&max_daily_km=50",
+            from,
+            from + Duration::days(2)
+        );
+        let (status, body) = get(&app.state, &uri, Some(KEY)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["days"].as_array().unwrap().len(), 3);
+        assert_eq!(app.provider.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn climatology_is_shown_in_the_profile_units() {
+        let app = test_app(keyed()).await;
+        let crag = app.storage.area_by_uuid("murrin").await.unwrap().unwrap();
+        let history: Vec<HistoricalDay> = (1..=31)
+            .map(|day| HistoricalDay {
+                date: NaiveDate::from_ymd_opt(2022, 7, day).unwrap(),
+                max_temp: Temperature::from_celsius(25.0),
+                min_temp: Temperature::from_celsius(15.0),
+                precip: Length::from_mm(1.0),
+                snow: Length::default(),
+                humidity: None,
+                max_wind: Some(Speed::from_kph(16.09344)),
+            })
+            .collect();
+        app.state
+            .climatology
+            .save_climatology(&Climatology::from_history(&crag, &history, Utc::now()))
+            .await
+            .unwrap();
+        app.state
+            .profiles
+            .save_profile(&Profile {
+                id: "imperial".to_string(),
+                name: None,
+                preferences: serde_json::from_value(json!({"units": "imperial"})).unwrap(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+
+        let (status, body) = get(
+            &app.state,
+            "/areas/murrin/climatology?profile_id=imperial",
+            Some(KEY),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["units"]["temperature"], "°F");
+        assert_eq!(body["months"][0]["avg_high"], 77.0);
+        assert_eq!(body["months"][0]["avg_max_wind_speed"], 10.0);
+        assert_eq!(body["months"][0]["monthly_precipitation"], 1.220472);
+
+        // Asking for units overrides the profile
+        let (_, body) = get(
+            &app.state,
+            "/areas/murrin/climatology?profile_id=imperial&units=metric",
+            Some(KEY),
+        )
+        .await;
+        assert_eq!(body["months"][0]["avg_high"], 25.0);
+
+        let (status, _) = get(
+            &app.state,
+            "/areas/murrin/climatology?profile_id=missing",
+            Some(KEY),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn climatology_without_history_is_not_found() {
+        let app = test_app(keyed()).await;
+        let (status, body) = get(&app.state, "/areas/murrin/climatology", Some(KEY)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["detail"].as_str().unwrap().contains("no full month"));
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_key_are_unauthorized() {
+        let app = test_app(keyed()).await;
+        let (status, body) = get(
+            &app.state,
+            "/areas/nearby?lat=49.7&lng=-123.1&radius_km=5",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["status"], 401);
+    }
+
+    #[tokio::test]
+    async fn unknown_and_revoked_keys_are_unauthorized() {
+        let app = test_app(keyed()).await;
+        let uri = "/areas/nearby?lat=49.7&lng=-123.1&radius_km=5";
+        for key in ["gl_unknown", REVOKED_KEY] {
+            let (status, _) = get(&app.state, uri, Some(key)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn keys_over_their_rate_are_limited() {
+        let app = test_app(auth::AuthConfig::new(true, None, 60, 1)).await;
+        let uri = "/areas/nearby?lat=49.7&lng=-123.1&radius_km=5";
+        let (status, _) = get(&app.state, uri, Some(KEY)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let res = router(app.state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header("x-api-key", KEY)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()["retry-after"], "1");
+    }
+
+    #[tokio::test]
+    async fn keys_are_not_needed_when_turned_off() {
+        let app = test_app(auth::AuthConfig::new(false, None, 60, 20)).await;
+        let (status, _) = get(
+            &app.state,
+            "/areas/nearby?lat=49.7&lng=-123.1&radius_km=5",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn admin_endpoints_need_the_admin_token() {
+        let app = test_app(keyed()).await;
+        let (status, _) = get(&app.state, "/admin/keys", Some(KEY)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = get(&app.state, "/admin/keys", Some("admin-secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 2);
+
+        let app = test_app(auth::AuthConfig::new(true, None, 60, 20)).await;
+        let (status, _) = get(&app.state, "/admin/keys", Some("admin-secret")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
     }
 }
@@ -0,0 +1,113 @@
+use crate::{error::ApiError, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use datamodels::{
+    profile::{Preferences, Profile},
+    units::Units,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct ProfileBody {
+    name: Option<String>,
+    #[serde(default)]
+    preferences: Preferences,
+}
+
+// Preferences from a stored profile, with inline ones given as a json object taking
+// their place field by field. None when neither is given.
+pub async fn resolve_preferences(
+    state: &AppState,
+    profile_id: Option<&str>,
+    inline: Option<&str>,
+) -> Result<Option<Preferences>, ApiError> {
+    let stored = match profile_id {
+        Some(id) => Some(
+            state
+                .profiles
+                .profile_by_id(id)
+                .await?
+                .ok_or_else(|| ApiError::NotFound(format!("No profile {}", id)))?
+                .preferences,
+        ),
+        None => None,
+    };
+    let inline = match inline {
+        Some(json) => Some(
+            serde_json::from_str::<Preferences>(json)
+                .map_err(|err| ApiError::Validation(format!("invalid preferences: {}", err)))?,
+        ),
+        None => None,
+    };
+    let preferences = match (stored, inline) {
+        (Some(stored), Some(inline)) => stored.merged(&inline),
+        (stored, inline) => match stored.or(inline) {
+            Some(preferences) => preferences,
+            None => return Ok(None),
+        },
+    };
+    preferences.validate().map_err(ApiError::Validation)?;
+    Ok(Some(preferences))
+}
+
+// Units asked for on the request, then the ones in the preferences, then metric
+pub fn units_for(requested: Option<Units>, preferences: Option<&Preferences>) -> Units {
+    requested
+        .or_else(|| preferences.and_then(|preferences| preferences.units))
+        .unwrap_or_default()
+}
+
+pub async fn create_profile(
+    State(state): State<AppState>,
+    Json(body): Json<ProfileBody>,
+) -> Result<(StatusCode, Json<Profile>), ApiError> {
+    body.preferences.validate().map_err(ApiError::Validation)?;
+    let now = Utc::now();
+    let profile = Profile {
+        id: storage::new_profile_id(),
+        name: body.name,
+        preferences: body.preferences,
+        created_at: now,
+        updated_at: now,
+    };
+    state.profiles.save_profile(&profile).await?;
+    Ok((StatusCode::CREATED, Json(profile)))
+}
+
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Profile>, ApiError> {
+    state
+        .profiles
+        .profile_by_id(&id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("No profile {}", id)))
+}
+
+// Replaces the name and preferences of an existing profile
+pub async fn update_profile(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(body): Json<ProfileBody>,
+) -> Result<Json<Profile>, ApiError> {
+    body.preferences.validate().map_err(ApiError::Validation)?;
+    let existing = state
+        .profiles
+        .profile_by_id(&id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No profile {}", id)))?;
+    let profile = Profile {
+        name: body.name,
+        preferences: body.preferences,
+        updated_at: Utc::now(),
+        ..existing
+    };
+    state.profiles.save_profile(&profile).await?;
+    Ok(Json(profile))
+}
@@ -0,0 +1,384 @@
+use super::{ProviderError, WeatherProvider};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use datamodels::AreaForecast;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use storage::{CachedForecast, ForecastCacheRepository};
+
+// Coordinates are rounded to 0.01°, about a kilometre, which is finer than any
+// provider's grid
+const COORD_SCALE: f64 = 100.0;
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+#[derive(Clone, Debug)]
+pub struct ForecastCacheConfig {
+    // Replaces the provider's update interval when set
+    pub ttl: Option<Duration>,
+    pub max_entries: usize,
+    // Also share forecasts between processes through storage
+    pub shared: bool,
+}
+
+impl ForecastCacheConfig {
+    // Reads FORECAST_CACHE_TTL_SECS, FORECAST_CACHE_MAX_ENTRIES and FORECAST_CACHE_SHARED
+    pub fn from_env() -> Self {
+        Self {
+            ttl: std::env::var("FORECAST_CACHE_TTL_SECS")
+                .ok()
+                .and_then(|val| val.parse().ok())
+                .map(Duration::seconds),
+            max_entries: std::env::var("FORECAST_CACHE_MAX_ENTRIES")
+                .ok()
+                .and_then(|val| val.parse().ok())
+                .unwrap_or(DEFAULT_MAX_ENTRIES),
+            shared: std::env::var("FORECAST_CACHE_SHARED").is_ok_and(|val| val == "true"),
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct CacheMetrics {
+    hits: AtomicU64,
+    shared_hits: AtomicU64,
+    // Requests that waited for an identical one in flight and used its result
+    coalesced: AtomicU64,
+    misses: AtomicU64,
+    errors: AtomicU64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CacheMetricsSnapshot {
+    pub hits: u64,
+    pub shared_hits: u64,
+    pub coalesced: u64,
+    pub misses: u64,
+    pub errors: u64,
+    // Share of requests answered without calling the provider
+    pub hit_ratio: f64,
+    pub entries: usize,
+}
+
+impl CacheMetrics {
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, entries: usize) -> CacheMetricsSnapshot {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let shared_hits = self.shared_hits.load(Ordering::Relaxed);
+        let coalesced = self.coalesced.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let served = hits + shared_hits + coalesced;
+        let total = served + misses;
+        CacheMetricsSnapshot {
+            hits,
+            shared_hits,
+            coalesced,
+            misses,
+            errors: self.errors.load(Ordering::Relaxed),
+            hit_ratio: if total == 0 {
+                0.0
+            } else {
+                served as f64 / total as f64
+            },
+            entries,
+        }
+    }
+}
+
+struct Entry {
+    days: u8,
+    forecast: AreaForecast,
+    expires_at: DateTime<Utc>,
+}
+
+// Serves repeated forecasts for the same place from memory, and from storage when
+// shared, until the provider has published new data. Concurrent identical requests
+// wait for the first one instead of all calling the provider.
+pub struct CachedProvider {
+    inner: Arc<dyn WeatherProvider>,
+    config: ForecastCacheConfig,
+    shared: Option<Arc<dyn ForecastCacheRepository>>,
+    entries: Mutex<HashMap<String, Entry>>,
+    in_flight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    metrics: Arc<CacheMetrics>,
+}
+
+fn cache_key(provider: &str, lat: f64, lng: f64) -> String {
+    // Rounded to integers so -0.001 and 0.001 share a key
+    format!(
+        "{}:{}:{}",
+        provider,
+        (lat * COORD_SCALE).round() as i64,
+        (lng * COORD_SCALE).round() as i64
+    )
+}
+
+// A longer cached forecast serves shorter requests
+fn first_days(forecast: &AreaForecast, days: u8) -> AreaForecast {
+    let mut forecast = forecast.clone();
+    forecast.days.truncate(days as usize);
+    forecast
+}
+
+impl CachedProvider {
+    pub fn new(
+        inner: Arc<dyn WeatherProvider>,
+        config: ForecastCacheConfig,
+        shared: Option<Arc<dyn ForecastCacheRepository>>,
+    ) -> Self {
+        let shared = shared.filter(|_| config.shared);
+        Self {
+            inner,
+            config,
+            shared,
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            metrics: Arc::new(CacheMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> CacheMetricsSnapshot {
+        let entries = self.entries.lock().expect("cache lock poisoned").len();
+        self.metrics.snapshot(entries)
+    }
+
+    fn ttl(&self) -> Duration {
+        self.config
+            .ttl
+            .unwrap_or_else(|| self.inner.update_interval())
+    }
+
+    fn cached(&self, key: &str, days: u8) -> Option<AreaForecast> {
+        let entries = self.entries.lock().expect("cache lock poisoned");
+        entries
+            .get(key)
+            .filter(|entry| entry.days >= days && entry.expires_at > Utc::now())
+            .map(|entry| first_days(&entry.forecast, days))
+    }
+
+    fn store(&self, key: &str, days: u8, forecast: AreaForecast, expires_at: DateTime<Utc>) {
+        let mut entries = self.entries.lock().expect("cache lock poisoned");
+        if entries.len() >= self.config.max_entries {
+            let now = Utc::now();
+            entries.retain(|_, entry| entry.expires_at > now);
+            // Still full of live entries, make room by dropping the ones expiring soonest
+            if entries.len() >= self.config.max_entries {
+                let mut expiring: Vec<(DateTime<Utc>, String)> = entries
+                    .iter()
+                    .map(|(key, entry)| (entry.expires_at, key.clone()))
+                    .collect();
+                expiring.sort();
+                let excess = entries.len() + 1 - self.config.max_entries.max(1);
+                for (_, key) in expiring.into_iter().take(excess) {
+                    entries.remove(&key);
+                }
+            }
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                days,
+                forecast,
+                expires_at,
+            },
+        );
+    }
+
+    fn in_flight<'a>(&'a self, key: &'a str) -> InFlight<'a> {
+        let lock = self
+            .in_flight
+            .lock()
+            .expect("cache lock poisoned")
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        InFlight {
+            cache: self,
+            key,
+            lock,
+        }
+    }
+
+    async fn fetch(
+        &self,
+        key: &str,
+        lat: f64,
+        lng: f64,
+        days: u8,
+    ) -> Result<AreaForecast, ProviderError> {
+        // Another request for the same place may have filled the cache while this waited
+        if let Some(forecast) = self.cached(key, days) {
+            CacheMetrics::count(&self.metrics.coalesced);
+            return Ok(forecast);
+        }
+
+        if let Some(shared) = &self.shared {
+            match shared.cached_forecast(key).await {
+                Ok(Some(cached)) if cached.days >= days => {
+                    CacheMetrics::count(&self.metrics.shared_hits);
+                    let forecast = first_days(&cached.forecast, days);
+                    self.store(key, cached.days, cached.forecast, cached.expires_at);
+                    return Ok(forecast);
+                }
+                Ok(_) => {}
+                Err(err) => tracing::warn!("could not read shared forecast cache: {}", err),
+            }
+        }
+
+        CacheMetrics::count(&self.metrics.misses);
+        let forecast = match self.inner.forecast(lat, lng, days).await {
+            Ok(forecast) => forecast,
+            Err(err) => {
+                CacheMetrics::count(&self.metrics.errors);
+                return Err(err);
+            }
+        };
+        let expires_at = forecast.fetched_at + self.ttl();
+        if let Some(shared) = &self.shared {
+            let cached = CachedForecast {
+                key: key.to_string(),
+                days,
+                forecast: forecast.clone(),
+                expires_at,
+            };
+            if let Err(err) = shared.save_cached_forecast(&cached).await {
+                tracing::warn!("could not write shared forecast cache: {}", err);
+            }
+        }
+        self.store(key, days, forecast.clone(), expires_at);
+        Ok(forecast)
+    }
+}
+
+// A request's hold on the in flight entry for its key. The entry is released on drop,
+// so a request that is cancelled mid fetch doesn't leave it behind.
+struct InFlight<'a> {
+    cache: &'a CachedProvider,
+    key: &'a str,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        let mut in_flight = self.cache.in_flight.lock().expect("cache lock poisoned");
+        // Held by the map and this request only, nobody else is waiting
+        if Arc::strong_count(&self.lock) <= 2 {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for CachedProvider {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn update_interval(&self) -> Duration {
+        self.inner.update_interval()
+    }
+
+    async fn forecast(&self, lat: f64, lng: f64, days: u8) -> Result<AreaForecast, ProviderError> {
+        let key = cache_key(self.inner.name(), lat, lng);
+        if let Some(forecast) = self.cached(&key, days) {
+            CacheMetrics::count(&self.metrics.hits);
+            return Ok(forecast);
+        }
+
+        let in_flight = self.in_flight(&key);
+        let _guard = in_flight.lock.lock().await;
+        self.fetch(&key, lat, lng, days).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    // Answers every other call, the first one never finishes
+    #[derive(Default)]
+    struct StallsOnce {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl WeatherProvider for StallsOnce {
+        fn name(&self) -> &'static str {
+            "stalls"
+        }
+
+        async fn forecast(
+            &self,
+            lat: f64,
+            lng: f64,
+            _days: u8,
+        ) -> Result<AreaForecast, ProviderError> {
+            if self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                std::future::pending::<()>().await;
+            }
+            Ok(AreaForecast {
+                provider: "stalls".to_string(),
+                lat,
+                lng,
+                utc_offset_seconds: 0,
+                fetched_at: Utc::now(),
+                elevation_m: None,
+                current: None,
+                days: Vec::new(),
+            })
+        }
+    }
+
+    fn cache() -> CachedProvider {
+        CachedProvider::new(
+            Arc::new(StallsOnce::default()),
+            ForecastCacheConfig {
+                ttl: None,
+                max_entries: 10,
+                shared: false,
+            },
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn cancelled_fetches_release_their_key() {
+        let cache = cache();
+        let cancelled = tokio::time::timeout(
+            std::time::Duration::from_millis(10),
+            cache.forecast(1.0, 2.0, 3),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+
+        let forecast = cache.forecast(1.0, 2.0, 3).await.unwrap();
+        assert_eq!(forecast.provider, "stalls");
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn waiters_take_over_from_a_cancelled_leader() {
+        let cache = cache();
+        let mut leader = Box::pin(cache.forecast(1.0, 2.0, 3));
+        let mut follower = Box::pin(cache.forecast(1.0, 2.0, 3));
+        // Start the leader's fetch, then queue the follower behind it
+        assert!(futures::poll!(leader.as_mut()).is_pending());
+        assert!(futures::poll!(follower.as_mut()).is_pending());
+
+        drop(leader);
+        let forecast = follower.await.unwrap();
+        assert_eq!(forecast.lat, 1.0);
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+        assert_eq!(cache.metrics().misses, 2);
+    }
+}
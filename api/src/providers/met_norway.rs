@@ -0,0 +1,313 @@
+use super::{ProviderError, WeatherProvider};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use datamodels::{
+    units::{Length, Speed, Temperature},
+    AreaForecast, DailyForecast, Observation,
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const DEFAULT_BASE_URL: &str = "https://api.met.no/weatherapi/locationforecast/2.0";
+
+#[derive(Clone, Debug)]
+pub struct MetNorwayConfig {
+    pub base_url: String,
+    // MET Norway's terms require an identifying user agent with contact details
+    pub user_agent: String,
+}
+
+impl MetNorwayConfig {
+    // Reads MET_NORWAY_USER_AGENT (required) and MET_NORWAY_URL
+    pub fn from_env() -> Result<Self, ProviderError> {
+        let user_agent = std::env::var("MET_NORWAY_USER_AGENT").map_err(|_| {
+            ProviderError::Config("MET_NORWAY_USER_AGENT must be set as an env var".to_string())
+        })?;
+        let base_url =
+            std::env::var("MET_NORWAY_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+
+        Ok(Self {
+            base_url,
+            user_agent,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct MetResponse {
+    geometry: MetGeometry,
+    properties: MetProperties,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetGeometry {
+    // [lng, lat, altitude]
+    coordinates: Vec<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetProperties {
+    timeseries: Vec<MetTimestep>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetTimestep {
+    time: DateTime<Utc>,
+    data: MetData,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetData {
+    instant: MetInstant,
+    next_1_hours: Option<MetPeriod>,
+    next_6_hours: Option<MetPeriod>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetInstant {
+    details: MetInstantDetails,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetInstantDetails {
+    air_temperature: f64,
+    relative_humidity: Option<f64>,
+    // m/s
+    wind_speed: Option<f64>,
+    wind_speed_of_gust: Option<f64>,
+    wind_from_direction: Option<f64>,
+    cloud_area_fraction: Option<f64>,
+    ultraviolet_index_clear_sky: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetPeriod {
+    summary: Option<MetSummary>,
+    details: Option<MetPeriodDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetSummary {
+    symbol_code: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct MetPeriodDetails {
+    precipitation_amount: Option<f64>,
+    probability_of_precipitation: Option<f64>,
+}
+
+fn to_forecast(response: MetResponse, days: u8) -> AreaForecast {
+    let lng = response
+        .geometry
+        .coordinates
+        .first()
+        .copied()
+        .unwrap_or_default();
+    let lat = response
+        .geometry
+        .coordinates
+        .get(1)
+        .copied()
+        .unwrap_or_default();
+    // MET Norway only returns UTC, approximate the local day from the longitude
+    let utc_offset_seconds = (lng / 15.0).round() as i32 * 3600;
+    let offset = Duration::seconds(utc_offset_seconds as i64);
+
+    let mut hours = Vec::new();
+    for step in response.properties.timeseries {
+        let details = step.data.instant.details;
+        // Near term steps are hourly, later ones cover six hours
+        let (period, step_hours) = match (step.data.next_1_hours, step.data.next_6_hours) {
+            (Some(period), _) => (period, 1),
+            (None, Some(period)) => (period, 6),
+            (None, None) => continue,
+        };
+        let precip_mm = period
+            .details
+            .as_ref()
+            .and_then(|d| d.precipitation_amount)
+            .unwrap_or_default();
+        let chance_of_rain = period
+            .details
+            .as_ref()
+            .and_then(|d| d.probability_of_precipitation);
+        let condition = period.summary.map(|s| s.symbol_code);
+
+        // Spread six hour steps into hourly observations so days aggregate evenly
+        for hour in 0..step_hours {
+            hours.push(Observation {
+                time: (step.time + Duration::hours(hour)).naive_utc() + offset,
+                temp: Temperature::from_celsius(details.air_temperature),
+                feels_like: None,
+                humidity: details.relative_humidity.unwrap_or_default(),
+                wind: Speed::from_ms(details.wind_speed.unwrap_or_default()),
+                gust: details.wind_speed_of_gust.map(Speed::from_ms),
+                wind_degree: details.wind_from_direction,
+                precip: Length::from_mm(precip_mm / step_hours as f64),
+                snow: None,
+                cloud: details.cloud_area_fraction.unwrap_or_default(),
+                chance_of_rain,
+                uv: details.ultraviolet_index_clear_sky,
+                is_day: None,
+                condition: condition.clone(),
+                us_epa_index: None,
+            });
+        }
+    }
+
+    let current = hours.first().cloned();
+
+    let mut by_date: BTreeMap<NaiveDate, Vec<Observation>> = BTreeMap::new();
+    for hour in hours {
+        by_date.entry(hour.time.date()).or_default().push(hour);
+    }
+    let days = by_date
+        .into_iter()
+        .filter_map(|(date, hours)| DailyForecast::from_hours(date, hours))
+        .take(days as usize)
+        .collect();
+
+    AreaForecast {
+        provider: "met-norway".to_string(),
+        lat,
+        lng,
+        utc_offset_seconds,
+        fetched_at: Utc::now(),
+        elevation_m: response.geometry.coordinates.get(2).copied(),
+        current,
+        days,
+    }
+}
+
+// A complete response body, or the typed error for a failed status
+fn parse_forecast(
+    status: StatusCode,
+    body: String,
+    days: u8,
+) -> Result<AreaForecast, ProviderError> {
+    if !status.is_success() {
+        let message = if body.is_empty() { None } else { Some(body) };
+        return Err(ProviderError::from_status(status, message));
+    }
+
+    Ok(to_forecast(serde_json::from_str(&body)?, days))
+}
+
+#[derive(Clone)]
+pub struct MetNorwayProvider {
+    http: reqwest::Client,
+    config: MetNorwayConfig,
+}
+
+impl MetNorwayProvider {
+    pub fn new(config: MetNorwayConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for MetNorwayProvider {
+    fn name(&self) -> &'static str {
+        "met-norway"
+    }
+
+    // Forecasts are regenerated about every half hour, see the Expires header
+    fn update_interval(&self) -> Duration {
+        Duration::minutes(30)
+    }
+
+    async fn forecast(&self, lat: f64, lng: f64, days: u8) -> Result<AreaForecast, ProviderError> {
+        let url = format!("{}/complete", self.config.base_url.trim_end_matches('/'));
+        // The api rejects coordinates with more than four decimals
+        let response = self
+            .http
+            .get(url)
+            .header(reqwest::header::USER_AGENT, &self.config.user_agent)
+            .query(&[
+                ("lat", format!("{:.4}", lat)),
+                ("lon", format!("{:.4}", lng)),
+            ])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        parse_forecast(status, body, days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, Timelike};
+
+    const COMPLETE: &str = include_str!("../../tests/fixtures/met_norway_complete.json");
+
+    fn forecast(days: u8) -> AreaForecast {
+        parse_forecast(StatusCode::OK, COMPLETE.to_string(), days).unwrap()
+    }
+
+    #[test]
+    fn maps_recorded_forecast() {
+        let forecast = forecast(2);
+
+        assert_eq!(forecast.provider, "met-norway");
+        assert_eq!(forecast.lat, 59.91);
+        assert_eq!(forecast.lng, 10.75);
+        assert_eq!(forecast.elevation_m, Some(94.0));
+        // Local time is approximated from the longitude
+        assert_eq!(forecast.utc_offset_seconds, 3600);
+
+        let current = forecast.current.unwrap();
+        assert_eq!(current.time.hour(), 11);
+        assert_eq!(current.temp.celsius(), 8.0);
+        assert!((current.wind.kph() - 9.0).abs() < 1e-9);
+        assert!((current.gust.unwrap().kph() - 18.0).abs() < 1e-9);
+        assert_eq!(current.condition.as_deref(), Some("partlycloudy_day"));
+    }
+
+    #[test]
+    fn splits_six_hour_steps_into_hours() {
+        let forecast = forecast(2);
+
+        // The last step has no period and is dropped, leaving one day
+        assert_eq!(forecast.days.len(), 1);
+        let day = &forecast.days[0];
+        assert_eq!(day.date, NaiveDate::from_ymd_opt(2023, 3, 19).unwrap());
+        let hours: Vec<u32> = day.hours.iter().map(|hour| hour.time.hour()).collect();
+        assert_eq!(hours, vec![11, 12, 13, 14, 15, 16, 17, 18]);
+
+        // 1.2mm over six hours is spread evenly
+        for hour in &day.hours[2..] {
+            assert!((hour.precip.mm() - 0.2).abs() < 1e-9);
+            assert_eq!(hour.chance_of_rain, Some(50.0));
+            assert_eq!(hour.condition.as_deref(), Some("lightrain"));
+        }
+        // Hourly steps take the next hour's period over the six hour one
+        assert!((day.hours[0].precip.mm() - 0.4).abs() < 1e-9);
+        assert!((day.total_precip.mm() - 1.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn status_maps_to_typed_error() {
+        match parse_forecast(StatusCode::FORBIDDEN, "Missing User-Agent".to_string(), 1) {
+            Err(ProviderError::Forbidden(msg)) => assert_eq!(msg, "Missing User-Agent"),
+            other => panic!("expected Forbidden, got {:?}", other),
+        }
+        match parse_forecast(StatusCode::SERVICE_UNAVAILABLE, String::new(), 1) {
+            Err(err @ ProviderError::Status(StatusCode::SERVICE_UNAVAILABLE, None)) => {
+                assert!(err.is_retryable())
+            }
+            other => panic!("expected Status, got {:?}", other),
+        }
+        assert!(matches!(
+            parse_forecast(StatusCode::OK, "not json".to_string(), 1),
+            Err(ProviderError::Parse(_))
+        ));
+    }
+}
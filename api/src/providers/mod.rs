@@ -0,0 +1,175 @@
+mod cache;
+mod met_norway;
+mod open_meteo;
+mod open_meteo_archive;
+mod weather_api;
+
+use async_trait::async_trait;
+use chrono::{Duration, NaiveDate};
+use datamodels::{climate::HistoricalDay, AreaForecast};
+use reqwest::StatusCode;
+use std::{fmt, sync::Arc};
+
+pub use cache::{CacheMetricsSnapshot, CachedProvider, ForecastCacheConfig};
+pub use met_norway::{MetNorwayConfig, MetNorwayProvider};
+pub use open_meteo::{OpenMeteoConfig, OpenMeteoProvider};
+pub use open_meteo_archive::{OpenMeteoArchiveConfig, OpenMeteoArchiveProvider};
+pub use weather_api::{WeatherApiConfig, WeatherApiProvider};
+
+#[async_trait]
+pub trait WeatherProvider: Send + Sync {
+    // Short identifier stored alongside every forecast, e.g. "weatherapi"
+    fn name(&self) -> &'static str;
+
+    // How often the provider publishes new data, cached forecasts are reused for this long
+    fn update_interval(&self) -> Duration {
+        Duration::minutes(60)
+    }
+
+    async fn forecast(&self, lat: f64, lng: f64, days: u8) -> Result<AreaForecast, ProviderError>;
+}
+
+// Observed daily weather for past dates, used to build climatologies
+#[async_trait]
+pub trait HistoryProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Every day from start to end inclusive that the source has data for
+    async fn daily_history(
+        &self,
+        lat: f64,
+        lng: f64,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<HistoricalDay>, ProviderError>;
+}
+
+#[derive(Debug)]
+pub enum ProviderError {
+    Config(String),
+    Request(reqwest::Error),
+    // 400: bad query, e.g. no location found for the coordinates
+    BadRequest(String),
+    // 401: key missing or invalid
+    Unauthorized(String),
+    // 403: quota exceeded, key disabled or plan has no access
+    Forbidden(String),
+    Status(StatusCode, Option<String>),
+    Parse(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(msg) => write!(f, "invalid weather provider config: {}", msg),
+            Self::Request(err) => write!(f, "weather provider request failed: {}", err),
+            Self::BadRequest(msg) => write!(f, "weather provider bad request: {}", msg),
+            Self::Unauthorized(msg) => write!(f, "weather provider unauthorized: {}", msg),
+            Self::Forbidden(msg) => write!(f, "weather provider forbidden: {}", msg),
+            Self::Status(status, Some(msg)) => {
+                write!(f, "weather provider returned {}: {}", status, msg)
+            }
+            Self::Status(status, None) => write!(f, "weather provider returned {}", status),
+            Self::Parse(msg) => write!(f, "could not parse weather provider response: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err)
+    }
+}
+
+impl From<serde_json::Error> for ProviderError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Parse(err.to_string())
+    }
+}
+
+impl ProviderError {
+    // Failures that may go away on their own, as opposed to bad config or input
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request(_) => true,
+            Self::Status(status, _) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+
+    // Map a non success status and the provider's own error message into a typed error
+    fn from_status(status: StatusCode, message: Option<String>) -> Self {
+        match (status, message) {
+            (StatusCode::BAD_REQUEST, Some(msg)) => Self::BadRequest(msg),
+            (StatusCode::UNAUTHORIZED, Some(msg)) => Self::Unauthorized(msg),
+            (StatusCode::FORBIDDEN, Some(msg)) => Self::Forbidden(msg),
+            (status, msg) => Self::Status(status, msg),
+        }
+    }
+}
+
+// Picks the backend from WEATHER_PROVIDER: weatherapi (default), open-meteo or met-norway
+pub fn provider_from_env() -> Result<Arc<dyn WeatherProvider>, ProviderError> {
+    let provider = std::env::var("WEATHER_PROVIDER").unwrap_or_else(|_| "weatherapi".to_string());
+    let provider: Arc<dyn WeatherProvider> = match provider.as_str() {
+        "weatherapi" => Arc::new(WeatherApiProvider::new(WeatherApiConfig::from_env()?)),
+        "open-meteo" => Arc::new(OpenMeteoProvider::new(OpenMeteoConfig::from_env())),
+        "met-norway" => Arc::new(MetNorwayProvider::new(MetNorwayConfig::from_env()?)),
+        other => {
+            return Err(ProviderError::Config(format!(
+                "unknown WEATHER_PROVIDER {}, expected weatherapi, open-meteo or met-norway",
+                other
+            )))
+        }
+    };
+    Ok(provider)
+}
+
+// Open-Meteo's archive API is the only history source, CLIMATE_ARCHIVE_URL can point it
+// at a compatible server or a local stub
+pub fn history_provider_from_env() -> Arc<dyn HistoryProvider> {
+    Arc::new(OpenMeteoArchiveProvider::new(
+        OpenMeteoArchiveConfig::from_env(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_status_uses_message_for_known_statuses() {
+        let msg = || Some("why".to_string());
+        assert!(matches!(
+            ProviderError::from_status(StatusCode::BAD_REQUEST, msg()),
+            ProviderError::BadRequest(_)
+        ));
+        assert!(matches!(
+            ProviderError::from_status(StatusCode::UNAUTHORIZED, msg()),
+            ProviderError::Unauthorized(_)
+        ));
+        assert!(matches!(
+            ProviderError::from_status(StatusCode::FORBIDDEN, msg()),
+            ProviderError::Forbidden(_)
+        ));
+        // Without the provider's reason there is nothing better than the status
+        assert!(matches!(
+            ProviderError::from_status(StatusCode::UNAUTHORIZED, None),
+            ProviderError::Status(StatusCode::UNAUTHORIZED, None)
+        ));
+    }
+
+    #[test]
+    fn only_transient_failures_are_retryable() {
+        assert!(ProviderError::Status(StatusCode::INTERNAL_SERVER_ERROR, None).is_retryable());
+        assert!(ProviderError::Status(StatusCode::TOO_MANY_REQUESTS, None).is_retryable());
+        assert!(!ProviderError::Status(StatusCode::NOT_FOUND, None).is_retryable());
+        assert!(!ProviderError::BadRequest("no location".to_string()).is_retryable());
+        assert!(!ProviderError::Config("no key".to_string()).is_retryable());
+        assert!(!ProviderError::Parse("bad json".to_string()).is_retryable());
+    }
+}
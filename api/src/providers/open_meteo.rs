@@ -0,0 +1,346 @@
+use super::{ProviderError, WeatherProvider};
+use async_trait::async_trait;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use datamodels::{
+    units::{Length, Speed, Temperature},
+    AreaForecast, DailyForecast, Observation,
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+const DEFAULT_BASE_URL: &str = "https://api.open-meteo.com/v1";
+
+const CURRENT_FIELDS: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,\
+    precipitation,snowfall,cloud_cover,wind_speed_10m,wind_gusts_10m,wind_direction_10m,is_day";
+const HOURLY_FIELDS: &str = "temperature_2m,apparent_temperature,relative_humidity_2m,\
+    precipitation,precipitation_probability,snowfall,cloud_cover,wind_speed_10m,wind_gusts_10m,\
+    wind_direction_10m,uv_index,is_day";
+const DAILY_FIELDS: &str = "temperature_2m_max,temperature_2m_min,precipitation_sum,snowfall_sum,\
+    precipitation_probability_max,wind_speed_10m_max,uv_index_max,sunrise,sunset";
+
+// Open-Meteo returns local times without seconds or offset, e.g. "2023-03-19T13:00"
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+#[derive(Clone, Debug)]
+pub struct OpenMeteoConfig {
+    pub base_url: String,
+}
+
+impl OpenMeteoConfig {
+    // Reads OPEN_METEO_URL, no key is needed for the free tier
+    pub fn from_env() -> Self {
+        Self {
+            base_url: std::env::var("OPEN_METEO_URL")
+                .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct OpenMeteoError {
+    pub(super) reason: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoResponse {
+    latitude: f64,
+    longitude: f64,
+    utc_offset_seconds: i32,
+    elevation: Option<f64>,
+    current: Option<OpenMeteoCurrent>,
+    hourly: OpenMeteoHourly,
+    daily: OpenMeteoDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoCurrent {
+    time: String,
+    temperature_2m: f64,
+    apparent_temperature: Option<f64>,
+    relative_humidity_2m: f64,
+    precipitation: f64,
+    snowfall: Option<f64>,
+    cloud_cover: f64,
+    wind_speed_10m: f64,
+    wind_gusts_10m: Option<f64>,
+    wind_direction_10m: Option<f64>,
+    is_day: Option<u8>,
+}
+
+// Hourly and daily values are returned as parallel arrays that can contain nulls
+#[derive(Deserialize, Debug)]
+struct OpenMeteoHourly {
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f64>>,
+    apparent_temperature: Vec<Option<f64>>,
+    relative_humidity_2m: Vec<Option<f64>>,
+    precipitation: Vec<Option<f64>>,
+    precipitation_probability: Vec<Option<f64>>,
+    snowfall: Vec<Option<f64>>,
+    cloud_cover: Vec<Option<f64>>,
+    wind_speed_10m: Vec<Option<f64>>,
+    wind_gusts_10m: Vec<Option<f64>>,
+    wind_direction_10m: Vec<Option<f64>>,
+    uv_index: Vec<Option<f64>>,
+    is_day: Vec<Option<u8>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoDaily {
+    time: Vec<String>,
+    temperature_2m_max: Vec<Option<f64>>,
+    temperature_2m_min: Vec<Option<f64>>,
+    precipitation_sum: Vec<Option<f64>>,
+    snowfall_sum: Vec<Option<f64>>,
+    precipitation_probability_max: Vec<Option<f64>>,
+    wind_speed_10m_max: Vec<Option<f64>>,
+    uv_index_max: Vec<Option<f64>>,
+    sunrise: Vec<Option<String>>,
+    sunset: Vec<Option<String>>,
+}
+
+fn at<T: Copy>(values: &[Option<T>], idx: usize) -> Option<T> {
+    values.get(idx).copied().flatten()
+}
+
+fn parse_time(val: &str) -> Result<NaiveDateTime, ProviderError> {
+    NaiveDateTime::parse_from_str(val, TIME_FORMAT)
+        .map_err(|err| ProviderError::Parse(format!("invalid time {}: {}", val, err)))
+}
+
+impl OpenMeteoHourly {
+    fn observation(&self, idx: usize) -> Result<Option<Observation>, ProviderError> {
+        // Skip hours the model has no temperature for rather than inventing one
+        let temp = match at(&self.temperature_2m, idx) {
+            Some(val) => Temperature::from_celsius(val),
+            None => return Ok(None),
+        };
+
+        Ok(Some(Observation {
+            time: parse_time(&self.time[idx])?,
+            temp,
+            feels_like: at(&self.apparent_temperature, idx).map(Temperature::from_celsius),
+            humidity: at(&self.relative_humidity_2m, idx).unwrap_or_default(),
+            wind: Speed::from_kph(at(&self.wind_speed_10m, idx).unwrap_or_default()),
+            gust: at(&self.wind_gusts_10m, idx).map(Speed::from_kph),
+            wind_degree: at(&self.wind_direction_10m, idx),
+            precip: Length::from_mm(at(&self.precipitation, idx).unwrap_or_default()),
+            snow: at(&self.snowfall, idx).map(Length::from_cm),
+            cloud: at(&self.cloud_cover, idx).unwrap_or_default(),
+            chance_of_rain: at(&self.precipitation_probability, idx),
+            uv: at(&self.uv_index, idx),
+            is_day: at(&self.is_day, idx).map(|val| val == 1),
+            condition: None,
+            us_epa_index: None,
+        }))
+    }
+}
+
+impl TryFrom<OpenMeteoResponse> for AreaForecast {
+    type Error = ProviderError;
+
+    fn try_from(response: OpenMeteoResponse) -> Result<Self, Self::Error> {
+        let current = match response.current {
+            Some(current) => Some(Observation {
+                time: parse_time(&current.time)?,
+                temp: Temperature::from_celsius(current.temperature_2m),
+                feels_like: current.apparent_temperature.map(Temperature::from_celsius),
+                humidity: current.relative_humidity_2m,
+                wind: Speed::from_kph(current.wind_speed_10m),
+                gust: current.wind_gusts_10m.map(Speed::from_kph),
+                wind_degree: current.wind_direction_10m,
+                precip: Length::from_mm(current.precipitation),
+                snow: current.snowfall.map(Length::from_cm),
+                cloud: current.cloud_cover,
+                chance_of_rain: None,
+                uv: None,
+                is_day: current.is_day.map(|val| val == 1),
+                condition: None,
+                us_epa_index: None,
+            }),
+            None => None,
+        };
+
+        let mut hours = Vec::with_capacity(response.hourly.time.len());
+        for idx in 0..response.hourly.time.len() {
+            if let Some(hour) = response.hourly.observation(idx)? {
+                hours.push(hour);
+            }
+        }
+
+        let daily = response.daily;
+        let mut days = Vec::with_capacity(daily.time.len());
+        for (idx, date) in daily.time.iter().enumerate() {
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|err| ProviderError::Parse(format!("invalid date {}: {}", date, err)))?;
+            let day_hours: Vec<Observation> = hours
+                .iter()
+                .filter(|hour| hour.time.date() == date)
+                .cloned()
+                .collect();
+            let mut day = match DailyForecast::from_hours(date, day_hours) {
+                Some(day) => day,
+                None => continue,
+            };
+
+            // Prefer the provider's own daily aggregates where they are present
+            if let Some(val) = at(&daily.temperature_2m_max, idx) {
+                day.max_temp = Temperature::from_celsius(val);
+            }
+            if let Some(val) = at(&daily.temperature_2m_min, idx) {
+                day.min_temp = Temperature::from_celsius(val);
+            }
+            if let Some(val) = at(&daily.precipitation_sum, idx) {
+                day.total_precip = Length::from_mm(val);
+            }
+            if let Some(val) = at(&daily.snowfall_sum, idx) {
+                day.total_snow = Length::from_cm(val);
+            }
+            if let Some(val) = at(&daily.wind_speed_10m_max, idx) {
+                day.max_wind = Speed::from_kph(val);
+            }
+            day.chance_of_rain =
+                at(&daily.precipitation_probability_max, idx).or(day.chance_of_rain);
+            day.uv = at(&daily.uv_index_max, idx).or(day.uv);
+            day.sunrise = daily
+                .sunrise
+                .get(idx)
+                .and_then(|val| val.as_deref())
+                .and_then(|val| parse_time(val).ok())
+                .map(|time| time.time());
+            day.sunset = daily
+                .sunset
+                .get(idx)
+                .and_then(|val| val.as_deref())
+                .and_then(|val| parse_time(val).ok())
+                .map(|time| time.time());
+            days.push(day);
+        }
+
+        Ok(AreaForecast {
+            provider: "open-meteo".to_string(),
+            lat: response.latitude,
+            lng: response.longitude,
+            utc_offset_seconds: response.utc_offset_seconds,
+            fetched_at: Utc::now(),
+            elevation_m: response.elevation,
+            current,
+            days,
+        })
+    }
+}
+
+// A forecast response body, or the typed error for a failed status
+fn parse_forecast(status: StatusCode, body: &str) -> Result<AreaForecast, ProviderError> {
+    if !status.is_success() {
+        let message = serde_json::from_str::<OpenMeteoError>(body)
+            .ok()
+            .map(|err| err.reason);
+        return Err(ProviderError::from_status(status, message));
+    }
+
+    serde_json::from_str::<OpenMeteoResponse>(body)?.try_into()
+}
+
+#[derive(Clone)]
+pub struct OpenMeteoProvider {
+    http: reqwest::Client,
+    config: OpenMeteoConfig,
+}
+
+impl OpenMeteoProvider {
+    pub fn new(config: OpenMeteoConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for OpenMeteoProvider {
+    fn name(&self) -> &'static str {
+        "open-meteo"
+    }
+
+    async fn forecast(&self, lat: f64, lng: f64, days: u8) -> Result<AreaForecast, ProviderError> {
+        let url = format!("{}/forecast", self.config.base_url.trim_end_matches('/'));
+        let response = self
+            .http
+            .get(url)
+            .query(&[
+                ("latitude", lat.to_string()),
+                ("longitude", lng.to_string()),
+                ("forecast_days", days.to_string()),
+                ("timezone", "auto".to_string()),
+                ("current", CURRENT_FIELDS.to_string()),
+                ("hourly", HOURLY_FIELDS.to_string()),
+                ("daily", DAILY_FIELDS.to_string()),
+            ])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        parse_forecast(status, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    const FORECAST: &str = include_str!("../../tests/fixtures/open_meteo_forecast.json");
+    const ERROR: &str = include_str!("../../tests/fixtures/open_meteo_error.json");
+
+    #[test]
+    fn maps_recorded_forecast() {
+        let forecast = parse_forecast(StatusCode::OK, FORECAST).unwrap();
+
+        assert_eq!(forecast.provider, "open-meteo");
+        assert_eq!(forecast.utc_offset_seconds, 3600);
+        assert_eq!(forecast.elevation_m, Some(812.0));
+
+        let current = forecast.current.unwrap();
+        assert_eq!(current.temp.celsius(), 13.7);
+        assert!((current.wind.kph() - 11.5).abs() < 1e-9);
+        assert_eq!(current.is_day, Some(true));
+
+        assert_eq!(forecast.days.len(), 2);
+        let first = &forecast.days[0];
+        assert_eq!(first.hours.len(), 4);
+        // The provider's own daily aggregates win over ones built from the hours
+        assert_eq!(first.max_temp.celsius(), 14.6);
+        assert_eq!(first.min_temp.celsius(), 3.8);
+        assert_eq!(first.sunrise, NaiveTime::from_hms_opt(6, 58, 0));
+        assert_eq!(first.sunset, NaiveTime::from_hms_opt(19, 4, 0));
+        assert_eq!(first.chance_of_rain, Some(20.0));
+    }
+
+    #[test]
+    fn skips_hours_without_temperature() {
+        let forecast = parse_forecast(StatusCode::OK, FORECAST).unwrap();
+        let second = &forecast.days[1];
+
+        assert_eq!(second.hours.len(), 3);
+        // The daily max is null, so it comes from the remaining hours
+        assert_eq!(second.max_temp.celsius(), 12.0);
+        assert_eq!(second.min_temp.celsius(), 4.9);
+        assert!((second.total_precip.mm() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn error_reason_becomes_typed_error() {
+        match parse_forecast(StatusCode::BAD_REQUEST, ERROR) {
+            Err(ProviderError::BadRequest(msg)) => assert!(msg.starts_with("Latitude must be")),
+            other => panic!("expected BadRequest, got {:?}", other),
+        }
+        match parse_forecast(StatusCode::TOO_MANY_REQUESTS, "") {
+            Err(err @ ProviderError::Status(StatusCode::TOO_MANY_REQUESTS, None)) => {
+                assert!(err.is_retryable())
+            }
+            other => panic!("expected Status, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use super::{open_meteo::OpenMeteoError, HistoryProvider, ProviderError};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use datamodels::{
+    climate::HistoricalDay,
+    units::{Length, Speed, Temperature},
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+const DEFAULT_BASE_URL: &str = "https://archive-api.open-meteo.com/v1";
+
+const DAILY_FIELDS: &str = "temperature_2m_max,temperature_2m_min,precipitation_sum,snowfall_sum,\
+    relative_humidity_2m_mean,wind_speed_10m_max";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Debug)]
+pub struct OpenMeteoArchiveConfig {
+    pub base_url: String,
+}
+
+impl OpenMeteoArchiveConfig {
+    // Reads CLIMATE_ARCHIVE_URL
+    pub fn from_env() -> Self {
+        Self {
+            base_url: std::env::var("CLIMATE_ARCHIVE_URL")
+                .unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ArchiveResponse {
+    daily: ArchiveDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct ArchiveDaily {
+    time: Vec<String>,
+    temperature_2m_max: Vec<Option<f64>>,
+    temperature_2m_min: Vec<Option<f64>>,
+    precipitation_sum: Vec<Option<f64>>,
+    #[serde(default)]
+    snowfall_sum: Vec<Option<f64>>,
+    #[serde(default)]
+    relative_humidity_2m_mean: Vec<Option<f64>>,
+    #[serde(default)]
+    wind_speed_10m_max: Vec<Option<f64>>,
+}
+
+fn at(values: &[Option<f64>], idx: usize) -> Option<f64> {
+    values.get(idx).copied().flatten()
+}
+
+impl TryFrom<ArchiveDaily> for Vec<HistoricalDay> {
+    type Error = ProviderError;
+
+    fn try_from(daily: ArchiveDaily) -> Result<Self, Self::Error> {
+        let mut days = Vec::with_capacity(daily.time.len());
+        for (idx, date) in daily.time.iter().enumerate() {
+            let date = NaiveDate::parse_from_str(date, DATE_FORMAT)
+                .map_err(|err| ProviderError::Parse(format!("invalid date {}: {}", date, err)))?;
+            // The most recent days are null until the reanalysis catches up
+            let (Some(max_temp), Some(min_temp), Some(precip)) = (
+                at(&daily.temperature_2m_max, idx),
+                at(&daily.temperature_2m_min, idx),
+                at(&daily.precipitation_sum, idx),
+            ) else {
+                continue;
+            };
+            days.push(HistoricalDay {
+                date,
+                max_temp: Temperature::from_celsius(max_temp),
+                min_temp: Temperature::from_celsius(min_temp),
+                precip: Length::from_mm(precip),
+                snow: Length::from_cm(at(&daily.snowfall_sum, idx).unwrap_or_default()),
+                humidity: at(&daily.relative_humidity_2m_mean, idx),
+                max_wind: at(&daily.wind_speed_10m_max, idx).map(Speed::from_kph),
+            });
+        }
+        Ok(days)
+    }
+}
+
+// A history response body, or the typed error for a failed status
+fn parse_history(status: StatusCode, body: &str) -> Result<Vec<HistoricalDay>, ProviderError> {
+    if !status.is_success() {
+        let message = serde_json::from_str::<OpenMeteoError>(body)
+            .ok()
+            .map(|err| err.reason);
+        return Err(ProviderError::from_status(status, message));
+    }
+
+    serde_json::from_str::<ArchiveResponse>(body)?
+        .daily
+        .try_into()
+}
+
+#[derive(Clone)]
+pub struct OpenMeteoArchiveProvider {
+    http: reqwest::Client,
+    config: OpenMeteoArchiveConfig,
+}
+
+impl OpenMeteoArchiveProvider {
+    pub fn new(config: OpenMeteoArchiveConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            config,
+        }
+    }
+}
+
+#[async_trait]
+impl HistoryProvider for OpenMeteoArchiveProvider {
+    fn name(&self) -> &'static str {
+        "open-meteo-archive"
+    }
+
+    async fn daily_history(
+        &self,
+        lat: f64,
+        lng: f64,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<HistoricalDay>, ProviderError> {
+        let url = format!("{}/archive", self.config.base_url.trim_end_matches('/'));
+        let response = self
+            .http
+            .get(url)
+            .query(&[
+                ("latitude", lat.to_string()),
+                ("longitude", lng.to_string()),
+                ("start_date", start.format(DATE_FORMAT).to_string()),
+                ("end_date", end.format(DATE_FORMAT).to_string()),
+                ("timezone", "auto".to_string()),
+                ("daily", DAILY_FIELDS.to_string()),
+            ])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        parse_history(status, &body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = include_str!("../../tests/fixtures/open_meteo_archive.json");
+    const ERROR: &str = include_str!("../../tests/fixtures/open_meteo_error.json");
+
+    #[test]
+    fn maps_recorded_history() {
+        let days = parse_history(StatusCode::OK, HISTORY).unwrap();
+
+        assert_eq!(days.len(), 2);
+        let first = &days[0];
+        assert_eq!(first.date, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+        assert_eq!(first.max_temp.celsius(), 6.2);
+        assert_eq!(first.min_temp.celsius(), -1.4);
+        assert!((first.precip.mm() - 3.5).abs() < 1e-9);
+        assert!((first.snow.cm() - 1.4).abs() < 1e-9);
+        assert_eq!(first.humidity, Some(87.0));
+        assert!((first.max_wind.unwrap().kph() - 18.4).abs() < 1e-9);
+
+        // Optional fields that are null stay unknown
+        let second = &days[1];
+        assert_eq!(second.snow, Length::default());
+        assert_eq!(second.humidity, None);
+        assert_eq!(second.max_wind, None);
+    }
+
+    #[test]
+    fn skips_days_the_reanalysis_has_not_reached() {
+        let days = parse_history(StatusCode::OK, HISTORY).unwrap();
+        assert!(days
+            .iter()
+            .all(|day| day.date != NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()));
+    }
+
+    #[test]
+    fn bad_dates_are_parse_errors() {
+        let body = HISTORY.replace("2023-01-02", "2023-13-02");
+        assert!(matches!(
+            parse_history(StatusCode::OK, &body),
+            Err(ProviderError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn error_reason_becomes_typed_error() {
+        match parse_history(StatusCode::BAD_REQUEST, ERROR) {
+            Err(ProviderError::BadRequest(msg)) => assert!(msg.starts_with("Latitude must be")),
+            other => panic!("expected BadRequest, got {:?}", other),
+        }
+    }
+}
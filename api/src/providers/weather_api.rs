@@ -0,0 +1,186 @@
+use super::{ProviderError, WeatherProvider};
+use async_trait::async_trait;
+use chrono::Duration;
+use datamodels::{AreaForecast, WeatherResponse};
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+const DEFAULT_BASE_URL: &str = "https://api.weatherapi.com/v1";
+
+#[derive(Clone, Debug)]
+pub struct WeatherApiConfig {
+    pub base_url: String,
+    pub api_key: String,
+}
+
+impl WeatherApiConfig {
+    // Reads WEATHER_API_KEY (required) and WEATHER_API_URL
+    pub fn from_env() -> Result<Self, ProviderError> {
+        let api_key = std::env::var("WEATHER_API_KEY").map_err(|_| {
+            ProviderError::Config("WEATHER_API_KEY must be set as an env var".to_string())
+        })?;
+        let base_url =
+            std::env::var("WEATHER_API_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string());
+
+        Ok(Self { base_url, api_key })
+    }
+}
+
+// Error body returned by weatherapi.com, e.g. {"error": {"code": 1006, "message": "..."}}
+#[derive(Deserialize, Debug)]
+struct WeatherApiErrorBody {
+    code: u32,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorEnvelope {
+    error: WeatherApiErrorBody,
+}
+
+// A forecast.json response body, or the typed error for a failed status
+fn parse_forecast(status: StatusCode, body: &str) -> Result<WeatherResponse, ProviderError> {
+    if !status.is_success() {
+        let message = serde_json::from_str::<ErrorEnvelope>(body)
+            .ok()
+            .map(|envelope| format!("({}) {}", envelope.error.code, envelope.error.message));
+        return Err(ProviderError::from_status(status, message));
+    }
+
+    Ok(serde_json::from_str(body)?)
+}
+
+#[derive(Clone)]
+pub struct WeatherApiProvider {
+    http: reqwest::Client,
+    config: WeatherApiConfig,
+}
+
+impl WeatherApiProvider {
+    pub fn new(config: WeatherApiConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            config,
+        }
+    }
+
+    async fn raw_forecast(
+        &self,
+        lat: f64,
+        lng: f64,
+        days: u8,
+    ) -> Result<WeatherResponse, ProviderError> {
+        let url = format!(
+            "{}/forecast.json",
+            self.config.base_url.trim_end_matches('/')
+        );
+        let response = self
+            .http
+            .get(url)
+            .query(&[
+                ("key", self.config.api_key.clone()),
+                ("q", format!("{},{}", lat, lng)),
+                ("days", days.to_string()),
+                ("aqi", "yes".to_string()),
+                ("alerts", "no".to_string()),
+            ])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await?;
+        parse_forecast(status, &body)
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for WeatherApiProvider {
+    fn name(&self) -> &'static str {
+        "weatherapi"
+    }
+
+    // Current conditions are refreshed every 15 minutes
+    fn update_interval(&self) -> Duration {
+        Duration::minutes(15)
+    }
+
+    async fn forecast(&self, lat: f64, lng: f64, days: u8) -> Result<AreaForecast, ProviderError> {
+        Ok(self.raw_forecast(lat, lng, days).await?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+
+    const FORECAST: &str = include_str!("../../tests/fixtures/weatherapi_forecast.json");
+    const ERROR: &str = include_str!("../../tests/fixtures/weatherapi_error.json");
+
+    #[test]
+    fn maps_recorded_forecast() {
+        let forecast: AreaForecast = parse_forecast(StatusCode::OK, FORECAST).unwrap().into();
+
+        assert_eq!(forecast.provider, "weatherapi");
+        assert_eq!(forecast.lat, 49.7);
+        assert_eq!(forecast.lng, -123.15);
+        // Local 13:00 at 20:00 UTC
+        assert_eq!(forecast.utc_offset_seconds, -7 * 3600);
+
+        let current = forecast.current.unwrap();
+        assert_eq!(current.temp.celsius(), 9.0);
+        assert_eq!(current.feels_like.unwrap().celsius(), 7.4);
+        assert!((current.gust.unwrap().kph() - 18.4).abs() < 1e-9);
+        assert_eq!(current.is_day, Some(true));
+        assert_eq!(current.us_epa_index, Some(1));
+
+        assert_eq!(forecast.days.len(), 1);
+        let day = &forecast.days[0];
+        assert_eq!(day.date, NaiveDate::from_ymd_opt(2023, 3, 19).unwrap());
+        assert_eq!(day.max_temp.celsius(), 11.3);
+        assert_eq!(day.min_temp.celsius(), 2.1);
+        assert!((day.total_precip.mm() - 1.2).abs() < 1e-9);
+        assert_eq!(day.chance_of_rain, Some(40.0));
+        assert_eq!(day.sunrise, NaiveTime::from_hms_opt(7, 16, 0));
+        assert_eq!(day.sunset, NaiveTime::from_hms_opt(19, 22, 0));
+
+        assert_eq!(day.hours.len(), 2);
+        assert_eq!(day.hours[0].is_day, Some(false));
+        assert_eq!(day.hours[0].us_epa_index, None);
+        assert_eq!(day.hours[1].is_day, Some(true));
+        assert_eq!(day.hours[1].us_epa_index, Some(2));
+        assert!((day.hours[1].precip.mm() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn error_body_becomes_typed_error() {
+        match parse_forecast(StatusCode::FORBIDDEN, ERROR) {
+            Err(ProviderError::Forbidden(msg)) => {
+                assert_eq!(msg, "(2008) API key has been disabled.")
+            }
+            other => panic!("expected Forbidden, got {:?}", other),
+        }
+        match parse_forecast(StatusCode::BAD_REQUEST, ERROR) {
+            Err(ProviderError::BadRequest(_)) => {}
+            other => panic!("expected BadRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn status_without_error_body_keeps_status() {
+        match parse_forecast(StatusCode::BAD_GATEWAY, "<html>bad gateway</html>") {
+            Err(err @ ProviderError::Status(StatusCode::BAD_GATEWAY, None)) => {
+                assert!(err.is_retryable())
+            }
+            other => panic!("expected Status, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unreadable_body_is_parse_error() {
+        assert!(matches!(
+            parse_forecast(StatusCode::OK, "{\"location\": {}}"),
+            Err(ProviderError::Parse(_))
+        ));
+    }
+}
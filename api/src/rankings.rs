@@ -0,0 +1,465 @@
+use crate::{error::ApiError, profiles, weather_data_model, AppState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use datamodels::{
+    climbing::{Discipline, RockType},
+    geo::{GeoPoint, Latitude, Longitude},
+    profile::Preferences,
+    scoring::{Factor, FactorScore, ScoredDay},
+    units::{Length, UnitLabels, Units},
+    Area, AreaWeather,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use storage::WeatherRepository;
+
+// Providers only forecast about two weeks ahead
+pub(crate) const MAX_FORECAST_DAYS: i64 = 14;
+// Forecasts fetched in parallel while ranking
+const RANKING_CONCURRENCY: usize = 8;
+const DEFAULT_MAX_AREAS: usize = 100;
+const DEFAULT_LIMIT: usize = 20;
+const TOP_FACTORS: usize = 3;
+const DEFAULT_STORED_MAX_AGE_MINUTES: i64 = 180;
+// Half the earth's circumference, reaches every area in a region from any origin
+const ANYWHERE_KM: f64 = 20_038.0;
+// How far from an origin areas are ranked when no max_distance_km is given, and the
+// furthest that can be asked for
+const DEFAULT_MAX_DISTANCE_KM: f64 = 250.0;
+const MAX_DISTANCE_KM: f64 = 1_000.0;
+
+#[derive(Deserialize, Debug)]
+pub struct RankingParams {
+    from: NaiveDate,
+    to: NaiveDate,
+    lat: Option<Latitude>,
+    lng: Option<Longitude>,
+    max_distance_km: Option<f64>,
+    // Only rank crags inside this OpenBeta area
+    region: Option<String>,
+    // Only rank crags with climbs of this discipline
+    discipline: Option<Discipline>,
+    min_climbs: Option<u32>,
+    limit: Option<usize>,
+    // Score for a stored profile, and or inline preferences as a json object
+    profile_id: Option<String>,
+    preferences: Option<String>,
+    // The profile's units, or metric, when not given
+    units: Option<Units>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RankedFactor {
+    pub factor: Factor,
+    // Average factor score over the date range, 0.0 to 1.0
+    pub score: f64,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DaySummary {
+    pub date: NaiveDate,
+    pub score: f64,
+    pub max_temperature: f64,
+    pub min_temperature: f64,
+    pub precipitation: f64,
+    pub max_wind_speed: f64,
+    pub condition: Option<String>,
+    pub wet_until: Option<NaiveDateTime>,
+    pub do_not_climb: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RankedArea {
+    pub uuid: Option<String>,
+    pub area_name: String,
+    pub path: String,
+    pub total_climbs: Option<u32>,
+    pub rock_type: Option<RockType>,
+    pub units: UnitLabels,
+    pub elevation: Option<f64>,
+    pub lat: f64,
+    pub lng: f64,
+    pub distance: Option<f64>,
+    pub score: f64,
+    pub top_factors: Vec<RankedFactor>,
+    pub forecast: Vec<DaySummary>,
+}
+
+fn bad_request(msg: &str) -> ApiError {
+    ApiError::Validation(msg.to_string())
+}
+
+// Distances on a request must be real, positive and no further than max_km
+pub(crate) fn check_distance_km(name: &str, km: f64, max_km: f64) -> Result<f64, ApiError> {
+    if !km.is_finite() || km <= 0.0 {
+        return Err(bad_request(&format!("{} must be a positive number", name)));
+    }
+    if km > max_km {
+        return Err(bad_request(&format!("{} must be at most {}", name, max_km)));
+    }
+    Ok(km)
+}
+
+// Average each factor over the days and keep the ones that hurt most
+fn top_factors(days: &[ScoredDay], units: Units) -> Vec<RankedFactor> {
+    let mut by_factor: HashMap<Factor, Vec<&FactorScore>> = HashMap::new();
+    for day in days {
+        for factor in &day.score.factors {
+            by_factor.entry(factor.factor).or_default().push(factor);
+        }
+    }
+
+    let mut ranked: Vec<(f64, RankedFactor)> = by_factor
+        .into_values()
+        .filter_map(|scores| {
+            let worst = scores.iter().min_by(|a, b| a.score.total_cmp(&b.score))?;
+            let count = scores.len() as f64;
+            let score = scores.iter().map(|f| f.score).sum::<f64>() / count;
+            let penalty = scores.iter().map(|f| f.penalty()).sum::<f64>() / count;
+            (score < 0.95).then(|| {
+                (
+                    penalty,
+                    RankedFactor {
+                        factor: worst.factor,
+                        score,
+                        reason: worst.in_units(units).reason,
+                    },
+                )
+            })
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranked
+        .into_iter()
+        .take(TOP_FACTORS)
+        .map(|(_, factor)| factor)
+        .collect()
+}
+
+fn rank_area(
+    area: Area,
+    distance_km: Option<f64>,
+    days: Vec<ScoredDay>,
+    units: Units,
+) -> Option<RankedArea> {
+    if days.is_empty() {
+        return None;
+    }
+    let score = days.iter().map(|day| day.score.score).sum::<f64>() / days.len() as f64;
+    let top_factors = top_factors(&days, units);
+    let forecast = days
+        .into_iter()
+        .map(|day| DaySummary {
+            date: day.forecast.date,
+            score: day.score.score,
+            max_temperature: units.temperature(day.forecast.max_temp),
+            min_temperature: units.temperature(day.forecast.min_temp),
+            precipitation: units.precipitation(day.forecast.total_precip),
+            max_wind_speed: units.speed(day.forecast.max_wind),
+            condition: day.forecast.condition,
+            wet_until: day.rock.as_ref().and_then(|rock| rock.wet_until),
+            do_not_climb: day.rock.is_some_and(|rock| rock.do_not_climb),
+        })
+        .collect();
+
+    let climbing = area.climbing.as_ref();
+    Some(RankedArea {
+        path: area.path(),
+        total_climbs: climbing.map(|climbing| climbing.total_climbs),
+        rock_type: climbing.and_then(|climbing| climbing.rock_type),
+        units: units.labels(),
+        elevation: area
+            .elevation_m()
+            .map(|elevation| units.elevation(Length::from_metres(elevation))),
+        uuid: area.uuid,
+        area_name: area.area_name,
+        lat: area.metadata.lat,
+        lng: area.metadata.lng,
+        distance: distance_km.map(|distance| units.distance(Length::from_km(distance))),
+        score,
+        top_factors,
+        forecast,
+    })
+}
+
+// An area's stored days, when they cover the whole range and are recent enough to
+// rank without asking the provider
+fn complete_days(
+    area: &Area,
+    mut days: Vec<AreaWeather>,
+    from: NaiveDate,
+    to: NaiveDate,
+    preferences: Option<&Preferences>,
+) -> Option<Vec<ScoredDay>> {
+    let max_age = std::env::var("STORED_FORECAST_MAX_AGE_MINUTES")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_STORED_MAX_AGE_MINUTES);
+    let oldest = Utc::now() - Duration::minutes(max_age);
+    let complete = days.len() as i64 == (to - from).num_days() + 1
+        && days.iter().all(|day| day.fetched_at >= oldest);
+    if !complete {
+        return None;
+    }
+    if let Some(preferences) = preferences {
+        let model = preferences.model_for(area);
+        days.iter_mut().for_each(|day| day.rescore(&model));
+    }
+    days.sort_by_key(|day| day.date);
+    Some(
+        days.into_iter()
+            .map(|day| ScoredDay {
+                forecast: day.forecast,
+                score: day.score,
+                rock: day.rock,
+            })
+            .collect(),
+    )
+}
+
+// Days the background refresh stored for the area, see complete_days
+async fn stored_days(
+    weather: &dyn WeatherRepository,
+    area: &Area,
+    from: NaiveDate,
+    to: NaiveDate,
+    preferences: Option<&Preferences>,
+) -> Option<Vec<ScoredDay>> {
+    match weather.weather_for_area(area, from, to).await {
+        Ok(days) => complete_days(area, days, from, to, preferences),
+        Err(err) => {
+            tracing::warn!(
+                "could not read stored weather for {}: {}",
+                area.area_name,
+                err
+            );
+            None
+        }
+    }
+}
+
+// Ranks every candidate from the stored days in one read, without a forecast per area
+// Stored days are keyed by the area's name and coordinates, as in weather_for_area
+type AreaKey = (String, u64, u64);
+
+pub(crate) fn area_key(area: &Area) -> AreaKey {
+    (
+        area.area_name.clone(),
+        area.metadata.lat.to_bits(),
+        area.metadata.lng.to_bits(),
+    )
+}
+
+// Every stored day in the range in one read, grouped by area
+pub(crate) async fn stored_by_area(
+    weather: &dyn WeatherRepository,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<HashMap<AreaKey, Vec<AreaWeather>>, ApiError> {
+    let mut by_area: HashMap<AreaKey, Vec<AreaWeather>> = HashMap::new();
+    for day in weather.weather_between(from, to).await? {
+        by_area
+            .entry((day.area_name.clone(), day.lat.to_bits(), day.lng.to_bits()))
+            .or_default()
+            .push(day);
+    }
+    Ok(by_area)
+}
+
+async fn rank_stored(
+    weather: &dyn WeatherRepository,
+    candidates: Vec<(Area, Option<f64>)>,
+    from: NaiveDate,
+    to: NaiveDate,
+    preferences: Option<&Preferences>,
+    units: Units,
+) -> Result<Vec<RankedArea>, ApiError> {
+    let mut by_area = stored_by_area(weather, from, to).await?;
+    // An empty list would read as every area being unclimbable
+    if by_area.is_empty() && !candidates.is_empty() {
+        return Err(ApiError::Unavailable(
+            "no forecasts are stored for these dates yet, the background refresh has not \
+             run or is turned off"
+                .to_string(),
+        ));
+    }
+    Ok(candidates
+        .into_iter()
+        .filter_map(|(area, distance)| {
+            let days = by_area.remove(&area_key(&area))?;
+            let days = complete_days(&area, days, from, to, preferences)?;
+            rank_area(area, distance, days, units)
+        })
+        .collect())
+}
+
+pub async fn get_rankings(
+    State(state): State<AppState>,
+    Query(params): Query<RankingParams>,
+) -> Result<Json<Vec<RankedArea>>, ApiError> {
+    let today = Utc::now().date_naive();
+    if params.from > params.to {
+        return Err(bad_request("from must not be after to"));
+    }
+    if params.from < today {
+        return Err(bad_request("from must not be in the past"));
+    }
+    let days_needed = (params.to - today).num_days() + 1;
+    if days_needed > MAX_FORECAST_DAYS {
+        return Err(bad_request("to must be within the next 14 days"));
+    }
+    let origin = match (params.lat, params.lng) {
+        (Some(lat), Some(lng)) => Some(GeoPoint { lat, lng }),
+        (None, None) => None,
+        _ => return Err(bad_request("lat and lng must be given together")),
+    };
+    if params.max_distance_km.is_some() && origin.is_none() {
+        return Err(bad_request("max_distance_km needs an origin lat and lng"));
+    }
+    let max_distance_km = params
+        .max_distance_km
+        .map(|km| check_distance_km("max_distance_km", km, MAX_DISTANCE_KM))
+        .transpose()?;
+
+    let preferences = profiles::resolve_preferences(
+        &state,
+        params.profile_id.as_deref(),
+        params.preferences.as_deref(),
+    )
+    .await?;
+    let units = profiles::units_for(params.units, preferences.as_ref());
+
+    // Nearest areas first when there is an origin
+    let mut candidates: Vec<(Area, Option<f64>)> = match (&params.region, origin) {
+        (Some(region), origin) => {
+            let mut crags: Vec<(Area, Option<f64>)> = state
+                .areas
+                .crags_within(region)
+                .await?
+                .into_iter()
+                .map(|area| {
+                    let distance = origin
+                        .map(|origin| origin.distance_km(area.metadata.lat, area.metadata.lng));
+                    (area, distance)
+                })
+                .filter(|(_, distance)| {
+                    distance.is_none_or(|d| d <= max_distance_km.unwrap_or(ANYWHERE_KM))
+                })
+                .collect();
+            crags.sort_by(|a, b| a.1.unwrap_or(0.0).total_cmp(&b.1.unwrap_or(0.0)));
+            crags
+        }
+        (None, Some(origin)) => state
+            .areas
+            .areas_near(origin, max_distance_km.unwrap_or(DEFAULT_MAX_DISTANCE_KM))
+            .await?
+            .into_iter()
+            .map(|nearby| (nearby.area, Some(nearby.distance_km)))
+            .collect(),
+        (None, None) => state
+            .areas
+            .all_areas()
+            .await?
+            .into_iter()
+            .map(|area| (area, None))
+            .collect(),
+    };
+    // Forecasts are per crag, walls inside a crag would only repeat it
+    candidates.retain(|(area, _)| area.is_crag);
+    if params.discipline.is_some() || params.min_climbs.is_some() {
+        candidates.retain(|(area, _)| {
+            area.climbing.as_ref().is_some_and(|climbing| {
+                let climbs = match params.discipline {
+                    Some(discipline) => climbing.climbs(discipline),
+                    None => climbing.total_climbs,
+                };
+                climbs >= params.min_climbs.unwrap_or(1)
+            })
+        });
+    }
+    if let Some(preferences) = &preferences {
+        candidates.retain(|(area, _)| preferences.allows(area));
+    }
+
+    // The whole catalog is ranked from stored scores, fetching a forecast for every
+    // area would spend the provider's quota on one request
+    if params.region.is_none() && origin.is_none() {
+        // The refresh stores FORECAST_DAYS days, later days are never there
+        let horizon = today + Duration::days(state.forecast_days as i64 - 1);
+        if params.to > horizon {
+            return Err(bad_request(&format!(
+                "rankings without a region or lat and lng use stored forecasts, which reach {}; \
+                 pass a region or lat and lng to rank later days",
+                horizon
+            )));
+        }
+        let mut ranked = rank_stored(
+            state.weather.as_ref(),
+            candidates,
+            params.from,
+            params.to,
+            preferences.as_ref(),
+            units,
+        )
+        .await?;
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+        ranked.truncate(params.limit.unwrap_or(DEFAULT_LIMIT));
+        return Ok(Json(ranked));
+    }
+
+    // Regions and origins may fetch forecasts, the cap keeps the nearest areas
+    let max_areas = std::env::var("RANKINGS_MAX_AREAS")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_MAX_AREAS);
+    candidates.truncate(max_areas);
+
+    let mut ranked: Vec<RankedArea> = futures::stream::iter(candidates)
+        .map(|(area, distance)| {
+            let provider = state.provider.clone();
+            let observations = state.observations.clone();
+            let weather = state.weather.clone();
+            let (from, to) = (params.from, params.to);
+            let preferences = preferences.clone();
+            async move {
+                if let Some(days) =
+                    stored_days(weather.as_ref(), &area, from, to, preferences.as_ref()).await
+                {
+                    return rank_area(area, distance, days, units);
+                }
+                let days = match weather_data_model::score_area(
+                    provider.as_ref(),
+                    observations.as_ref(),
+                    &area,
+                    days_needed as u8,
+                    preferences.as_ref(),
+                )
+                .await
+                {
+                    Ok(days) => days,
+                    Err(err) => {
+                        tracing::warn!("skipping {} in rankings: {}", area.area_name, err);
+                        return None;
+                    }
+                };
+                let days = days
+                    .into_iter()
+                    .filter(|day| day.forecast.date >= from && day.forecast.date <= to)
+                    .collect();
+                rank_area(area, distance, days, units)
+            }
+        })
+        .buffer_unordered(RANKING_CONCURRENCY)
+        .filter_map(|ranked| async move { ranked })
+        .collect()
+        .await;
+
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked.truncate(params.limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(Json(ranked))
+}
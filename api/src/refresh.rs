@@ -0,0 +1,380 @@
+use crate::{
+    error::ApiError,
+    providers::{ProviderError, WeatherProvider},
+    weather_data_model, AppState,
+};
+use datamodels::{Area, AreaForecast};
+use futures::StreamExt;
+use rand::Rng;
+use std::{collections::HashMap, time::Duration};
+
+const KM_PER_DEGREE: f64 = 111.32;
+const DEFAULT_INTERVAL_MINUTES: u64 = 60;
+const DEFAULT_GROUP_KM: f64 = 5.0;
+const DEFAULT_CONCURRENCY: usize = 4;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BASE_SECS: u64 = 2;
+
+#[derive(Clone, Debug)]
+pub struct RefreshConfig {
+    pub interval: Duration,
+    // Crags in the same square of this size share one forecast
+    pub group_km: f64,
+    pub concurrency: usize,
+    pub max_retries: u32,
+    pub retry_base: Duration,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(default)
+}
+
+impl RefreshConfig {
+    // Reads REFRESH_INTERVAL_MINUTES, hourly when unset and off when 0. Also reads
+    // REFRESH_GROUP_KM, REFRESH_CONCURRENCY, REFRESH_MAX_RETRIES and REFRESH_RETRY_BASE_SECS.
+    pub fn from_env() -> Option<Self> {
+        let minutes: u64 = env_or("REFRESH_INTERVAL_MINUTES", DEFAULT_INTERVAL_MINUTES);
+        (minutes > 0).then(|| Self {
+            interval: Duration::from_secs(minutes * 60),
+            group_km: env_or("REFRESH_GROUP_KM", DEFAULT_GROUP_KM).max(0.1),
+            concurrency: env_or("REFRESH_CONCURRENCY", DEFAULT_CONCURRENCY).max(1),
+            max_retries: env_or("REFRESH_MAX_RETRIES", DEFAULT_MAX_RETRIES),
+            retry_base: Duration::from_secs(env_or(
+                "REFRESH_RETRY_BASE_SECS",
+                DEFAULT_RETRY_BASE_SECS,
+            )),
+        })
+    }
+}
+
+#[derive(Default, Debug)]
+struct RefreshStats {
+    groups: usize,
+    crags: usize,
+    stored_days: usize,
+    failed_groups: usize,
+}
+
+// Buckets crags into squares group_km across, walls of one crag and neighbouring crags
+// then cost a single forecast
+fn group_crags(crags: Vec<Area>, group_km: f64) -> Vec<Vec<Area>> {
+    let lat_step = group_km / KM_PER_DEGREE;
+    let mut groups: HashMap<(i64, i64), Vec<Area>> = HashMap::new();
+    for crag in crags {
+        let row = (crag.metadata.lat / lat_step).floor() as i64;
+        // Squares keep their width towards the poles by using more degrees of longitude
+        let row_lat = (row as f64 + 0.5) * lat_step;
+        let lng_step = lat_step / row_lat.to_radians().cos().max(0.01);
+        let col = (crag.metadata.lng / lng_step).floor() as i64;
+        groups.entry((row, col)).or_default().push(crag);
+    }
+    groups.into_values().collect()
+}
+
+fn centre(group: &[Area]) -> (f64, f64) {
+    let count = group.len() as f64;
+    (
+        group.iter().map(|area| area.metadata.lat).sum::<f64>() / count,
+        group.iter().map(|area| area.metadata.lng).sum::<f64>() / count,
+    )
+}
+
+// Retries failures that may clear up, waiting a random time up to an exponentially
+// growing limit so many groups failing together do not retry in step
+async fn forecast_with_retries(
+    provider: &dyn WeatherProvider,
+    lat: f64,
+    lng: f64,
+    days: u8,
+    config: &RefreshConfig,
+) -> Result<AreaForecast, ProviderError> {
+    let mut attempt = 0;
+    loop {
+        match provider.forecast(lat, lng, days).await {
+            Err(err) if err.is_retryable() && attempt < config.max_retries => {
+                let limit = config.retry_base.as_millis() as u64 * 2u64.pow(attempt);
+                let wait = Duration::from_millis(rand::thread_rng().gen_range(0..=limit));
+                tracing::debug!(
+                    "retrying forecast for {},{} in {:?}: {}",
+                    lat,
+                    lng,
+                    wait,
+                    err
+                );
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn refresh_group(state: &AppState, config: &RefreshConfig, group: &[Area]) -> Option<usize> {
+    let (lat, lng) = centre(group);
+    let forecast = match forecast_with_retries(
+        state.provider.as_ref(),
+        lat,
+        lng,
+        state.forecast_days,
+        config,
+    )
+    .await
+    {
+        Ok(forecast) => forecast,
+        Err(err) => {
+            tracing::warn!(
+                "could not refresh {} crags near {},{}: {}",
+                group.len(),
+                lat,
+                lng,
+                err
+            );
+            return None;
+        }
+    };
+
+    let mut stored = 0;
+    for crag in group {
+        match weather_data_model::store_forecast(
+            state.weather.as_ref(),
+            state.observations.as_ref(),
+            crag,
+            forecast.clone(),
+        )
+        .await
+        {
+            Ok(days) => stored += days.len(),
+            Err(err) => tracing::warn!("could not store forecast for {}: {}", crag.area_name, err),
+        }
+    }
+    Some(stored)
+}
+
+async fn refresh_all(state: &AppState, config: &RefreshConfig) -> Result<RefreshStats, ApiError> {
+    let crags: Vec<Area> = state
+        .areas
+        .all_areas()
+        .await?
+        .into_iter()
+        .filter(|area| area.is_crag)
+        .collect();
+    let mut stats = RefreshStats {
+        crags: crags.len(),
+        ..RefreshStats::default()
+    };
+    let groups = group_crags(crags, config.group_km);
+    stats.groups = groups.len();
+
+    let results: Vec<Option<usize>> = futures::stream::iter(groups)
+        .map(|group| {
+            let state = state.clone();
+            let config = config.clone();
+            async move { refresh_group(&state, &config, &group).await }
+        })
+        .buffer_unordered(config.concurrency)
+        .collect()
+        .await;
+    for result in results {
+        match result {
+            Some(days) => stats.stored_days += days,
+            None => stats.failed_groups += 1,
+        }
+    }
+    Ok(stats)
+}
+
+// Refreshes every stored crag's forecast now and then once per interval, each run
+// starting after the previous one has finished
+pub fn spawn(state: AppState, config: RefreshConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match refresh_all(&state, &config).await {
+                Ok(stats) => tracing::info!(
+                    "refreshed {} crags in {} groups, stored {} days, {} groups failed",
+                    stats.crags,
+                    stats.groups,
+                    stats.stored_days,
+                    stats.failed_groups
+                ),
+                Err(err) => tracing::warn!("forecast refresh failed: {}", err),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use reqwest::StatusCode;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    fn crag(name: &str, lat: f64, lng: f64) -> Area {
+        serde_json::from_value(serde_json::json!({
+            "area_name": name,
+            "metadata": {"lat": lat, "lng": lng},
+        }))
+        .unwrap()
+    }
+
+    fn names(mut groups: Vec<Vec<Area>>) -> Vec<Vec<String>> {
+        let mut names: Vec<Vec<String>> = groups
+            .iter_mut()
+            .map(|group| {
+                let mut names: Vec<String> =
+                    group.iter().map(|area| area.area_name.clone()).collect();
+                names.sort();
+                names
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn config(max_retries: u32) -> RefreshConfig {
+        RefreshConfig {
+            interval: Duration::from_secs(60),
+            group_km: DEFAULT_GROUP_KM,
+            concurrency: 1,
+            max_retries,
+            retry_base: Duration::ZERO,
+        }
+    }
+
+    // Fails with the queued errors in order, then answers
+    struct Flaky {
+        errors: Mutex<Vec<ProviderError>>,
+        calls: AtomicUsize,
+    }
+
+    impl Flaky {
+        fn new(errors: Vec<ProviderError>) -> Self {
+            Self {
+                errors: Mutex::new(errors),
+                calls: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl WeatherProvider for Flaky {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn forecast(
+            &self,
+            lat: f64,
+            lng: f64,
+            _days: u8,
+        ) -> Result<AreaForecast, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut errors = self.errors.lock().unwrap();
+            if !errors.is_empty() {
+                return Err(errors.remove(0));
+            }
+            Ok(AreaForecast {
+                provider: "flaky".to_string(),
+                lat,
+                lng,
+                utc_offset_seconds: 0,
+                fetched_at: Utc::now(),
+                elevation_m: None,
+                current: None,
+                days: Vec::new(),
+            })
+        }
+    }
+
+    fn unavailable() -> ProviderError {
+        ProviderError::Status(StatusCode::SERVICE_UNAVAILABLE, None)
+    }
+
+    #[test]
+    fn refreshing_is_on_by_default() {
+        if std::env::var("REFRESH_INTERVAL_MINUTES").is_err() {
+            let config = RefreshConfig::from_env().unwrap();
+            assert_eq!(
+                config.interval,
+                Duration::from_secs(DEFAULT_INTERVAL_MINUTES * 60)
+            );
+        }
+    }
+
+    #[test]
+    fn neighbouring_crags_share_a_group() {
+        let groups = group_crags(
+            vec![
+                crag("Smoke Bluffs", 49.701, -123.141),
+                crag("Smoke Bluffs Wall", 49.702, -123.142),
+                crag("Murrin", 49.651, -123.201),
+                crag("Chamonix", 45.92, 6.87),
+            ],
+            DEFAULT_GROUP_KM,
+        );
+        assert_eq!(
+            names(groups),
+            vec![
+                vec!["Chamonix".to_string()],
+                vec!["Murrin".to_string()],
+                vec!["Smoke Bluffs".to_string(), "Smoke Bluffs Wall".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_keep_their_width_towards_the_poles() {
+        // Crags 6.7km apart at the equator, 1.1km apart at 70 north
+        let equator = group_crags(
+            vec![crag("a", 0.01, 10.02), crag("b", 0.01, 10.08)],
+            DEFAULT_GROUP_KM,
+        );
+        assert_eq!(equator.len(), 2);
+        let north = group_crags(
+            vec![crag("a", 70.01, 10.02), crag("b", 70.01, 10.05)],
+            DEFAULT_GROUP_KM,
+        );
+        assert_eq!(north.len(), 1);
+    }
+
+    #[test]
+    fn groups_are_fetched_at_their_centre() {
+        let (lat, lng) = centre(&[crag("a", 49.0, -123.0), crag("b", 49.2, -123.4)]);
+        assert!((lat - 49.1).abs() < 1e-9);
+        assert!((lng - -123.2).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn passing_failures_are_retried() {
+        let provider = Flaky::new(vec![unavailable(), unavailable()]);
+        let forecast = forecast_with_retries(&provider, 49.7, -123.1, 3, &config(3)).await;
+        assert!(forecast.is_ok());
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_at_the_limit() {
+        let provider = Flaky::new(vec![unavailable(), unavailable(), unavailable()]);
+        let forecast = forecast_with_retries(&provider, 49.7, -123.1, 3, &config(2)).await;
+        assert!(matches!(forecast, Err(ProviderError::Status(..))));
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn lasting_failures_are_not_retried() {
+        let provider = Flaky::new(vec![ProviderError::Unauthorized("bad key".to_string())]);
+        let forecast = forecast_with_retries(&provider, 49.7, -123.1, 3, &config(3)).await;
+        assert!(matches!(forecast, Err(ProviderError::Unauthorized(_))));
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::error::{Problem, PROBLEM_JSON};
+use axum::{
+    http::{header, HeaderName, HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rand::RngCore;
+use tracing::Instrument;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_ID_LEN: usize = 64;
+// Enough of a plain text error body to keep as the problem detail
+const MAX_DETAIL_BYTES: usize = 4096;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// The id of the request being handled, None outside a request
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+fn generate() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// Keeps an id from a proxy in front of us when it looks like one
+fn incoming<B>(req: &Request<B>) -> Option<String> {
+    req.headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|val| val.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_ID_LEN
+                && id
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.')
+        })
+        .map(str::to_string)
+}
+
+// Errors axum produces itself, like a query that does not parse or an unknown route,
+// are plain text. They are turned into problem details like our own errors.
+async fn as_problem(response: Response) -> Response {
+    let is_problem = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|val| val.as_bytes().starts_with(PROBLEM_JSON.as_bytes()));
+    let status = response.status();
+    if is_problem || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let detail = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => {
+            String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_DETAIL_BYTES)]).into_owned()
+        }
+        Err(_) => String::new(),
+    };
+    let detail = if detail.trim().is_empty() {
+        status.canonical_reason().unwrap_or("Error").to_string()
+    } else {
+        detail
+    };
+    let code = match status {
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::UNPROCESSABLE_ENTITY => "validation_error",
+        status if status.is_server_error() => "internal_error",
+        _ => "bad_request",
+    };
+    let mut problem = Problem::new(status, code, detail).into_response();
+    for (name, value) in parts.headers {
+        // Keeps headers like Allow on a 405, the body headers belong to the old body
+        if let Some(name) =
+            name.filter(|name| *name != header::CONTENT_TYPE && *name != header::CONTENT_LENGTH)
+        {
+            problem.headers_mut().insert(name, value);
+        }
+    }
+    problem
+}
+
+// Gives every request an id, reused from X-Request-Id when the caller sent one. It is
+// echoed in the response header, in error bodies and in the request's log lines.
+pub async fn assign_request_id<B>(req: Request<B>, next: Next<B>) -> Response {
+    let id = incoming(&req).unwrap_or_else(generate);
+    let span = tracing::info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path());
+    let mut response = REQUEST_ID
+        .scope(
+            id.clone(),
+            async move { as_problem(next.run(req).await).await },
+        )
+        .instrument(span)
+        .await;
+
+    if let Ok(val) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, val);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/id", get(|| async { current().unwrap_or_default() }))
+            .route(
+                "/missing",
+                get(|| async { Err::<(), _>(ApiError::NotFound("no area".to_string())) }),
+            )
+            .layer(middleware::from_fn(assign_request_id))
+    }
+
+    async fn send(method: &str, uri: &str, id: Option<&str>) -> (Response, Vec<u8>) {
+        let mut req = Request::builder().method(method).uri(uri);
+        if let Some(id) = id {
+            req = req.header(&REQUEST_ID_HEADER, id);
+        }
+        let response = app()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap().to_vec();
+        (
+            Response::from_parts(parts, axum::body::boxed(axum::body::Empty::new())),
+            body,
+        )
+    }
+
+    fn header_id(response: &Response) -> String {
+        response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn ids_are_generated_and_echoed() {
+        let (response, body) = send("GET", "/id", None).await;
+        let id = header_id(&response);
+        assert_eq!(id.len(), 32);
+        assert!(id.bytes().all(|b| b.is_ascii_hexdigit()));
+        // The handler saw the same id the response carries
+        assert_eq!(body, id.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn ids_from_a_proxy_are_kept() {
+        let (response, body) = send("GET", "/id", Some("edge-1234.abc_9")).await;
+        assert_eq!(header_id(&response), "edge-1234.abc_9");
+        assert_eq!(body, b"edge-1234.abc_9");
+    }
+
+    #[tokio::test]
+    async fn unusable_ids_are_replaced() {
+        for id in ["", "has spaces", "<script>", &"a".repeat(MAX_ID_LEN + 1)] {
+            let (response, _) = send("GET", "/id", Some(id)).await;
+            let echoed = header_id(&response);
+            assert_ne!(echoed, id);
+            assert_eq!(echoed.len(), 32);
+        }
+    }
+
+    #[tokio::test]
+    async fn error_bodies_carry_the_request_id() {
+        let (response, body) = send("GET", "/missing", Some("req-1")).await;
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body["request_id"], "req-1");
+        assert_eq!(body["detail"], "no area");
+    }
+
+    #[tokio::test]
+    async fn axum_errors_become_problem_details() {
+        let (response, body) = send("GET", "/nowhere", Some("req-2")).await;
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        assert_eq!(body["code"], "not_found");
+        assert_eq!(body["detail"], "Not Found");
+        assert_eq!(body["request_id"], "req-2");
+
+        let (response, body) = send("POST", "/id", None).await;
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["code"], "method_not_allowed");
+        // Headers of the original response, like Allow, are kept
+        assert!(response.headers().contains_key(header::ALLOW));
+    }
+}
@@ -0,0 +1,298 @@
+use crate::{
+    climatology,
+    error::ApiError,
+    rankings::{self, MAX_FORECAST_DAYS},
+    weather_data_model, AppState,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use datamodels::{
+    climbing::Discipline,
+    geo::{GeoPoint, Latitude, Longitude},
+    trip::{self, CragDay, ScoreSource, TripCrag, TripOptions, TripStop},
+    Area,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+const MAX_TRIP_DAYS: i64 = 30;
+// Crags scored in parallel while planning
+const PLANNING_CONCURRENCY: usize = 8;
+const DEFAULT_MAX_AREAS: usize = 40;
+// Further than anyone drives between two climbing days
+const MAX_DAILY_KM: f64 = 1_000.0;
+
+#[derive(Deserialize, Debug)]
+pub struct TripParams {
+    from: NaiveDate,
+    to: NaiveDate,
+    lat: Latitude,
+    lng: Longitude,
+    max_daily_km: f64,
+    // Days where no reachable crag scores at least this become rest days
+    rest_below: Option<f64>,
+    discipline: Option<Discipline>,
+    min_climbs: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Activity {
+    Climb,
+    Rest,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TripArea {
+    pub uuid: Option<String>,
+    pub area_name: String,
+    pub path: String,
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl From<&Area> for TripArea {
+    fn from(area: &Area) -> Self {
+        Self {
+            uuid: area.uuid.clone(),
+            area_name: area.area_name.clone(),
+            path: area.path(),
+            lat: area.metadata.lat,
+            lng: area.metadata.lng,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TripDay {
+    pub date: NaiveDate,
+    pub activity: Activity,
+    // Where the day ends, None while still at the start
+    pub area: Option<TripArea>,
+    pub drive_km: f64,
+    pub score: Option<f64>,
+    pub source: Option<ScoreSource>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TripPlan {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_score: f64,
+    pub climbing_days: usize,
+    pub rest_days: usize,
+    pub total_drive_km: f64,
+    pub days: Vec<TripDay>,
+}
+
+fn bad_request(msg: &str) -> ApiError {
+    ApiError::Validation(msg.to_string())
+}
+
+// Forecast scores inside the forecast window, climatology for the month after it
+async fn crag_days(
+    state: AppState,
+    area: &Area,
+    dates: &[NaiveDate],
+    forecast_days: i64,
+) -> Vec<Option<CragDay>> {
+    let mut days: Vec<Option<CragDay>> = vec![None; dates.len()];
+    if forecast_days > 0 {
+        match weather_data_model::score_area(
+            state.provider.as_ref(),
+            state.observations.as_ref(),
+            area,
+            forecast_days as u8,
+            None,
+        )
+        .await
+        {
+            Ok(scored) => {
+                for scored in scored {
+                    if let Some(idx) = dates.iter().position(|date| *date == scored.forecast.date) {
+                        days[idx] = Some(CragDay {
+                            score: scored.score.score,
+                            source: ScoreSource::Forecast,
+                        });
+                    }
+                }
+            }
+            Err(err) => tracing::warn!("no forecast for {} in trip: {}", area.area_name, err),
+        }
+    }
+
+    if days.iter().all(Option::is_some) {
+        return days;
+    }
+    match climatology::climatology_for(&state, area, false).await {
+        Ok(climate) => {
+            for (day, date) in days.iter_mut().zip(dates) {
+                if day.is_none() {
+                    *day = climate.month(date.month()).map(|month| CragDay {
+                        score: month.score,
+                        source: ScoreSource::Climatology,
+                    });
+                }
+            }
+        }
+        Err(err) => tracing::warn!("no climatology for {} in trip: {}", area.area_name, err),
+    }
+    days
+}
+
+// Day by day itinerary over the crags within driving range, with rest days where the
+// weather is bad everywhere reachable
+pub async fn plan_trip(
+    State(state): State<AppState>,
+    Query(params): Query<TripParams>,
+) -> Result<Json<TripPlan>, ApiError> {
+    let today = Utc::now().date_naive();
+    if params.from > params.to {
+        return Err(bad_request("from must not be after to"));
+    }
+    if params.from < today {
+        return Err(bad_request("from must not be in the past"));
+    }
+    let trip_days = (params.to - params.from).num_days() + 1;
+    if trip_days > MAX_TRIP_DAYS {
+        return Err(bad_request("trips can be at most 30 days long"));
+    }
+    let max_daily_km =
+        rankings::check_distance_km("max_daily_km", params.max_daily_km, MAX_DAILY_KM)?;
+    let mut options = TripOptions {
+        max_daily_km,
+        ..TripOptions::default()
+    };
+    if let Some(rest_below) = params.rest_below {
+        options.rest_below = rest_below;
+    }
+    let start = GeoPoint {
+        lat: params.lat,
+        lng: params.lng,
+    };
+
+    // Nothing further than a full day's drive per trip day can be reached
+    let mut candidates: Vec<Area> = state
+        .areas
+        .areas_near(start, max_daily_km * trip_days as f64)
+        .await?
+        .into_iter()
+        .map(|nearby| nearby.area)
+        .filter(|area| area.is_crag)
+        .filter(|area| {
+            if params.discipline.is_none() && params.min_climbs.is_none() {
+                return true;
+            }
+            area.climbing.as_ref().is_some_and(|climbing| {
+                let climbs = match params.discipline {
+                    Some(discipline) => climbing.climbs(discipline),
+                    None => climbing.total_climbs,
+                };
+                climbs >= params.min_climbs.unwrap_or(1)
+            })
+        })
+        .collect();
+    let max_areas = std::env::var("TRIP_MAX_AREAS")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_MAX_AREAS);
+    candidates.truncate(max_areas);
+
+    let dates: Vec<NaiveDate> = (0..trip_days)
+        .map(|day| params.from + Duration::days(day))
+        .collect();
+    // Trips that start after the forecast window are planned from climatology alone,
+    // without asking the provider for days that are all thrown away
+    let forecast_end = today + Duration::days(MAX_FORECAST_DAYS - 1);
+    let forecast_days = if params.from > forecast_end {
+        0
+    } else {
+        (params.to.min(forecast_end) - today).num_days() + 1
+    };
+    let crags: Vec<TripCrag> = futures::stream::iter(candidates.clone())
+        .map(|area| {
+            let state = state.clone();
+            let dates = dates.clone();
+            async move {
+                TripCrag {
+                    lat: area.metadata.lat,
+                    lng: area.metadata.lng,
+                    days: crag_days(state, &area, &dates, forecast_days).await,
+                }
+            }
+        })
+        .buffered(PLANNING_CONCURRENCY)
+        .collect()
+        .await;
+
+    let stops = trip::plan_trip(
+        start.lat(),
+        start.lng(),
+        &crags,
+        trip_days as usize,
+        &options,
+    );
+    let days: Vec<TripDay> = stops
+        .into_iter()
+        .zip(dates.iter().enumerate())
+        .map(|(stop, (idx, date))| match stop {
+            TripStop::Climb {
+                crag,
+                drive_km,
+                day,
+            } => TripDay {
+                date: *date,
+                activity: Activity::Climb,
+                area: Some(TripArea::from(&candidates[crag])),
+                drive_km,
+                score: Some(day.score),
+                source: Some(day.source),
+                reason: None,
+            },
+            TripStop::Rest { crag, drive_km } => {
+                let best = crags
+                    .iter()
+                    .filter_map(|crag| crag.days[idx])
+                    .map(|day| day.score)
+                    .max_by(f64::total_cmp);
+                let reason = match best {
+                    Some(best) if best < options.rest_below => format!(
+                        "best crag in range scores {:.0}, below {:.0}",
+                        best, options.rest_below
+                    ),
+                    Some(_) => "moving on to better conditions".to_string(),
+                    None => "no weather known for any crag in range".to_string(),
+                };
+                TripDay {
+                    date: *date,
+                    activity: Activity::Rest,
+                    area: crag.map(|crag| TripArea::from(&candidates[crag])),
+                    drive_km,
+                    score: None,
+                    source: None,
+                    reason: Some(reason),
+                }
+            }
+        })
+        .collect();
+
+    Ok(Json(TripPlan {
+        from: params.from,
+        to: params.to,
+        total_score: days.iter().filter_map(|day| day.score).sum(),
+        climbing_days: days
+            .iter()
+            .filter(|day| matches!(day.activity, Activity::Climb))
+            .count(),
+        rest_days: days
+            .iter()
+            .filter(|day| matches!(day.activity, Activity::Rest))
+            .count(),
+        total_drive_km: days.iter().map(|day| day.drive_km).sum(),
+        days,
+    }))
+}
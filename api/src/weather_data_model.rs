@@ -1,778 +1,115 @@
-use datamodels::{Area, AreaWeather, Metadata, ResponseAndArea, WeatherResponse};
+use crate::{error::ApiError, providers::WeatherProvider};
+use chrono::{DateTime, Duration, Utc};
+use datamodels::{
+    archive::{ArchivedObservation, ObservationKind},
+    geo::GeoPoint,
+    profile::Preferences,
+    scoring::ScoredDay,
+    Area, AreaForecast, AreaWeather, Observation, ResponseAndArea,
+};
+use storage::{AreaRepository, ObservationRepository, WeatherRepository};
+
+// Archived history handed to the drying model
+const RECENT_HOURS: i64 = 48;
+// Requests further than this from every stored area are rejected
+const DEFAULT_AREA_MATCH_RADIUS_KM: f64 = 25.0;
 
 pub async fn get_weather_from_api(
+    provider: &dyn WeatherProvider,
     lat: f64,
     lng: f64,
-) -> Result<WeatherResponse, Box<dyn std::error::Error>> {
-    // TODO replace mock_json with real query to weather API
-    let mock_json: WeatherResponse = serde_json::from_str(
-        r#"{
-            "location": {
-                "name": "Edina",
-                "region": "Missouri",
-                "country": "United States of America",
-                "lat": 40.13,
-                "lon": -92.14,
-                "tz_id": "America/Chicago",
-                "localtime_epoch": 1679286189,
-                "localtime": "2023-03-19 23:23"
-            },
-            "current": {
-                "temp_c": -2.2,
-                "temp_f": 28.0,
-                "condition": {
-                    "text": "Clear",
-                    "icon": "//cdn.weatherapi.com/weather/64x64/night/113.png",
-                    "code": 1000
-                },
-                "wind_mph": 9.4,
-                "wind_kph": 15.1,
-                "wind_degree": 190,
-                "precip_mm": 0.0,
-                "precip_in": 0.0,
-                "humidity": 55,
-                "cloud": 0,
-                "feelslike_c": -8.0,
-                "feelslike_f": 17.5,
-                "uv": 1.0,
-                "air_quality": {
-                    "co": 247.0,
-                    "no2": 2.5,
-                    "o3": 101.5999984741211,
-                    "so2": 1.7999999523162842,
-                    "pm2_5": 5.599999904632568,
-                    "pm10": 5.800000190734863,
-                    "us-epa-index": 1,
-                    "gb-defra-index": 1
-                }
-            },
-            "forecast": {
-                "forecastday": [
-                    {
-                        "date": "2023-03-19",
-                        "day": {
-                            "maxtemp_c": 6.0,
-                            "maxtemp_f": 42.8,
-                            "mintemp_c": -7.7,
-                            "mintemp_f": 18.1,
-                            "avgtemp_c": -1.8,
-                            "avgtemp_f": 28.7,
-                            "maxwind_mph": 13.0,
-                            "maxwind_kph": 20.9,
-                            "totalprecip_mm": 0.0,
-                            "totalprecip_in": 0.0,
-                            "totalsnow_cm": 0.0,
-                            "avghumidity": 47.0,
-                            "daily_chance_of_rain": 0,
-                            "condition": {
-                                "text": "Sunny",
-                                "icon": "//cdn.weatherapi.com/weather/64x64/day/113.png",
-                                "code": 1000
-                            },
-                            "air_quality": {
-                                "co": 243.8079992675781,
-                                "no2": 1.2560000026226044,
-                                "o3": 94.38800140380859,
-                                "so2": 0.31600000739097595,
-                                "pm2_5": 1.7439999961853028,
-                                "pm10": 1.923999993801117,
-                                "us-epa-index": 1,
-                                "gb-defra-index": 1
-                            }
-                        },
-                        "astro": {
-                            "is_moon_up": 0,
-                            "is_sun_up": 1
-                        },
-                        "hour": [
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 1.399999976158142,
-                                    "o3": 97.30000305175781,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 1.399999976158142,
-                                    "pm10": 1.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 1.5,
-                                    "o3": 97.30000305175781,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 1.600000023841858,
-                                    "pm10": 1.7999999523162842,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 1.5,
-                                    "o3": 95.80000305175781,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 1.899999976158142,
-                                    "pm10": 2.0999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 1.5,
-                                    "o3": 95.80000305175781,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.200000047683716,
-                                    "pm10": 2.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 1.600000023841858,
-                                    "o3": 94.4000015258789,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.5,
-                                    "pm10": 2.799999952316284,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 250.3000030517578,
-                                    "no2": 1.600000023841858,
-                                    "o3": 93.0,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.700000047683716,
-                                    "pm10": 3.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 250.3000030517578,
-                                    "no2": 1.7000000476837158,
-                                    "o3": 93.0,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.700000047683716,
-                                    "pm10": 3.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 250.3000030517578,
-                                    "no2": 1.7000000476837158,
-                                    "o3": 91.5999984741211,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.799999952316284,
-                                    "pm10": 3.0999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 253.6999969482422,
-                                    "no2": 1.7999999523162842,
-                                    "o3": 90.80000305175781,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.9000000953674316,
-                                    "pm10": 3.200000047683716,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 253.6999969482422,
-                                    "no2": 2.200000047683716,
-                                    "o3": 91.5999984741211,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 2.799999952316284,
-                                    "pm10": 3.0999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 253.6999969482422,
-                                    "no2": 2.0,
-                                    "o3": 93.0,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 2.5999999046325684,
-                                    "pm10": 2.9000000953674316,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 1.5,
-                                    "o3": 95.80000305175781,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 2.299999952316284,
-                                    "pm10": 2.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 243.6999969482422,
-                                    "no2": 1.100000023841858,
-                                    "o3": 97.30000305175781,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 2.0999999046325684,
-                                    "pm10": 2.299999952316284,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 243.6999969482422,
-                                    "no2": 0.8999999761581421,
-                                    "o3": 97.30000305175781,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 2.0,
-                                    "pm10": 2.200000047683716,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 233.6999969482422,
-                                    "no2": 0.6000000238418579,
-                                    "o3": 104.4000015258789,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 1.7000000476837158,
-                                    "pm10": 1.899999976158142,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 0.5,
-                                    "o3": 107.30000305175781,
-                                    "so2": 0.30000001192092896,
-                                    "pm2_5": 1.399999976158142,
-                                    "pm10": 1.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 0.5,
-                                    "o3": 107.30000305175781,
-                                    "so2": 0.4000000059604645,
-                                    "pm2_5": 1.2999999523162842,
-                                    "pm10": 1.399999976158142,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 223.60000610351562,
-                                    "no2": 0.6000000238418579,
-                                    "o3": 107.30000305175781,
-                                    "so2": 0.5,
-                                    "pm2_5": 1.399999976158142,
-                                    "pm10": 1.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 1.0,
-                                    "o3": 105.9000015258789,
-                                    "so2": 0.699999988079071,
-                                    "pm2_5": 1.7000000476837158,
-                                    "pm10": 1.7999999523162842,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 230.3000030517578,
-                                    "no2": 1.5,
-                                    "o3": 103.0,
-                                    "so2": 0.8999999761581421,
-                                    "pm2_5": 2.0,
-                                    "pm10": 2.0999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 230.3000030517578,
-                                    "no2": 1.7999999523162842,
-                                    "o3": 101.5999984741211,
-                                    "so2": 1.0,
-                                    "pm2_5": 2.4000000953674316,
-                                    "pm10": 2.5999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 233.6999969482422,
-                                    "no2": 2.0,
-                                    "o3": 100.0999984741211,
-                                    "so2": 1.100000023841858,
-                                    "pm2_5": 3.0999999046325684,
-                                    "pm10": 3.4000000953674316,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 240.3000030517578,
-                                    "no2": 2.5999999046325684,
-                                    "o3": 100.0999984741211,
-                                    "so2": 2.0,
-                                    "pm2_5": 4.699999809265137,
-                                    "pm10": 5.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 247.0,
-                                    "no2": 2.5,
-                                    "o3": 101.5999984741211,
-                                    "so2": 1.7999999523162842,
-                                    "pm2_5": 5.599999904632568,
-                                    "pm10": 5.800000190734863,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            }
-                        ]
-                    },
-                    {
-                        "date": "2023-03-20",
-                        "day": {
-                            "maxtemp_c": 14.4,
-                            "maxtemp_f": 57.9,
-                            "mintemp_c": -2.8,
-                            "mintemp_f": 27.0,
-                            "avgtemp_c": 4.6,
-                            "avgtemp_f": 40.2,
-                            "maxwind_mph": 22.6,
-                            "maxwind_kph": 36.4,
-                            "totalprecip_mm": 0.0,
-                            "totalprecip_in": 0.0,
-                            "totalsnow_cm": 0.0,
-                            "avghumidity": 54.0,
-                            "daily_chance_of_rain": 0,
-                            "condition": {
-                                "text": "Sunny",
-                                "icon": "//cdn.weatherapi.com/weather/64x64/day/113.png",
-                                "code": 1000
-                            },
-                            "air_quality": {
-                                "co": 234.19600036621094,
-                                "no2": 1.5319999837875367,
-                                "o3": 101.74,
-                                "so2": 1.2679999959468842,
-                                "pm2_5": 3.3560000133514403,
-                                "pm10": 3.671999998092651,
-                                "us-epa-index": 1,
-                                "gb-defra-index": 1
-                            }
-                        },
-                        "astro": {
-                            "is_moon_up": 0,
-                            "is_sun_up": 1
-                        },
-                        "hour": [
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 243.6999969482422,
-                                    "no2": 2.0999999046325684,
-                                    "o3": 101.5999984741211,
-                                    "so2": 1.399999976158142,
-                                    "pm2_5": 4.800000190734863,
-                                    "pm10": 5.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 240.3000030517578,
-                                    "no2": 1.7999999523162842,
-                                    "o3": 100.0999984741211,
-                                    "so2": 1.899999976158142,
-                                    "pm2_5": 3.9000000953674316,
-                                    "pm10": 4.099999904632568,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.600000023841858,
-                                    "o3": 100.0999984741211,
-                                    "so2": 2.0,
-                                    "pm2_5": 3.4000000953674316,
-                                    "pm10": 3.700000047683716,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.5,
-                                    "o3": 100.0999984741211,
-                                    "so2": 1.7999999523162842,
-                                    "pm2_5": 3.4000000953674316,
-                                    "pm10": 3.5999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.399999976158142,
-                                    "o3": 98.69999694824219,
-                                    "so2": 1.5,
-                                    "pm2_5": 3.0999999046325684,
-                                    "pm10": 3.4000000953674316,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.2999999523162842,
-                                    "o3": 98.69999694824219,
-                                    "so2": 1.2999999523162842,
-                                    "pm2_5": 2.9000000953674316,
-                                    "pm10": 3.0999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.2000000476837158,
-                                    "o3": 97.30000305175781,
-                                    "so2": 1.100000023841858,
-                                    "pm2_5": 2.700000047683716,
-                                    "pm10": 2.9000000953674316,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.2000000476837158,
-                                    "o3": 97.30000305175781,
-                                    "so2": 1.2000000476837158,
-                                    "pm2_5": 2.9000000953674316,
-                                    "pm10": 3.0999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 1.5,
-                                    "o3": 97.30000305175781,
-                                    "so2": 1.2999999523162842,
-                                    "pm2_5": 3.299999952316284,
-                                    "pm10": 3.5999999046325684,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 2.299999952316284,
-                                    "o3": 97.30000305175781,
-                                    "so2": 1.2999999523162842,
-                                    "pm2_5": 3.700000047683716,
-                                    "pm10": 4.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 237.0,
-                                    "no2": 2.5,
-                                    "o3": 100.0999984741211,
-                                    "so2": 1.399999976158142,
-                                    "pm2_5": 3.9000000953674316,
-                                    "pm10": 4.400000095367432,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 233.6999969482422,
-                                    "no2": 2.0999999046325684,
-                                    "o3": 100.0999984741211,
-                                    "so2": 1.600000023841858,
-                                    "pm2_5": 4.099999904632568,
-                                    "pm10": 4.900000095367432,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 230.3000030517578,
-                                    "no2": 1.7999999523162842,
-                                    "o3": 101.5999984741211,
-                                    "so2": 1.7000000476837158,
-                                    "pm2_5": 4.599999904632568,
-                                    "pm10": 5.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 1.399999976158142,
-                                    "o3": 104.4000015258789,
-                                    "so2": 1.7000000476837158,
-                                    "pm2_5": 5.5,
-                                    "pm10": 6.300000190734863,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 1.0,
-                                    "o3": 110.19999694824219,
-                                    "so2": 1.5,
-                                    "pm2_5": 6.400000095367432,
-                                    "pm10": 7.199999809265137,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 223.60000610351562,
-                                    "no2": 0.699999988079071,
-                                    "o3": 113.0,
-                                    "so2": 1.2999999523162842,
-                                    "pm2_5": 6.599999904632568,
-                                    "pm10": 7.5,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 220.3000030517578,
-                                    "no2": 0.6000000238418579,
-                                    "o3": 114.4000015258789,
-                                    "so2": 1.100000023841858,
-                                    "pm2_5": 6.300000190734863,
-                                    "pm10": 7.199999809265137,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 220.3000030517578,
-                                    "no2": 0.699999988079071,
-                                    "o3": 113.0,
-                                    "so2": 1.100000023841858,
-                                    "pm2_5": 5.800000190734863,
-                                    "pm10": 6.699999809265137,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 220.3000030517578,
-                                    "no2": 0.8999999761581421,
-                                    "o3": 113.0,
-                                    "so2": 1.0,
-                                    "pm2_5": 5.5,
-                                    "pm10": 6.300000190734863,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 220.3000030517578,
-                                    "no2": 1.2000000476837158,
-                                    "o3": 111.5999984741211,
-                                    "so2": 1.100000023841858,
-                                    "pm2_5": 5.099999904632568,
-                                    "pm10": 6.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 223.60000610351562,
-                                    "no2": 1.5,
-                                    "o3": 110.19999694824219,
-                                    "so2": 1.2000000476837158,
-                                    "pm2_5": 5.0,
-                                    "pm10": 5.800000190734863,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 1.600000023841858,
-                                    "o3": 107.30000305175781,
-                                    "so2": 1.2000000476837158,
-                                    "pm2_5": 5.0,
-                                    "pm10": 5.900000095367432,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 1.600000023841858,
-                                    "o3": 105.9000015258789,
-                                    "so2": 1.100000023841858,
-                                    "pm2_5": 5.099999904632568,
-                                    "pm10": 6.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            },
-                            {
-                                "condition": {},
-                                "air_quality": {
-                                    "co": 227.0,
-                                    "no2": 1.5,
-                                    "o3": 103.0,
-                                    "so2": 1.0,
-                                    "pm2_5": 5.0,
-                                    "pm10": 6.0,
-                                    "us-epa-index": 1,
-                                    "gb-defra-index": 1
-                                }
-                            }
-                        ]
-                    }
-                ]
-            }
-        }"#,
-    )?;
+    days: u8,
+) -> Result<AreaForecast, ApiError> {
+    tracing::debug!(
+        "fetching {} day forecast for {},{} from {}",
+        days,
+        lat,
+        lng,
+        provider.name()
+    );
+    Ok(provider.forecast(lat, lng, days).await?)
+}
+
+// Archived hours before `until`, for the drying model
+pub async fn recent_hours(
+    observations: &dyn ObservationRepository,
+    area: &Area,
+    until: DateTime<Utc>,
+) -> Result<Vec<Observation>, ApiError> {
+    Ok(observations
+        .observations_for_area(area, until - Duration::hours(RECENT_HOURS), until)
+        .await?
+        .into_iter()
+        .filter(|archived| archived.kind == ObservationKind::PastHour)
+        .map(|archived| archived.observation)
+        .collect())
+}
 
-    return Ok(mock_json);
+// Fetch a forecast for the area and score it, using archived hours for the drying model
+pub async fn score_area(
+    provider: &dyn WeatherProvider,
+    observations: &dyn ObservationRepository,
+    area: &Area,
+    days: u8,
+    preferences: Option<&Preferences>,
+) -> Result<Vec<ScoredDay>, ApiError> {
+    let forecast =
+        get_weather_from_api(provider, area.metadata.lat, area.metadata.lng, days).await?;
+    let recent = recent_hours(observations, area, forecast.fetched_at)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::warn!("no archived weather for {}: {}", area.area_name, err);
+            Vec::new()
+        });
+    Ok(area.score_forecast_for(forecast, &recent, preferences))
 }
 
-pub async fn add_weather_to_db(
-    response_json: WeatherResponse,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Add the weather data from a response string to the database
+// The stored crag within AREA_MATCH_RADIUS_KM of the point that forecasts are kept for
+pub async fn nearest_crag(areas: &dyn AreaRepository, point: GeoPoint) -> Result<Area, ApiError> {
+    let max_km = std::env::var("AREA_MATCH_RADIUS_KM")
+        .ok()
+        .and_then(|val| val.parse().ok())
+        .unwrap_or(DEFAULT_AREA_MATCH_RADIUS_KM);
+    areas.nearest_crag(point, max_km).await?.ok_or_else(|| {
+        ApiError::NotFound(format!(
+            "No stored crag near {},{}",
+            point.lat(),
+            point.lng()
+        ))
+    })
+}
+
+// Archives the forecast's past hours for the area and stores its scored days, replacing
+// any earlier forecast for the same days. Returns the stored days.
+pub async fn store_forecast(
+    weather: &dyn WeatherRepository,
+    observations: &dyn ObservationRepository,
+    area: &Area,
+    forecast: AreaForecast,
+) -> Result<Vec<AreaWeather>, ApiError> {
+    // Keep what has already happened before the providers forget it. Without the archive
+    // the rock is dried from the forecast alone, as in score_area.
+    let recent = recent_hours(observations, area, forecast.fetched_at)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::warn!("no archived weather for {}: {}", area.area_name, err);
+            Vec::new()
+        });
+    observations
+        .append_observations(&ArchivedObservation::from_forecast(area, &forecast))
+        .await?;
+
+    // Add the weather data from the forecast to the database
     let response_and_area = ResponseAndArea {
-        response: response_json,
-        area: Area {
-            area_name: "test_area".to_string(),
-            metadata: Metadata {
-                lat: 45.0,
-                lng: -122.0,
-            },
-        },
+        response: forecast,
+        area: area.clone(),
+        recent,
     };
-    let goldilocks_model_data: AreaWeather = response_and_area.into();
-    println!("{:?}", goldilocks_model_data);
-    Ok(())
+    let goldilocks_model_data: Vec<AreaWeather> = response_and_area.into();
+
+    // Replaces the previous forecast for each area and day instead of adding another
+    for area_weather in &goldilocks_model_data {
+        weather.upsert_weather(area_weather).await?;
+    }
+    Ok(goldilocks_model_data)
 }
@@ -0,0 +1,191 @@
+use crate::{
+    forecast::{AreaForecast, Observation},
+    Area,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservationKind {
+    // Conditions reported as current when the forecast was fetched
+    Current,
+    // A forecast hour that had already passed when it was fetched
+    PastHour,
+}
+
+// One hour of weather that actually happened at an area, kept after the providers drop it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchivedObservation {
+    pub area_uuid: Option<String>,
+    pub area_name: String,
+    pub lat: f64,
+    pub lng: f64,
+    pub provider: String,
+    pub kind: ObservationKind,
+    pub observed_at: DateTime<Utc>,
+    pub fetched_at: DateTime<Utc>,
+    pub observation: Observation,
+}
+
+impl ArchivedObservation {
+    // Whether this was observed at the area, matched like stored areas are
+    pub fn is_for(&self, area: &Area) -> bool {
+        match &area.uuid {
+            Some(uuid) => self.area_uuid.as_ref() == Some(uuid),
+            None => {
+                self.area_name == area.area_name
+                    && self.lat == area.metadata.lat
+                    && self.lng == area.metadata.lng
+            }
+        }
+    }
+
+    // The current conditions and every forecast hour up to the fetch time
+    pub fn from_forecast(area: &Area, forecast: &AreaForecast) -> Vec<Self> {
+        let offset = Duration::seconds(forecast.utc_offset_seconds as i64);
+        let entry = |kind, observation: &Observation| Self {
+            area_uuid: area.uuid.clone(),
+            area_name: area.area_name.clone(),
+            lat: area.metadata.lat,
+            lng: area.metadata.lng,
+            provider: forecast.provider.clone(),
+            kind,
+            observed_at: (observation.time - offset).and_utc(),
+            fetched_at: forecast.fetched_at,
+            observation: observation.clone(),
+        };
+
+        let mut archived: Vec<Self> = forecast
+            .days
+            .iter()
+            .flat_map(|day| day.hours.iter())
+            .map(|hour| entry(ObservationKind::PastHour, hour))
+            .filter(|entry| entry.observed_at <= forecast.fetched_at)
+            .collect();
+        if let Some(current) = &forecast.current {
+            archived.push(entry(ObservationKind::Current, current));
+        }
+        archived
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        forecast::DailyForecast,
+        units::{Length, Speed, Temperature},
+    };
+    use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+
+    fn at(hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 6, 1)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn hour(time: NaiveDateTime) -> Observation {
+        Observation {
+            time,
+            temp: Temperature::from_celsius(15.0),
+            feels_like: None,
+            humidity: 50.0,
+            wind: Speed::from_kph(0.0),
+            gust: None,
+            wind_degree: None,
+            precip: Length::default(),
+            snow: None,
+            cloud: 100.0,
+            chance_of_rain: None,
+            uv: None,
+            is_day: Some(true),
+            condition: None,
+            us_epa_index: None,
+        }
+    }
+
+    fn area(json: &str) -> Area {
+        serde_json::from_str(json).unwrap()
+    }
+
+    // Local midnight to 23:00 at UTC-7, fetched at 10:00 local
+    fn forecast(current: Option<Observation>) -> AreaForecast {
+        let hours = (0..24).map(|h| hour(at(h))).collect();
+        AreaForecast {
+            provider: "test".to_string(),
+            lat: 49.7,
+            lng: -123.1,
+            utc_offset_seconds: -7 * 3600,
+            fetched_at: Utc.with_ymd_and_hms(2023, 6, 1, 17, 0, 0).unwrap(),
+            elevation_m: None,
+            current,
+            days: vec![DailyForecast::from_hours(at(0).date(), hours).unwrap()],
+        }
+    }
+
+    #[test]
+    fn from_forecast_keeps_only_hours_already_past() {
+        let crag = area(
+            r#"{"uuid": "a", "area_name": "Smoke Bluffs", "metadata": {"lat": 49.7, "lng": -123.1}}"#,
+        );
+        let archived = ArchivedObservation::from_forecast(&crag, &forecast(None));
+
+        // 00:00 to 10:00 local have happened by the fetch, the rest are still forecast
+        assert_eq!(archived.len(), 11);
+        assert!(archived
+            .iter()
+            .all(|entry| entry.kind == ObservationKind::PastHour));
+        assert_eq!(
+            archived[0].observed_at,
+            Utc.with_ymd_and_hms(2023, 6, 1, 7, 0, 0).unwrap()
+        );
+        assert_eq!(
+            archived.last().unwrap().observed_at,
+            Utc.with_ymd_and_hms(2023, 6, 1, 17, 0, 0).unwrap()
+        );
+        assert_eq!(archived[0].area_uuid.as_deref(), Some("a"));
+        assert_eq!(archived[0].provider, "test");
+    }
+
+    #[test]
+    fn from_forecast_archives_the_current_conditions() {
+        let crag =
+            area(r#"{"area_name": "Smoke Bluffs", "metadata": {"lat": 49.7, "lng": -123.1}}"#);
+        let archived = ArchivedObservation::from_forecast(&crag, &forecast(Some(hour(at(10)))));
+
+        let current: Vec<_> = archived
+            .iter()
+            .filter(|entry| entry.kind == ObservationKind::Current)
+            .collect();
+        assert_eq!(current.len(), 1);
+        assert_eq!(
+            current[0].observed_at,
+            Utc.with_ymd_and_hms(2023, 6, 1, 17, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn is_for_matches_by_uuid_or_name_and_coordinates() {
+        let synced = area(
+            r#"{"uuid": "a", "area_name": "Smoke Bluffs", "metadata": {"lat": 49.7, "lng": -123.1}}"#,
+        );
+        let archived = &ArchivedObservation::from_forecast(&synced, &forecast(None))[0];
+        assert!(archived.is_for(&synced));
+        assert!(archived.is_for(&area(
+            r#"{"uuid": "a", "area_name": "Renamed", "metadata": {"lat": 49.8, "lng": -123.2}}"#
+        )));
+        assert!(!archived.is_for(&area(
+            r#"{"uuid": "b", "area_name": "Smoke Bluffs", "metadata": {"lat": 49.7, "lng": -123.1}}"#
+        )));
+
+        let local =
+            area(r#"{"area_name": "Smoke Bluffs", "metadata": {"lat": 49.7, "lng": -123.1}}"#);
+        let archived = &ArchivedObservation::from_forecast(&local, &forecast(None))[0];
+        assert!(archived.is_for(&local));
+        assert!(!archived.is_for(&area(
+            r#"{"area_name": "Smoke Bluffs", "metadata": {"lat": 49.8, "lng": -123.1}}"#
+        )));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    forecast::DailyForecast,
+    scoring::ScoringModel,
+    units::{Length, Speed, Temperature},
+    Area,
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Days with at least this much rain count as rainy
+const RAINY_DAY_MM: f64 = 1.0;
+// Historical days scoring at least this count as good climbing days
+const GOOD_DAY_SCORE: f64 = 60.0;
+
+// One day of observed weather from a historical archive
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoricalDay {
+    pub date: NaiveDate,
+    #[serde(rename = "max_temp_c")]
+    pub max_temp: Temperature,
+    #[serde(rename = "min_temp_c")]
+    pub min_temp: Temperature,
+    #[serde(rename = "precip_m")]
+    pub precip: Length,
+    #[serde(rename = "snow_m")]
+    pub snow: Length,
+    pub humidity: Option<f64>,
+    #[serde(rename = "max_wind_ms")]
+    pub max_wind: Option<Speed>,
+}
+
+impl From<&HistoricalDay> for DailyForecast {
+    fn from(day: &HistoricalDay) -> Self {
+        Self {
+            date: day.date,
+            max_temp: day.max_temp,
+            min_temp: day.min_temp,
+            avg_temp: Temperature::from_celsius(
+                (day.max_temp.celsius() + day.min_temp.celsius()) / 2.0,
+            ),
+            max_wind: day.max_wind.unwrap_or_default(),
+            total_precip: day.precip,
+            total_snow: day.snow,
+            avg_humidity: day.humidity,
+            chance_of_rain: None,
+            uv: None,
+            condition: None,
+            us_epa_index: None,
+            sunrise: None,
+            sunset: None,
+            hours: Vec::new(),
+        }
+    }
+}
+
+// Typical weather in one calendar month, averaged over the archive's years
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MonthClimate {
+    // 1 is January
+    pub month: u32,
+    #[serde(rename = "avg_high_c")]
+    pub avg_high: Temperature,
+    #[serde(rename = "avg_low_c")]
+    pub avg_low: Temperature,
+    // Total for the month, averaged over the years
+    #[serde(rename = "monthly_precip_m")]
+    pub monthly_precip: Length,
+    pub rainy_days: f64,
+    pub avg_humidity: Option<f64>,
+    #[serde(rename = "avg_max_wind_ms")]
+    pub avg_max_wind: Option<Speed>,
+    pub good_days: f64,
+    // Average goldilocks score of the month's days, 0 to 100
+    pub score: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Climatology {
+    pub area_uuid: Option<String>,
+    pub area_name: String,
+    pub lat: f64,
+    pub lng: f64,
+    // Range of the history the months were built from
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub computed_at: DateTime<Utc>,
+    pub months: Vec<MonthClimate>,
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), val| (sum + val, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+fn days_in_month(year: i32, month: u32) -> usize {
+    let first = NaiveDate::from_ymd_opt(year, month, 1);
+    let next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+    match (first, next) {
+        (Some(first), Some(next)) => (next - first).num_days() as usize,
+        _ => 0,
+    }
+}
+
+impl Climatology {
+    // Scores every historical day with the area's model and averages them per month.
+    // Months the history only partly covers are left out, their totals would read low.
+    pub fn from_history(area: &Area, days: &[HistoricalDay], computed_at: DateTime<Utc>) -> Self {
+        let mut by_year_month: BTreeMap<(i32, u32), Vec<&HistoricalDay>> = BTreeMap::new();
+        for day in days {
+            by_year_month
+                .entry((day.date.year(), day.date.month()))
+                .or_default()
+                .push(day);
+        }
+        let complete: Vec<&HistoricalDay> = by_year_month
+            .into_iter()
+            .filter(|((year, month), days)| {
+                days.iter()
+                    .map(|day| day.date)
+                    .collect::<BTreeSet<_>>()
+                    .len()
+                    == days_in_month(*year, *month)
+            })
+            .flat_map(|(_, days)| days)
+            .collect();
+
+        let model = ScoringModel::for_area(area);
+        let mut by_month: BTreeMap<u32, Vec<(&HistoricalDay, f64)>> = BTreeMap::new();
+        for day in &complete {
+            let score = model.score_day(&DailyForecast::from(*day)).score;
+            by_month
+                .entry(day.date.month())
+                .or_default()
+                .push((*day, score));
+        }
+
+        let months = by_month
+            .into_iter()
+            .map(|(month, days)| {
+                // Per month counts are averaged over the years that month was seen in
+                let years = days
+                    .iter()
+                    .map(|(day, _)| day.date.year())
+                    .collect::<BTreeSet<i32>>()
+                    .len() as f64;
+                let per_year = |count: usize| count as f64 / years;
+                MonthClimate {
+                    month,
+                    avg_high: Temperature::from_celsius(
+                        mean(days.iter().map(|(day, _)| day.max_temp.celsius()))
+                            .unwrap_or_default(),
+                    ),
+                    avg_low: Temperature::from_celsius(
+                        mean(days.iter().map(|(day, _)| day.min_temp.celsius()))
+                            .unwrap_or_default(),
+                    ),
+                    monthly_precip: Length::from_mm(
+                        days.iter().map(|(day, _)| day.precip.mm()).sum::<f64>() / years,
+                    ),
+                    rainy_days: per_year(
+                        days.iter()
+                            .filter(|(day, _)| day.precip.mm() >= RAINY_DAY_MM)
+                            .count(),
+                    ),
+                    avg_humidity: mean(days.iter().filter_map(|(day, _)| day.humidity)),
+                    avg_max_wind: mean(
+                        days.iter()
+                            .filter_map(|(day, _)| day.max_wind.map(Speed::ms)),
+                    )
+                    .map(Speed::from_ms),
+                    good_days: per_year(
+                        days.iter()
+                            .filter(|(_, score)| *score >= GOOD_DAY_SCORE)
+                            .count(),
+                    ),
+                    score: mean(days.iter().map(|(_, score)| *score)).unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        Self {
+            area_uuid: area.uuid.clone(),
+            area_name: area.area_name.clone(),
+            lat: area.metadata.lat,
+            lng: area.metadata.lng,
+            from: complete
+                .iter()
+                .map(|day| day.date)
+                .min()
+                .unwrap_or_default(),
+            to: complete
+                .iter()
+                .map(|day| day.date)
+                .max()
+                .unwrap_or_default(),
+            computed_at,
+            months,
+        }
+    }
+
+    pub fn month(&self, month: u32) -> Option<&MonthClimate> {
+        self.months.iter().find(|climate| climate.month == month)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area() -> Area {
+        serde_json::from_str(r#"{"area_name": "Crag", "metadata": {"lat": 49.7, "lng": -123.1}}"#)
+            .unwrap()
+    }
+
+    fn day(date: NaiveDate, max_c: f64, precip_mm: f64) -> HistoricalDay {
+        HistoricalDay {
+            date,
+            max_temp: Temperature::from_celsius(max_c),
+            min_temp: Temperature::from_celsius(max_c - 10.0),
+            precip: Length::from_mm(precip_mm),
+            snow: Length::default(),
+            humidity: Some(50.0),
+            max_wind: Some(Speed::from_kph(10.0)),
+        }
+    }
+
+    // Every day of a month, raining `precip_mm` on the first `rainy` days
+    fn month(year: i32, month: u32, max_c: f64, rainy: u32, precip_mm: f64) -> Vec<HistoricalDay> {
+        (1..=days_in_month(year, month) as u32)
+            .map(|d| {
+                let date = NaiveDate::from_ymd_opt(year, month, d).unwrap();
+                day(date, max_c, if d <= rainy { precip_mm } else { 0.0 })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn month_lengths_follow_the_calendar() {
+        assert_eq!(days_in_month(2023, 1), 31);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 12), 31);
+    }
+
+    #[test]
+    fn monthly_totals_are_averaged_over_the_years() {
+        let history = [month(2021, 7, 24.0, 4, 5.0), month(2022, 7, 26.0, 2, 5.0)].concat();
+        let climatology = Climatology::from_history(&area(), &history, Utc::now());
+
+        let july = climatology.month(7).unwrap();
+        assert_eq!(july.avg_high.celsius(), 25.0);
+        assert_eq!(july.avg_low.celsius(), 15.0);
+        // 20mm one year and 10mm the next
+        assert!((july.monthly_precip.mm() - 15.0).abs() < 1e-9);
+        assert_eq!(july.rainy_days, 3.0);
+        assert_eq!(july.avg_humidity, Some(50.0));
+        assert!((july.avg_max_wind.unwrap().kph() - 10.0).abs() < 1e-9);
+        assert!(july.good_days <= 31.0);
+        assert!(climatology.month(8).is_none());
+    }
+
+    #[test]
+    fn partly_covered_months_are_left_out() {
+        // The archive starts mid June and stops before the end of August
+        let june = month(2022, 6, 20.0, 10, 5.0).split_off(14);
+        let mut august = month(2022, 8, 28.0, 0, 0.0);
+        august.truncate(10);
+        let history = [june, month(2022, 7, 25.0, 0, 0.0), august].concat();
+        let climatology = Climatology::from_history(&area(), &history, Utc::now());
+
+        let months: Vec<u32> = climatology.months.iter().map(|m| m.month).collect();
+        assert_eq!(months, vec![7]);
+        assert_eq!(
+            climatology.from,
+            NaiveDate::from_ymd_opt(2022, 7, 1).unwrap()
+        );
+        assert_eq!(
+            climatology.to,
+            NaiveDate::from_ymd_opt(2022, 7, 31).unwrap()
+        );
+    }
+
+    #[test]
+    fn months_with_a_missing_day_are_left_out() {
+        let mut history = month(2022, 7, 25.0, 0, 0.0);
+        history.remove(15);
+        let climatology = Climatology::from_history(&area(), &history, Utc::now());
+        assert!(climatology.months.is_empty());
+    }
+
+    #[test]
+    fn good_days_count_days_that_score_well() {
+        let history = [
+            month(2021, 5, 18.0, 0, 0.0),
+            month(2021, 1, -15.0, 31, 20.0),
+        ]
+        .concat();
+        let climatology = Climatology::from_history(&area(), &history, Utc::now());
+
+        assert_eq!(climatology.month(5).unwrap().good_days, 31.0);
+        assert_eq!(climatology.month(1).unwrap().good_days, 0.0);
+        assert!(climatology.month(5).unwrap().score > climatology.month(1).unwrap().score);
+    }
+}
@@ -0,0 +1,200 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Discipline {
+    Sport,
+    Trad,
+    Boulder,
+    TopRope,
+    Aid,
+    Alpine,
+    Mixed,
+    Ice,
+    DeepWaterSolo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RockType {
+    Granite,
+    Sandstone,
+    Gritstone,
+    Limestone,
+    Dolomite,
+    Basalt,
+    Gneiss,
+    Quartzite,
+    Conglomerate,
+    Rhyolite,
+    Schist,
+    Volcanic,
+}
+
+// Words that name each rock type in free text, most specific first so "gritstone"
+// is not read as sandstone
+const ROCK_KEYWORDS: [(&str, RockType); 13] = [
+    ("gritstone", RockType::Gritstone),
+    ("sandstone", RockType::Sandstone),
+    ("granite", RockType::Granite),
+    ("limestone", RockType::Limestone),
+    ("dolomite", RockType::Dolomite),
+    ("basalt", RockType::Basalt),
+    ("gneiss", RockType::Gneiss),
+    ("quartzite", RockType::Quartzite),
+    ("conglomerate", RockType::Conglomerate),
+    ("rhyolite", RockType::Rhyolite),
+    ("schist", RockType::Schist),
+    ("welded tuff", RockType::Volcanic),
+    ("volcanic", RockType::Volcanic),
+];
+
+impl RockType {
+    // OpenBeta has no rock type field, so take the first one the description mentions
+    pub fn from_description(description: &str) -> Option<Self> {
+        let text = description.to_lowercase();
+        ROCK_KEYWORDS
+            .iter()
+            .filter_map(|(word, rock)| text.find(word).map(|pos| (pos, *rock)))
+            .min_by_key(|(pos, _)| *pos)
+            .map(|(_, rock)| rock)
+    }
+
+    // Rock that loses strength when wet, holds break if climbed too soon after rain
+    pub fn is_soft(&self) -> bool {
+        matches!(self, Self::Sandstone | Self::Conglomerate)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GradeBands {
+    pub beginner: u32,
+    pub intermediate: u32,
+    pub advanced: u32,
+    pub expert: u32,
+    pub unknown: u32,
+}
+
+// What there is to climb at an area, aggregated over everything below it
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ClimbingInfo {
+    pub total_climbs: u32,
+    #[serde(default)]
+    pub by_discipline: BTreeMap<Discipline, u32>,
+    #[serde(default)]
+    pub grade_bands: GradeBands,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rock_type: Option<RockType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // Metres above sea level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation_m: Option<f64>,
+}
+
+impl ClimbingInfo {
+    pub fn climbs(&self, discipline: Discipline) -> u32 {
+        self.by_discipline.get(&discipline).copied().unwrap_or(0)
+    }
+
+    // Share of the climbs in the discipline, 0.0 when the area has none
+    pub fn share(&self, discipline: Discipline) -> f64 {
+        if self.total_climbs == 0 {
+            0.0
+        } else {
+            self.climbs(discipline) as f64 / self.total_climbs as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rock_types_are_read_from_descriptions() {
+        let cases = [
+            ("Steep GRANITE cracks", RockType::Granite),
+            ("Soft desert sandstone towers", RockType::Sandstone),
+            ("Classic Peak District gritstone edges", RockType::Gritstone),
+            ("Pocketed limestone and dolomite", RockType::Limestone),
+            ("Columnar basalt", RockType::Basalt),
+            ("Featured gneiss slabs", RockType::Gneiss),
+            ("Hard quartzite", RockType::Quartzite),
+            ("Montserrat style conglomerate", RockType::Conglomerate),
+            ("Rhyolite spires", RockType::Rhyolite),
+            ("Mica schist", RockType::Schist),
+            ("Owens River Gorge welded tuff", RockType::Volcanic),
+            ("Volcanic plugs", RockType::Volcanic),
+        ];
+        for (description, rock) in cases {
+            assert_eq!(
+                RockType::from_description(description),
+                Some(rock),
+                "{}",
+                description
+            );
+        }
+    }
+
+    #[test]
+    fn the_first_rock_mentioned_wins() {
+        assert_eq!(
+            RockType::from_description("Sandstone caps over a granite base"),
+            Some(RockType::Sandstone)
+        );
+        assert_eq!(
+            RockType::from_description("Granite, with some sandstone boulders nearby"),
+            Some(RockType::Granite)
+        );
+    }
+
+    #[test]
+    fn descriptions_without_a_rock_have_no_type() {
+        assert_eq!(
+            RockType::from_description("Great views, short approach"),
+            None
+        );
+        assert_eq!(RockType::from_description(""), None);
+    }
+
+    #[test]
+    fn only_rock_weakened_by_water_is_soft() {
+        assert!(RockType::Sandstone.is_soft());
+        assert!(RockType::Conglomerate.is_soft());
+        assert!(!RockType::Granite.is_soft());
+        assert!(!RockType::Gritstone.is_soft());
+        assert!(!RockType::Limestone.is_soft());
+    }
+
+    #[test]
+    fn missing_disciplines_count_as_none() {
+        let info = ClimbingInfo {
+            total_climbs: 20,
+            by_discipline: BTreeMap::from([(Discipline::Sport, 15), (Discipline::Trad, 5)]),
+            ..ClimbingInfo::default()
+        };
+        assert_eq!(info.climbs(Discipline::Sport), 15);
+        assert_eq!(info.climbs(Discipline::Boulder), 0);
+        assert_eq!(info.share(Discipline::Sport), 0.75);
+        assert_eq!(info.share(Discipline::Ice), 0.0);
+        assert_eq!(ClimbingInfo::default().share(Discipline::Sport), 0.0);
+    }
+
+    #[test]
+    fn stored_info_without_the_optional_fields_still_reads() {
+        let info: ClimbingInfo = serde_json::from_str(r#"{"total_climbs": 3}"#).unwrap();
+        assert_eq!(info.total_climbs, 3);
+        assert!(info.by_discipline.is_empty());
+        assert_eq!(info.grade_bands, GradeBands::default());
+        assert_eq!(info.rock_type, None);
+
+        let info: ClimbingInfo = serde_json::from_str(
+            r#"{"total_climbs": 3, "by_discipline": {"deep_water_solo": 2, "top_rope": 1}}"#,
+        )
+        .unwrap();
+        assert_eq!(info.climbs(Discipline::DeepWaterSolo), 2);
+        assert_eq!(info.climbs(Discipline::TopRope), 1);
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    climbing::RockType,
+    forecast::{AreaForecast, Observation},
+    solar::{self, Exposure, WallSun},
+    units::{Length, Reading, Units},
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+// Water left on the rock, in mm, below which it counts as dry
+const DRY_FILM_MM: f64 = 0.05;
+// Fresh snow holds roughly a millimetre of water per centimetre
+const SNOW_WATER_MM_PER_CM: f64 = 1.0;
+// Soft rock still wet at this hour rules out the day
+const MIDDAY: u32 = 12;
+// Rain this long before midday is counted in the reason
+const RECENT_RAIN_HOURS: i64 = 48;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RockCondition {
+    pub date: NaiveDate,
+    // Local time the rock should be dry again, None when it is dry all day
+    pub wet_until: Option<NaiveDateTime>,
+    // Soft rock that is still wet at midday, climbing it would break holds
+    pub do_not_climb: bool,
+    pub reason: String,
+    // The rain the reason quotes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<Reading>,
+}
+
+// Rain and melted snow an hour puts on the rock, in mm
+fn water_mm(hour: &Observation) -> f64 {
+    hour.precip.mm() + hour.snow.map_or(0.0, Length::cm) * SNOW_WATER_MM_PER_CM
+}
+
+// Water a surface holds before the rest runs off, porous rock soaks up more
+fn holding_mm(rock: Option<RockType>) -> f64 {
+    match rock {
+        Some(RockType::Sandstone) => 3.0,
+        Some(RockType::Conglomerate) => 2.5,
+        Some(RockType::Gritstone) => 1.5,
+        Some(RockType::Schist | RockType::Volcanic) => 1.2,
+        Some(RockType::Limestone | RockType::Dolomite) | None => 1.0,
+        Some(
+            RockType::Granite
+            | RockType::Gneiss
+            | RockType::Quartzite
+            | RockType::Basalt
+            | RockType::Rhyolite,
+        ) => 0.8,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DryingModel {
+    // mm evaporated per hour in mild, still, overcast air at 50% humidity
+    pub base_rate_mm: f64,
+    // Soft rock stays weak this long after its surface looks dry
+    pub soft_rock_extra_hours: i64,
+}
+
+impl Default for DryingModel {
+    fn default() -> Self {
+        Self {
+            base_rate_mm: 0.15,
+            soft_rock_extra_hours: 24,
+        }
+    }
+}
+
+impl DryingModel {
+    // Evaporation in mm over one hour. Dry air, wind, warmth and sun on the wall
+    // all speed it up.
+    pub fn drying_rate(&self, hour: &Observation, sun: Option<WallSun>) -> f64 {
+        let humidity = ((100.0 - hour.humidity) / 50.0).clamp(0.1, 1.5);
+        let wind = 1.0 + hour.wind.kph() / 20.0;
+        let warmth = ((hour.temp.celsius() + 5.0) / 20.0).clamp(0.1, 2.0);
+        let sunshine = 1.0 - hour.cloud / 100.0;
+        let sun = match sun {
+            Some(WallSun {
+                exposure: Exposure::Sun,
+                incidence,
+            }) => 1.0 + 2.0 * incidence * sunshine,
+            Some(WallSun {
+                exposure: Exposure::Shade,
+                ..
+            }) => 0.8,
+            Some(WallSun {
+                exposure: Exposure::Night,
+                ..
+            }) => 0.5,
+            None if hour.is_day == Some(false) => 0.5,
+            None => 1.0 + 0.5 * sunshine,
+        };
+        self.base_rate_mm * humidity * wind * warmth * sun
+    }
+
+    // One condition per forecast day. recent holds observed hours before the forecast
+    // so rain that already fell is counted.
+    pub fn rock_conditions(
+        &self,
+        forecast: &AreaForecast,
+        rock: Option<RockType>,
+        aspect_deg: Option<f64>,
+        recent: &[Observation],
+    ) -> Vec<RockCondition> {
+        let mut hours: Vec<&Observation> = recent
+            .iter()
+            .chain(forecast.days.iter().flat_map(|day| day.hours.iter()))
+            .collect();
+        // Stable, so an observed hour wins over the forecast for the same time
+        hours.sort_by_key(|hour| hour.time);
+        hours.dedup_by_key(|hour| hour.time);
+
+        let soft = rock.is_some_and(|rock| rock.is_soft());
+        let holding = holding_mm(rock);
+        let mut water = 0.0;
+        let mut soaked_until: Option<NaiveDateTime> = None;
+        let wet: Vec<bool> = hours
+            .iter()
+            .map(|hour| {
+                let was_wet = water > DRY_FILM_MM;
+                let sun = aspect_deg.map(|aspect| {
+                    solar::wall_sun(
+                        aspect,
+                        solar::sun_position_local(
+                            forecast.lat,
+                            forecast.lng,
+                            hour.time,
+                            forecast.utc_offset_seconds,
+                        ),
+                    )
+                });
+                let added = water_mm(hour);
+                water = (water + added).min(holding);
+                water = (water - self.drying_rate(hour, sun)).max(0.0);
+                if soft && was_wet && water <= DRY_FILM_MM {
+                    soaked_until = Some(hour.time + Duration::hours(self.soft_rock_extra_hours));
+                }
+                water > DRY_FILM_MM || soaked_until.is_some_and(|until| hour.time < until)
+            })
+            .collect();
+
+        forecast
+            .days
+            .iter()
+            .map(|day| {
+                let in_day = |time: NaiveDateTime| time.date() == day.date;
+                let Some(last_wet) = (0..hours.len())
+                    .rev()
+                    .find(|&i| wet[i] && in_day(hours[i].time))
+                else {
+                    // Providers without hourly data only give the daily totals
+                    let water = day.total_precip.mm() + day.total_snow.cm() * SNOW_WATER_MM_PER_CM;
+                    if day.hours.is_empty() && water > holding {
+                        let reading = Reading::Precipitation(Length::from_mm(water));
+                        return RockCondition {
+                            date: day.date,
+                            wet_until: day
+                                .date
+                                .succ_opt()
+                                .map(|next| next.and_time(NaiveTime::MIN)),
+                            do_not_climb: soft,
+                            reason: format!(
+                                "{} of rain forecast, rock wet",
+                                reading.describe(Units::Metric)
+                            ),
+                            reading: Some(reading),
+                        };
+                    }
+                    return RockCondition {
+                        date: day.date,
+                        wet_until: None,
+                        do_not_climb: false,
+                        reason: "rock dry".to_string(),
+                        reading: None,
+                    };
+                };
+
+                // Dry from the first dry hour after the day's last wet one
+                let wet_until = hours[last_wet..]
+                    .iter()
+                    .zip(&wet[last_wet..])
+                    .find(|(_, wet)| !**wet)
+                    .map(|(hour, _)| hour.time)
+                    .unwrap_or(hours[hours.len() - 1].time + Duration::hours(1));
+                let midday = day.date.and_hms_opt(MIDDAY, 0, 0).unwrap_or_default();
+                let do_not_climb = soft && wet_until > midday;
+                let rain_mm: f64 = hours
+                    .iter()
+                    .filter(|hour| {
+                        hour.time <= midday
+                            && hour.time > midday - Duration::hours(RECENT_RAIN_HOURS)
+                    })
+                    .map(|hour| water_mm(hour))
+                    .sum();
+                let reading =
+                    do_not_climb.then(|| Reading::Precipitation(Length::from_mm(rain_mm)));
+                let reason = match reading {
+                    Some(reading) => format!(
+                        "soft rock still wet at midday after {} of rain, dry from {}",
+                        reading.describe(Units::Metric),
+                        wet_until.format("%a %H:%M")
+                    ),
+                    None => format!("rock wet until {}", wet_until.format("%a %H:%M")),
+                };
+                RockCondition {
+                    date: day.date,
+                    wet_until: Some(wet_until),
+                    do_not_climb,
+                    reason,
+                    reading,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        forecast::DailyForecast,
+        units::{Speed, Temperature},
+    };
+    use chrono::Utc;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 6, day).unwrap()
+    }
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        date(day).and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    // Mild, still and overcast at 50% humidity, so the rock dries at the base rate
+    fn hour(day: u32, hour: u32, precip_mm: f64) -> Observation {
+        Observation {
+            time: at(day, hour),
+            temp: Temperature::from_celsius(15.0),
+            feels_like: None,
+            humidity: 50.0,
+            wind: Speed::from_kph(0.0),
+            gust: None,
+            wind_degree: None,
+            precip: Length::from_mm(precip_mm),
+            snow: None,
+            cloud: 100.0,
+            chance_of_rain: None,
+            uv: None,
+            is_day: Some(true),
+            condition: None,
+            us_epa_index: None,
+        }
+    }
+
+    // A dry first day, then rain at midnight starting the second
+    fn forecast(days: u32) -> AreaForecast {
+        AreaForecast {
+            provider: "test".to_string(),
+            lat: 45.0,
+            lng: 6.0,
+            utc_offset_seconds: 0,
+            fetched_at: Utc::now(),
+            elevation_m: None,
+            current: None,
+            days: (1..=days)
+                .filter_map(|day| {
+                    let hours = (0..24)
+                        .map(|h| hour(day, h, if day == 2 && h == 0 { 5.0 } else { 0.0 }))
+                        .collect();
+                    DailyForecast::from_hours(date(day), hours)
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn base_conditions_dry_at_the_base_rate() {
+        let model = DryingModel::default();
+        assert!((model.drying_rate(&hour(1, 12, 0.0), None) - model.base_rate_mm).abs() < 1e-9);
+        let night = Observation {
+            is_day: Some(false),
+            ..hour(1, 0, 0.0)
+        };
+        assert!((model.drying_rate(&night, None) - model.base_rate_mm / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rain_dries_off_by_a_known_hour() {
+        let conditions = DryingModel::default().rock_conditions(&forecast(2), None, None, &[]);
+
+        assert_eq!(conditions.len(), 2);
+        assert_eq!(conditions[0].wet_until, None);
+        assert!(!conditions[0].do_not_climb);
+        // 1mm held on the rock, 0.15mm gone an hour, under the dry film after 06:00
+        assert_eq!(conditions[1].wet_until, Some(at(2, 6)));
+        assert!(!conditions[1].do_not_climb);
+    }
+
+    #[test]
+    fn recent_rain_is_counted() {
+        let recent = vec![hour(1, 0, 5.0)];
+        let mut forecast = forecast(1);
+        forecast.days[0].hours.retain(|hour| hour.time > at(1, 0));
+        let conditions = DryingModel::default().rock_conditions(&forecast, None, None, &recent);
+
+        assert_eq!(conditions[0].wet_until, Some(at(1, 6)));
+    }
+
+    #[test]
+    fn soft_rock_stays_soaked_after_it_looks_dry() {
+        let conditions = DryingModel::default().rock_conditions(
+            &forecast(3),
+            Some(RockType::Sandstone),
+            None,
+            &[],
+        );
+
+        // Sandstone holds 3mm, dry on the surface at 19:00 and soaked for a day more
+        assert_eq!(conditions[1].wet_until, Some(at(3, 19)));
+        assert!(conditions[1].do_not_climb);
+        assert_eq!(conditions[2].wet_until, Some(at(3, 19)));
+        assert!(conditions[2].do_not_climb);
+    }
+
+    #[test]
+    fn daily_totals_are_used_without_hours() {
+        let mut forecast = forecast(2);
+        forecast.days.iter_mut().for_each(|day| day.hours.clear());
+        forecast.days[1].total_precip = Length::from_mm(5.0);
+        let conditions =
+            DryingModel::default().rock_conditions(&forecast, Some(RockType::Sandstone), None, &[]);
+
+        assert_eq!(conditions[0].wet_until, None);
+        assert_eq!(conditions[1].wet_until, Some(at(3, 0)));
+        assert!(conditions[1].do_not_climb);
+    }
+}
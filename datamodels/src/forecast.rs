@@ -0,0 +1,146 @@
+use crate::units::{Length, Speed, Temperature};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Provider neutral forecast. Every weather backend maps its own response into this
+// shape so scoring and storage never depend on a single vendor's json.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AreaForecast {
+    pub provider: String,
+    pub lat: f64,
+    pub lng: f64,
+    // Offset of the local times below from UTC
+    pub utc_offset_seconds: i32,
+    pub fetched_at: DateTime<Utc>,
+    // Height of the provider's model grid cell, when it reports one
+    #[serde(default)]
+    pub elevation_m: Option<f64>,
+    pub current: Option<Observation>,
+    pub days: Vec<DailyForecast>,
+}
+
+// Standard atmosphere cooling per metre of height
+const LAPSE_RATE_C_PER_M: f64 = 0.0065;
+
+impl AreaForecast {
+    // Model grid cells average the terrain, so a crag high above the cell is colder
+    // than forecast. Shifts every temperature to the crag's elevation.
+    pub fn adjusted_to_elevation(mut self, elevation_m: f64) -> Self {
+        let Some(grid_m) = self.elevation_m else {
+            return self;
+        };
+        let delta = (grid_m - elevation_m) * LAPSE_RATE_C_PER_M;
+        let adjust = |hour: &mut Observation| {
+            hour.temp = hour.temp.shifted(delta);
+            hour.feels_like = hour.feels_like.map(|temp| temp.shifted(delta));
+        };
+        if let Some(current) = self.current.as_mut() {
+            adjust(current);
+        }
+        for day in &mut self.days {
+            day.max_temp = day.max_temp.shifted(delta);
+            day.min_temp = day.min_temp.shifted(delta);
+            day.avg_temp = day.avg_temp.shifted(delta);
+            day.hours.iter_mut().for_each(adjust);
+        }
+        self.elevation_m = Some(elevation_m);
+        self
+    }
+}
+
+// A single point in time, used both for current conditions and hourly forecasts. Stored
+// field names keep the units they are written in.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Observation {
+    // Local time at the forecast location
+    pub time: NaiveDateTime,
+    #[serde(rename = "temp_c")]
+    pub temp: Temperature,
+    #[serde(rename = "feelslike_c")]
+    pub feels_like: Option<Temperature>,
+    pub humidity: f64,
+    #[serde(rename = "wind_ms")]
+    pub wind: Speed,
+    #[serde(rename = "gust_ms")]
+    pub gust: Option<Speed>,
+    pub wind_degree: Option<f64>,
+    #[serde(rename = "precip_m")]
+    pub precip: Length,
+    #[serde(rename = "snow_m", default)]
+    pub snow: Option<Length>,
+    pub cloud: f64,
+    pub chance_of_rain: Option<f64>,
+    pub uv: Option<f64>,
+    pub is_day: Option<bool>,
+    pub condition: Option<String>,
+    pub us_epa_index: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DailyForecast {
+    pub date: NaiveDate,
+    #[serde(rename = "max_temp_c")]
+    pub max_temp: Temperature,
+    #[serde(rename = "min_temp_c")]
+    pub min_temp: Temperature,
+    #[serde(rename = "avg_temp_c")]
+    pub avg_temp: Temperature,
+    #[serde(rename = "max_wind_ms")]
+    pub max_wind: Speed,
+    #[serde(rename = "total_precip_m")]
+    pub total_precip: Length,
+    #[serde(rename = "total_snow_m")]
+    pub total_snow: Length,
+    pub avg_humidity: Option<f64>,
+    pub chance_of_rain: Option<f64>,
+    pub uv: Option<f64>,
+    pub condition: Option<String>,
+    pub us_epa_index: Option<u8>,
+    pub sunrise: Option<NaiveTime>,
+    pub sunset: Option<NaiveTime>,
+    pub hours: Vec<Observation>,
+}
+
+impl DailyForecast {
+    // Build a daily summary for providers that only return hourly data.
+    // Returns None when there are no hours to summarise.
+    pub fn from_hours(date: NaiveDate, hours: Vec<Observation>) -> Option<Self> {
+        if hours.is_empty() {
+            return None;
+        }
+
+        let count = hours.len() as f64;
+        let temps = || hours.iter().map(|h| h.temp.celsius());
+        let max_temp = Temperature::from_celsius(temps().fold(f64::MIN, f64::max));
+        let min_temp = Temperature::from_celsius(temps().fold(f64::MAX, f64::min));
+        let avg_temp = Temperature::from_celsius(temps().sum::<f64>() / count);
+        let max_wind = Speed::from_ms(hours.iter().map(|h| h.wind.ms()).fold(0.0, f64::max));
+        let total_precip = hours.iter().map(|h| h.precip).sum();
+        let total_snow = hours.iter().filter_map(|h| h.snow).sum();
+        let avg_humidity = Some(hours.iter().map(|h| h.humidity).sum::<f64>() / count);
+        let chance_of_rain = hours
+            .iter()
+            .filter_map(|h| h.chance_of_rain)
+            .reduce(f64::max);
+        let uv = hours.iter().filter_map(|h| h.uv).reduce(f64::max);
+        let us_epa_index = hours.iter().filter_map(|h| h.us_epa_index).max();
+
+        Some(Self {
+            date,
+            max_temp,
+            min_temp,
+            avg_temp,
+            max_wind,
+            total_precip,
+            total_snow,
+            avg_humidity,
+            chance_of_rain,
+            uv,
+            condition: None,
+            us_epa_index,
+            sunrise: None,
+            sunset: None,
+            hours,
+        })
+    }
+}
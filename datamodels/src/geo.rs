@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+// Great circle distance between two coordinates
+pub fn haversine_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lng = (lng2 - lng1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoordError {
+    NotFinite(&'static str),
+    LatitudeOutOfRange(f64),
+}
+
+impl fmt::Display for CoordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFinite(name) => write!(f, "{} must be a finite number", name),
+            Self::LatitudeOutOfRange(lat) => write!(
+                f,
+                "lat must be between -90 and 90, got {} (were lat and lng swapped?)",
+                lat
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CoordError {}
+
+// Degrees north, always within -90 to 90
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct Latitude(f64);
+
+impl Latitude {
+    pub fn new(lat: f64) -> Result<Self, CoordError> {
+        if !lat.is_finite() {
+            return Err(CoordError::NotFinite("lat"));
+        }
+        if !(-90.0..=90.0).contains(&lat) {
+            return Err(CoordError::LatitudeOutOfRange(lat));
+        }
+        Ok(Self(lat))
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.0
+    }
+}
+
+impl TryFrom<f64> for Latitude {
+    type Error = CoordError;
+
+    fn try_from(lat: f64) -> Result<Self, Self::Error> {
+        Self::new(lat)
+    }
+}
+
+impl From<Latitude> for f64 {
+    fn from(lat: Latitude) -> Self {
+        lat.0
+    }
+}
+
+// Degrees east, always within -180 to 180. Longitudes past the antimeridian, like 190
+// from a map that has scrolled round the world, are wrapped back to -170.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct Longitude(f64);
+
+impl Longitude {
+    pub fn new(lng: f64) -> Result<Self, CoordError> {
+        if !lng.is_finite() {
+            return Err(CoordError::NotFinite("lng"));
+        }
+        if (-180.0..=180.0).contains(&lng) {
+            return Ok(Self(lng));
+        }
+        Ok(Self((lng + 180.0).rem_euclid(360.0) - 180.0))
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.0
+    }
+}
+
+impl TryFrom<f64> for Longitude {
+    type Error = CoordError;
+
+    fn try_from(lng: f64) -> Result<Self, Self::Error> {
+        Self::new(lng)
+    }
+}
+
+impl From<Longitude> for f64 {
+    fn from(lng: Longitude) -> Self {
+        lng.0
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GeoPoint {
+    pub lat: Latitude,
+    pub lng: Longitude,
+}
+
+impl GeoPoint {
+    pub fn new(lat: f64, lng: f64) -> Result<Self, CoordError> {
+        Ok(Self {
+            lat: Latitude::new(lat)?,
+            lng: Longitude::new(lng)?,
+        })
+    }
+
+    pub fn lat(&self) -> f64 {
+        self.lat.degrees()
+    }
+
+    pub fn lng(&self) -> f64 {
+        self.lng.degrees()
+    }
+
+    pub fn distance_km(&self, lat: f64, lng: f64) -> f64 {
+        haversine_km(self.lat(), self.lng(), lat, lng)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeoJsonType {
+    Point,
+}
+
+// GeoJSON point as stored in mongo for 2dsphere indexing. Note the coordinate
+// order is [lng, lat].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeoJsonPoint {
+    #[serde(rename = "type")]
+    pub kind: GeoJsonType,
+    pub coordinates: [f64; 2],
+}
+
+impl GeoJsonPoint {
+    pub fn new(lat: f64, lng: f64) -> Self {
+        Self {
+            kind: GeoJsonType::Point,
+            coordinates: [lng, lat],
+        }
+    }
+
+    pub fn lat(&self) -> f64 {
+        self.coordinates[1]
+    }
+
+    pub fn lng(&self) -> f64 {
+        self.coordinates[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latitudes_must_be_on_the_globe() {
+        assert_eq!(Latitude::new(49.7).unwrap().degrees(), 49.7);
+        assert_eq!(Latitude::new(-90.0).unwrap().degrees(), -90.0);
+        assert_eq!(
+            Latitude::new(-123.1),
+            Err(CoordError::LatitudeOutOfRange(-123.1))
+        );
+        assert_eq!(Latitude::new(f64::NAN), Err(CoordError::NotFinite("lat")));
+        assert!(serde_json::from_str::<Latitude>("91.0").is_err());
+    }
+
+    #[test]
+    fn longitudes_wrap_round_the_antimeridian() {
+        assert_eq!(Longitude::new(-123.1).unwrap().degrees(), -123.1);
+        assert_eq!(Longitude::new(180.0).unwrap().degrees(), 180.0);
+        assert_eq!(Longitude::new(190.0).unwrap().degrees(), -170.0);
+        assert_eq!(Longitude::new(-190.0).unwrap().degrees(), 170.0);
+        assert_eq!(Longitude::new(720.0).unwrap().degrees(), 0.0);
+        assert_eq!(
+            Longitude::new(f64::INFINITY),
+            Err(CoordError::NotFinite("lng"))
+        );
+        let lng: Longitude = serde_json::from_str("200.0").unwrap();
+        assert_eq!(lng.degrees(), -160.0);
+    }
+
+    #[test]
+    fn points_check_both_coordinates() {
+        let point = GeoPoint::new(49.7, 236.9).unwrap();
+        assert_eq!(point.lat(), 49.7);
+        assert!((point.lng() - -123.1).abs() < 1e-9);
+        assert!(GeoPoint::new(123.1, 49.7).is_err());
+    }
+
+    #[test]
+    fn distances_are_great_circles() {
+        // A degree of latitude is about 111km anywhere
+        assert!((haversine_km(49.0, -123.0, 50.0, -123.0) - 111.19).abs() < 0.01);
+        // Across the antimeridian is short, not most of the way round
+        assert!(haversine_km(0.0, 179.5, 0.0, -179.5) < 112.0);
+        let point = GeoPoint::new(49.7, -123.1).unwrap();
+        assert_eq!(point.distance_km(49.7, -123.1), 0.0);
+    }
+
+    #[test]
+    fn geojson_points_are_lng_first() {
+        let point = GeoJsonPoint::new(49.7, -123.1);
+        assert_eq!(point.coordinates, [-123.1, 49.7]);
+        assert_eq!(point.lat(), 49.7);
+        assert_eq!(point.lng(), -123.1);
+    }
+}
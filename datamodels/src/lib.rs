@@ -1,18 +1,161 @@
-use chrono::{NaiveDate, NaiveDateTime};
+pub mod api_key;
+pub mod archive;
+pub mod climate;
+pub mod climbing;
+pub mod drying;
+mod forecast;
+pub mod geo;
+pub mod profile;
+pub mod rollup;
+pub mod scoring;
+pub mod solar;
+pub mod trip;
+pub mod units;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{de, Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug)]
+use climbing::ClimbingInfo;
+use drying::{DryingModel, RockCondition};
+pub use forecast::{AreaForecast, DailyForecast, Observation};
+use geo::{CoordError, GeoJsonPoint, GeoPoint};
+use profile::Preferences;
+use scoring::{DayScore, ScoredDay, ScoringModel, Site};
+use units::{Length, Speed, Temperature};
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Area {
+    // Stable OpenBeta area id, missing for areas imported before the id was synced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
     pub area_name: String,
     pub metadata: Metadata,
+    // Filled in from metadata when the area is stored, see Area::with_location
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoJsonPoint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_uuid: Option<String>,
+    // Area ids from the country down to the parent
+    #[serde(default)]
+    pub ancestors: Vec<String>,
+    // Area names from the country down to this area
+    #[serde(default)]
+    pub path_tokens: Vec<String>,
+    // Leaf areas hold climbs rather than other areas
+    #[serde(default = "default_true")]
+    pub is_leaf: bool,
+    // Crags are where forecasts are fetched: the smallest area grouping nearby walls.
+    // Areas imported without a hierarchy count as their own crag.
+    #[serde(default = "default_true")]
+    pub is_crag: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub climbing: Option<ClimbingInfo>,
+    // Compass bearing the main wall faces, 180 is south facing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspect_deg: Option<f64>,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+fn default_true() -> bool {
+    true
+}
+
+impl Area {
+    // Names the area by its path so duplicates like "Main Wall" can be told apart
+    pub fn path(&self) -> String {
+        if self.path_tokens.is_empty() {
+            self.area_name.clone()
+        } else {
+            self.path_tokens.join(" / ")
+        }
+    }
+
+    pub fn elevation_m(&self) -> Option<f64> {
+        self.climbing
+            .as_ref()
+            .and_then(|climbing| climbing.elevation_m)
+    }
+
+    // Scores a forecast for this area: shifted to its elevation, weighted for what is
+    // climbed here and marked down while rain leaves the rock wet. recent holds
+    // observed hours before the forecast.
+    pub fn score_forecast(&self, forecast: AreaForecast, recent: &[Observation]) -> Vec<ScoredDay> {
+        self.score_forecast_for(forecast, recent, None)
+    }
+
+    // Same as score_forecast, weighted for a climber's preferences when given
+    pub fn score_forecast_for(
+        &self,
+        forecast: AreaForecast,
+        recent: &[Observation],
+        preferences: Option<&Preferences>,
+    ) -> Vec<ScoredDay> {
+        let forecast = match self.elevation_m() {
+            Some(elevation_m) => forecast.adjusted_to_elevation(elevation_m),
+            None => forecast,
+        };
+        let model = match preferences {
+            Some(preferences) => preferences.model_for(self),
+            None => ScoringModel::for_area(self),
+        };
+        let rock_type = self
+            .climbing
+            .as_ref()
+            .and_then(|climbing| climbing.rock_type);
+        let conditions =
+            DryingModel::default().rock_conditions(&forecast, rock_type, self.aspect_deg, recent);
+        model
+            .score_forecast(&forecast)
+            .into_iter()
+            .zip(conditions)
+            .map(|(mut day, rock)| {
+                model.apply_rock_condition(&mut day.score, &rock);
+                day.rock = Some(rock);
+                day
+            })
+            .collect()
+    }
+
+    // True for the region itself and for every area below it
+    pub fn is_within(&self, region_uuid: &str) -> bool {
+        self.uuid.as_deref() == Some(region_uuid)
+            || self.ancestors.iter().any(|uuid| uuid == region_uuid)
+    }
+
+    // Copy of the area with its GeoJSON location set from the metadata coordinates
+    pub fn with_location(&self) -> Self {
+        Self {
+            location: Some(GeoJsonPoint::new(self.metadata.lat, self.metadata.lng)),
+            ..self.clone()
+        }
+    }
+
+    // Like with_location, after checking the coordinates and wrapping the longitude
+    pub fn located(&self) -> Result<Self, CoordError> {
+        let point = self.metadata.point()?;
+        Ok(Self {
+            metadata: Metadata {
+                lat: point.lat(),
+                lng: point.lng(),
+            },
+            ..self.clone()
+        }
+        .with_location())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Metadata {
     pub lat: f64,
     pub lng: f64,
 }
 
+impl Metadata {
+    // The coordinates as a checked point, with the longitude wrapped into range
+    pub fn point(&self) -> Result<GeoPoint, CoordError> {
+        GeoPoint::new(self.lat, self.lng)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Data {
     pub areas: Vec<Area>,
@@ -21,7 +164,7 @@ mod weather_datetime_format {
     use chrono::NaiveDateTime;
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M";
+    const FORMAT: &str = "%Y-%m-%d %H:%M";
 
     pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -44,7 +187,7 @@ mod weather_date_format {
     use chrono::NaiveDate;
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d";
+    const FORMAT: &str = "%Y-%m-%d";
 
     pub fn serialize<S>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -85,19 +228,21 @@ pub struct Location {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Current {
+    #[serde(with = "weather_datetime_format")]
+    last_updated: NaiveDateTime,
     temp_c: f64,
-    temp_f: f64,
     condition: Condition,
-    wind_mph: f64,
     wind_kph: f64,
     wind_degree: f64,
     precip_mm: f64,
-    precip_in: f64,
     humidity: f64,
     cloud: f64,
     feelslike_c: f64,
-    feelslike_f: f64,
     uv: f64,
+    #[serde(default)]
+    gust_kph: Option<f64>,
+    #[serde(deserialize_with = "deserialize_int_to_bool")]
+    is_day: bool,
     air_quality: AirQuality,
 }
 
@@ -139,22 +284,21 @@ struct ForecastDay {
 #[derive(Serialize, Deserialize, Debug)]
 struct Day {
     maxtemp_c: f64,
-    maxtemp_f: f64,
     mintemp_c: f64,
-    mintemp_f: f64,
     avgtemp_c: f64,
-    avgtemp_f: f64,
-    maxwind_mph: f64,
     maxwind_kph: f64,
     totalprecip_mm: f64,
-    totalprecip_in: f64,
     totalsnow_cm: f64,
+    avghumidity: f64,
     daily_chance_of_rain: f64,
+    uv: f64,
     condition: Condition,
     air_quality: AirQuality,
 }
 #[derive(Serialize, Deserialize, Debug)]
 struct Astro {
+    sunrise: String,
+    sunset: String,
     #[serde(deserialize_with = "deserialize_int_to_bool")]
     is_moon_up: bool,
     #[serde(deserialize_with = "deserialize_int_to_bool")]
@@ -163,13 +307,28 @@ struct Astro {
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Hour {
-    condition: NoCondition,
-    air_quality: AirQuality,
+    #[serde(with = "weather_datetime_format")]
+    time: NaiveDateTime,
+    temp_c: f64,
+    #[serde(deserialize_with = "deserialize_int_to_bool")]
+    is_day: bool,
+    condition: Condition,
+    wind_kph: f64,
+    wind_degree: f64,
+    precip_mm: f64,
+    #[serde(default)]
+    snow_cm: Option<f64>,
+    humidity: f64,
+    cloud: f64,
+    feelslike_c: f64,
+    chance_of_rain: f64,
+    gust_kph: f64,
+    uv: f64,
+    #[serde(default)]
+    air_quality: Option<AirQuality>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct NoCondition {}
-
+// weatherapi.com flags are 1 for true and 0 for false
 fn deserialize_int_to_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
     D: de::Deserializer<'de>,
@@ -177,8 +336,8 @@ where
     let val: i8 = de::Deserialize::deserialize(deserializer)?;
 
     match val {
-        0 => Ok(true),
-        1 => Ok(false),
+        0 => Ok(false),
+        1 => Ok(true),
         _ => Err(de::Error::unknown_variant(
             &val.to_string()[..],
             &["0", "1"],
@@ -186,21 +345,269 @@ where
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+// Astro times come back as e.g. "06:57 AM" or "No sunset" near the poles
+fn parse_astro_time(val: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(val, "%I:%M %p").ok()
+}
+
+impl From<Hour> for Observation {
+    fn from(hour: Hour) -> Self {
+        Self {
+            time: hour.time,
+            temp: Temperature::from_celsius(hour.temp_c),
+            feels_like: Some(Temperature::from_celsius(hour.feelslike_c)),
+            humidity: hour.humidity,
+            wind: Speed::from_kph(hour.wind_kph),
+            gust: Some(Speed::from_kph(hour.gust_kph)),
+            wind_degree: Some(hour.wind_degree),
+            precip: Length::from_mm(hour.precip_mm),
+            snow: hour.snow_cm.map(Length::from_cm),
+            cloud: hour.cloud,
+            chance_of_rain: Some(hour.chance_of_rain),
+            uv: Some(hour.uv),
+            is_day: Some(hour.is_day),
+            condition: Some(hour.condition.text),
+            us_epa_index: hour.air_quality.map(|aq| aq.us_epa_index as u8),
+        }
+    }
+}
+
+impl From<ForecastDay> for DailyForecast {
+    fn from(forecast_day: ForecastDay) -> Self {
+        let day = forecast_day.day;
+        Self {
+            date: forecast_day.date,
+            max_temp: Temperature::from_celsius(day.maxtemp_c),
+            min_temp: Temperature::from_celsius(day.mintemp_c),
+            avg_temp: Temperature::from_celsius(day.avgtemp_c),
+            max_wind: Speed::from_kph(day.maxwind_kph),
+            total_precip: Length::from_mm(day.totalprecip_mm),
+            total_snow: Length::from_cm(day.totalsnow_cm),
+            avg_humidity: Some(day.avghumidity),
+            chance_of_rain: Some(day.daily_chance_of_rain),
+            uv: Some(day.uv),
+            condition: Some(day.condition.text),
+            us_epa_index: Some(day.air_quality.us_epa_index as u8),
+            sunrise: parse_astro_time(&forecast_day.astro.sunrise),
+            sunset: parse_astro_time(&forecast_day.astro.sunset),
+            hours: forecast_day
+                .hour
+                .into_iter()
+                .map(Observation::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<WeatherResponse> for AreaForecast {
+    fn from(response: WeatherResponse) -> Self {
+        let location = response.location;
+        let current = response.current;
+        // localtime is the wall clock at the location when localtime_epoch was taken
+        let utc_offset = location.localtime
+            - DateTime::from_timestamp(location.localtime_epoch as i64, 0)
+                .map(|utc| utc.naive_utc())
+                .unwrap_or(location.localtime);
+        // Offsets are whole quarter hours, strip the seconds lost to the minute resolution
+        let utc_offset_seconds = (utc_offset.num_seconds() as f64 / 900.0).round() as i32 * 900;
+
+        Self {
+            provider: "weatherapi".to_string(),
+            lat: location.lat,
+            lng: location.lon,
+            utc_offset_seconds,
+            fetched_at: Utc::now(),
+            elevation_m: None,
+            current: Some(Observation {
+                time: current.last_updated,
+                temp: Temperature::from_celsius(current.temp_c),
+                feels_like: Some(Temperature::from_celsius(current.feelslike_c)),
+                humidity: current.humidity,
+                wind: Speed::from_kph(current.wind_kph),
+                gust: current.gust_kph.map(Speed::from_kph),
+                wind_degree: Some(current.wind_degree),
+                precip: Length::from_mm(current.precip_mm),
+                snow: None,
+                cloud: current.cloud,
+                chance_of_rain: None,
+                uv: Some(current.uv),
+                is_day: Some(current.is_day),
+                condition: Some(current.condition.text),
+                us_epa_index: Some(current.air_quality.us_epa_index as u8),
+            }),
+            days: response
+                .forecast
+                .forecastday
+                .into_iter()
+                .map(DailyForecast::from)
+                .collect(),
+        }
+    }
+}
+
+// One stored forecast day for one area. Documents are keyed by the area and the
+// forecast date so a refresh replaces the previous forecast for that day.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AreaWeather {
+    // Missing on days stored before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
     pub area_name: String,
+    pub lat: f64,
+    pub lng: f64,
+    #[serde(with = "weather_date_format")]
+    pub date: NaiveDate,
+    pub provider: String,
+    pub fetched_at: DateTime<Utc>,
+    pub forecast: DailyForecast,
+    pub score: DayScore,
+    #[serde(default)]
+    pub rock: Option<RockCondition>,
+    // Where the forecast was for, missing on days stored before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub site: Option<Site>,
+}
+
+impl AreaWeather {
+    // Score the stored day again with another model, placing the sun as the first
+    // scoring did
+    pub fn rescore(&mut self, model: &ScoringModel) {
+        self.score = model.score_day_at(&self.forecast, self.site);
+        if let Some(rock) = &self.rock {
+            model.apply_rock_condition(&mut self.score, rock);
+        }
+    }
 }
 
 pub struct ResponseAndArea {
-    pub response: WeatherResponse,
+    pub response: AreaForecast,
     pub area: Area,
+    // Archived hours before the forecast, used to tell if recent rain left the rock wet
+    pub recent: Vec<Observation>,
 }
 
-// TODO trait to map between weather data models
-impl From<ResponseAndArea> for AreaWeather {
+impl From<ResponseAndArea> for Vec<AreaWeather> {
     fn from(ra: ResponseAndArea) -> Self {
-        Self {
-            area_name: ra.area.area_name,
+        let provider = ra.response.provider.clone();
+        let fetched_at = ra.response.fetched_at;
+        let site = Site::of(&ra.response);
+        ra.area
+            .score_forecast(ra.response, &ra.recent)
+            .into_iter()
+            .map(|day| AreaWeather {
+                uuid: ra.area.uuid.clone(),
+                area_name: ra.area.area_name.clone(),
+                lat: ra.area.metadata.lat,
+                lng: ra.area.metadata.lng,
+                date: day.forecast.date,
+                provider: provider.clone(),
+                fetched_at,
+                forecast: day.forecast,
+                score: day.score,
+                rock: day.rock,
+                site: Some(site),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // weatherapi.com sends flags as 1 for true and 0 for false. The mapping used to be
+    // the other way round, which turned every daytime hour into night.
+    #[test]
+    fn int_flags_map_one_to_true() {
+        let astro: Astro = serde_json::from_str(
+            r#"{"sunrise": "06:57 AM", "sunset": "07:01 PM", "is_moon_up": 0, "is_sun_up": 1}"#,
+        )
+        .unwrap();
+        assert!(astro.is_sun_up);
+        assert!(!astro.is_moon_up);
+    }
+
+    // A clear June day at a south facing wall in the Alps, 07:00 to 19:00 local time
+    fn sunny_wall() -> (Area, AreaForecast) {
+        let area = Area {
+            aspect_deg: Some(180.0),
+            ..serde_json::from_str(
+                r#"{"area_name": "Wall", "metadata": {"lat": 46.5, "lng": 8.0}}"#,
+            )
+            .unwrap()
+        };
+        let date = NaiveDate::from_ymd_opt(2023, 6, 21).unwrap();
+        let hours = (7..=19)
+            .map(|hour| Observation {
+                time: date.and_hms_opt(hour, 0, 0).unwrap(),
+                temp: Temperature::from_celsius(6.0),
+                feels_like: None,
+                humidity: 40.0,
+                wind: Speed::from_kph(5.0),
+                gust: None,
+                wind_degree: None,
+                precip: Length::default(),
+                snow: None,
+                cloud: 0.0,
+                chance_of_rain: None,
+                uv: None,
+                is_day: Some(true),
+                condition: None,
+                us_epa_index: None,
+            })
+            .collect();
+        let forecast = AreaForecast {
+            provider: "test".to_string(),
+            lat: 46.5,
+            lng: 8.0,
+            utc_offset_seconds: 7200,
+            fetched_at: Utc::now(),
+            elevation_m: None,
+            current: None,
+            days: vec![DailyForecast::from_hours(date, hours).unwrap()],
+        };
+        (area, forecast)
+    }
+
+    #[test]
+    fn rescoring_keeps_the_wall_sun() {
+        let (area, forecast) = sunny_wall();
+        let mut stored: Vec<AreaWeather> = ResponseAndArea {
+            response: forecast,
+            area: area.clone(),
+            recent: Vec::new(),
         }
+        .into();
+        let day = &mut stored[0];
+        let first = day.score.clone();
+        let temperature = |score: &DayScore| {
+            score
+                .factors
+                .iter()
+                .find(|factor| factor.factor == scoring::Factor::Temperature)
+                .cloned()
+                .unwrap()
+        };
+        // Cold air, but the wall in sun makes it just right
+        assert!(temperature(&first).reason.contains("wall in sun"));
+
+        // Preferences that change nothing must not change the score
+        day.rescore(&Preferences::default().model_for(&area));
+        assert_eq!(temperature(&day.score).reason, temperature(&first).reason);
+        assert!((temperature(&day.score).score - temperature(&first).score).abs() < 1e-9);
+        assert!((day.score.score - first.score).abs() < 1e-9);
+
+        // Days stored before the site was kept still rescore, without the sun
+        day.site = None;
+        day.rescore(&Preferences::default().model_for(&area));
+        assert!(!temperature(&day.score).reason.contains("wall in sun"));
+    }
+
+    #[test]
+    fn int_flags_reject_other_values() {
+        let astro = serde_json::from_str::<Astro>(
+            r#"{"sunrise": "06:57 AM", "sunset": "07:01 PM", "is_moon_up": 2, "is_sun_up": 1}"#,
+        );
+        assert!(astro.is_err());
     }
 }
@@ -0,0 +1,220 @@
+use crate::{
+    climbing::{Discipline, RockType},
+    scoring::{FactorWeights, ScoringModel},
+    units::{Speed, Temperature, Units},
+    Area,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Wind at three times the tolerance scores zero, as with the default model
+const WIND_LIMIT_FACTOR: f64 = 3.0;
+// Humidity this far above the tolerance scores zero
+const HUMIDITY_LIMIT_MARGIN: f64 = 45.0;
+
+// What a climber finds "just right". Anything left unset keeps the area's defaults.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Preferences {
+    #[serde(
+        rename = "min_temp_c",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub min_temp: Option<Temperature>,
+    #[serde(
+        rename = "max_temp_c",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub max_temp: Option<Temperature>,
+    // Wind above this starts to count against a day
+    #[serde(
+        rename = "max_wind_kph",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::units::kph::option"
+    )]
+    pub max_wind: Option<Speed>,
+    // Humidity above this starts to count against a day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_humidity: Option<f64>,
+    // Only crags with climbs in one of these
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disciplines: Vec<Discipline>,
+    // Only crags of these rock types, crags with unknown rock are kept
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rock_types: Vec<RockType>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub avoid_rock_types: Vec<RockType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<FactorWeights>,
+    // What responses are shown in when a request does not ask. Thresholds above are
+    // always given in metric.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units: Option<Units>,
+}
+
+impl Preferences {
+    pub fn validate(&self) -> Result<(), String> {
+        if let (Some(min), Some(max)) = (self.min_temp, self.max_temp) {
+            if min > max {
+                return Err("min_temp_c must not be above max_temp_c".to_string());
+            }
+        }
+        if self.max_wind.is_some_and(|wind| wind.ms() < 0.0) {
+            return Err("max_wind_kph must not be negative".to_string());
+        }
+        if self
+            .max_humidity
+            .is_some_and(|humidity| !(0.0..=100.0).contains(&humidity))
+        {
+            return Err("max_humidity must be between 0 and 100".to_string());
+        }
+        if let Some(weights) = &self.weights {
+            let all = [
+                weights.temperature,
+                weights.precipitation,
+                weights.humidity,
+                weights.wind,
+                weights.sky,
+                weights.air_quality,
+            ];
+            if all.iter().any(|weight| *weight < 0.0) || all.iter().sum::<f64>() <= 0.0 {
+                return Err("weights must not be negative and must not all be zero".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    // These preferences with any set in other taking their place
+    pub fn merged(&self, other: &Preferences) -> Self {
+        fn list<T: Clone>(ours: &[T], theirs: &[T]) -> Vec<T> {
+            if theirs.is_empty() {
+                ours.to_vec()
+            } else {
+                theirs.to_vec()
+            }
+        }
+        Self {
+            min_temp: other.min_temp.or(self.min_temp),
+            max_temp: other.max_temp.or(self.max_temp),
+            max_wind: other.max_wind.or(self.max_wind),
+            max_humidity: other.max_humidity.or(self.max_humidity),
+            disciplines: list(&self.disciplines, &other.disciplines),
+            rock_types: list(&self.rock_types, &other.rock_types),
+            avoid_rock_types: list(&self.avoid_rock_types, &other.avoid_rock_types),
+            weights: other.weights.clone().or_else(|| self.weights.clone()),
+            units: other.units.or(self.units),
+        }
+    }
+
+    // The area's model with the climber's own thresholds and weights on top
+    pub fn model_for(&self, area: &Area) -> ScoringModel {
+        let mut model = ScoringModel::for_area(area);
+        if let Some(min) = self.min_temp {
+            model.ideal_min_temp_c = min.celsius();
+        }
+        if let Some(max) = self.max_temp {
+            model.ideal_max_temp_c = max.celsius();
+        }
+        if model.ideal_min_temp_c > model.ideal_max_temp_c {
+            // Only one end was set and it passed the area's other end
+            let end = self
+                .min_temp
+                .or(self.max_temp)
+                .unwrap_or_default()
+                .celsius();
+            model.ideal_min_temp_c = end;
+            model.ideal_max_temp_c = end;
+        }
+        if let Some(wind) = self.max_wind {
+            model.ideal_max_wind_kph = wind.kph();
+            model.max_wind_kph = wind.kph() * WIND_LIMIT_FACTOR;
+        }
+        if let Some(humidity) = self.max_humidity {
+            model.ideal_max_humidity = humidity;
+            model.max_humidity = (humidity + HUMIDITY_LIMIT_MARGIN).min(100.0);
+        }
+        if let Some(weights) = &self.weights {
+            model.weights = weights.clone();
+        }
+        model
+    }
+
+    // Whether the area has what the climber is after
+    pub fn allows(&self, area: &Area) -> bool {
+        let Some(climbing) = &area.climbing else {
+            return self.disciplines.is_empty();
+        };
+        let disciplines = self.disciplines.is_empty()
+            || self
+                .disciplines
+                .iter()
+                .any(|discipline| climbing.climbs(*discipline) > 0);
+        let rock = match climbing.rock_type {
+            Some(rock) => {
+                (self.rock_types.is_empty() || self.rock_types.contains(&rock))
+                    && !self.avoid_rock_types.contains(&rock)
+            }
+            None => true,
+        };
+        disciplines && rock
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub preferences: Preferences,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds_keep_their_metric_names() {
+        let preferences: Preferences = serde_json::from_str(
+            r#"{"min_temp_c": 5.0, "max_temp_c": 18.0, "max_wind_kph": 36.0}"#,
+        )
+        .unwrap();
+        assert_eq!(preferences.min_temp, Some(Temperature::from_celsius(5.0)));
+        assert!((preferences.max_wind.unwrap().ms() - 10.0).abs() < 1e-9);
+        assert!(preferences.validate().is_ok());
+
+        let json = serde_json::to_value(&preferences).unwrap();
+        assert_eq!(json["max_temp_c"], 18.0);
+        assert_eq!(json["max_wind_kph"], 36.0);
+    }
+
+    #[test]
+    fn thresholds_replace_the_area_model() {
+        let preferences = Preferences {
+            min_temp: Some(Temperature::from_celsius(2.0)),
+            max_wind: Some(Speed::from_kph(10.0)),
+            ..Preferences::default()
+        };
+        let area: Area = serde_json::from_str(
+            r#"{"area_name": "Crag", "metadata": {"lat": 49.7, "lng": -123.1}}"#,
+        )
+        .unwrap();
+        let model = preferences.model_for(&area);
+        assert_eq!(model.ideal_min_temp_c, 2.0);
+        assert!((model.ideal_max_wind_kph - 10.0).abs() < 1e-9);
+        assert!((model.max_wind_kph - 30.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inverted_temperatures_are_rejected() {
+        let preferences = Preferences {
+            min_temp: Some(Temperature::from_celsius(20.0)),
+            max_temp: Some(Temperature::from_celsius(10.0)),
+            ..Preferences::default()
+        };
+        assert!(preferences.validate().is_err());
+    }
+}
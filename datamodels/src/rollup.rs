@@ -0,0 +1,156 @@
+use crate::{units::Units, AreaWeather};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// How a region looks on one day, summarised over the crags inside it
+#[derive(Serialize, Debug, Clone)]
+pub struct RegionDay {
+    pub date: NaiveDate,
+    // Crags with a stored forecast for the day
+    pub crags: usize,
+    pub avg_score: f64,
+    pub best_score: f64,
+    // Names repeat across regions, so the uuid is what identifies the crag
+    pub best_crag_uuid: Option<String>,
+    pub best_crag_name: String,
+    // In the units the days were rolled up for
+    pub max_temperature: f64,
+    pub min_temperature: f64,
+    pub max_precipitation: f64,
+}
+
+// Roll per crag forecasts up to one summary per day, earliest first
+pub fn roll_up(weather: &[AreaWeather], units: Units) -> Vec<RegionDay> {
+    let mut by_date: BTreeMap<NaiveDate, Vec<&AreaWeather>> = BTreeMap::new();
+    for day in weather {
+        by_date.entry(day.date).or_default().push(day);
+    }
+
+    by_date
+        .into_iter()
+        .filter_map(|(date, days)| {
+            let best = days
+                .iter()
+                .max_by(|a, b| a.score.score.total_cmp(&b.score.score))?;
+            let count = days.len();
+            Some(RegionDay {
+                date,
+                crags: count,
+                avg_score: days.iter().map(|day| day.score.score).sum::<f64>() / count as f64,
+                best_score: best.score.score,
+                best_crag_uuid: best.uuid.clone(),
+                best_crag_name: best.area_name.clone(),
+                max_temperature: days
+                    .iter()
+                    .map(|day| units.temperature(day.forecast.max_temp))
+                    .fold(f64::MIN, f64::max),
+                min_temperature: days
+                    .iter()
+                    .map(|day| units.temperature(day.forecast.min_temp))
+                    .fold(f64::MAX, f64::min),
+                max_precipitation: days
+                    .iter()
+                    .map(|day| units.precipitation(day.forecast.total_precip))
+                    .fold(0.0, f64::max),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scoring::DayScore,
+        units::{Length, Speed, Temperature},
+        DailyForecast,
+    };
+    use chrono::Utc;
+
+    fn crag_day(
+        uuid: &str,
+        date: NaiveDate,
+        score: f64,
+        max_c: f64,
+        precip_mm: f64,
+    ) -> AreaWeather {
+        AreaWeather {
+            uuid: Some(uuid.to_string()),
+            // Crags in different regions often share a name
+            area_name: "The Wall".to_string(),
+            lat: 49.7,
+            lng: -123.1,
+            date,
+            provider: "test".to_string(),
+            fetched_at: Utc::now(),
+            forecast: DailyForecast {
+                date,
+                max_temp: Temperature::from_celsius(max_c),
+                min_temp: Temperature::from_celsius(max_c - 10.0),
+                avg_temp: Temperature::from_celsius(max_c - 5.0),
+                max_wind: Speed::from_kph(10.0),
+                total_precip: Length::from_mm(precip_mm),
+                total_snow: Length::default(),
+                avg_humidity: None,
+                chance_of_rain: None,
+                uv: None,
+                condition: None,
+                us_epa_index: None,
+                sunrise: None,
+                sunset: None,
+                hours: Vec::new(),
+            },
+            score: DayScore {
+                date,
+                score,
+                factors: Vec::new(),
+                best_window_start: None,
+                hours: Vec::new(),
+            },
+            rock: None,
+            site: None,
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 6, day).unwrap()
+    }
+
+    #[test]
+    fn days_are_summarised_over_their_crags() {
+        let weather = vec![
+            crag_day("b", date(2), 40.0, 20.0, 5.0),
+            crag_day("a", date(1), 80.0, 15.0, 0.0),
+            crag_day("b", date(1), 60.0, 25.0, 2.0),
+        ];
+        let days = roll_up(&weather, Units::Metric);
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, date(1));
+        assert_eq!(days[0].crags, 2);
+        assert_eq!(days[0].avg_score, 70.0);
+        assert_eq!(days[0].best_score, 80.0);
+        assert_eq!(days[0].best_crag_uuid.as_deref(), Some("a"));
+        assert_eq!(days[0].max_temperature, 25.0);
+        assert_eq!(days[0].min_temperature, 5.0);
+        assert_eq!(days[0].max_precipitation, 2.0);
+        assert_eq!(days[1].date, date(2));
+        assert_eq!(days[1].best_crag_uuid.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn days_are_rolled_up_in_the_requested_units() {
+        let weather = vec![crag_day("a", date(1), 80.0, 25.0, 25.4)];
+        let days = roll_up(&weather, Units::Imperial);
+
+        assert_eq!(days[0].max_temperature, 77.0);
+        assert_eq!(days[0].min_temperature, 59.0);
+        assert_eq!(days[0].max_precipitation, 1.0);
+    }
+
+    #[test]
+    fn no_weather_rolls_up_to_no_days() {
+        assert!(roll_up(&[], Units::Metric).is_empty());
+    }
+}
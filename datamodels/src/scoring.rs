@@ -0,0 +1,780 @@
+use crate::{
+    climbing::Discipline,
+    drying::RockCondition,
+    forecast::{AreaForecast, DailyForecast, Observation},
+    solar::{self, Exposure, WallSun},
+    units::{Length, Reading, Speed, Temperature, Units},
+    Area,
+};
+use chrono::{NaiveDate, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+// Factor scores are floored before combining so one terrible factor drags the
+// total towards zero without making the logarithm blow up
+const MIN_FACTOR_SCORE: f64 = 0.01;
+// Rock in full sun feels this much warmer than the air, shaded rock a little colder
+const SUN_WARMING_C: f64 = 10.0;
+const SHADE_COOLING_C: f64 = 2.0;
+// Wet rock is a property of the crag rather than a preference, so profiles can't
+// weight it away. Matches the default precipitation weight.
+const WETNESS_WEIGHT: f64 = 0.3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Factor {
+    Temperature,
+    Precipitation,
+    Humidity,
+    Wind,
+    Sky,
+    AirQuality,
+    Wetness,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FactorScore {
+    pub factor: Factor,
+    // 0.0 (unclimbable) to 1.0 (just right)
+    pub score: f64,
+    pub weight: f64,
+    pub reason: String,
+    // The value the reason quotes, missing on scores stored before it was kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading: Option<Reading>,
+}
+
+impl FactorScore {
+    // Reasons are written in metric, this swaps the quoted value into the client's units
+    pub fn in_units(&self, units: Units) -> Self {
+        let reason = match self.reading {
+            Some(reading) => self.reason.replacen(
+                &reading.describe(Units::Metric),
+                &reading.describe(units),
+                1,
+            ),
+            None => self.reason.clone(),
+        };
+        Self {
+            reason,
+            reading: None,
+            ..self.clone()
+        }
+    }
+
+    // How much this factor drags down the combined score, used to rank explanations
+    pub fn penalty(&self) -> f64 {
+        -self.weight * self.score.max(MIN_FACTOR_SCORE).ln()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HourScore {
+    pub time: NaiveDateTime,
+    // 0 to 100
+    pub score: f64,
+    pub factors: Vec<FactorScore>,
+    // Sun on the wall, known when the area has an aspect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exposure: Option<Exposure>,
+}
+
+// Where a forecast is for and its offset from UTC, needed to place the sun
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Site {
+    pub lat: f64,
+    pub lng: f64,
+    pub utc_offset_seconds: i32,
+}
+
+impl Site {
+    pub fn of(forecast: &AreaForecast) -> Self {
+        Self {
+            lat: forecast.lat,
+            lng: forecast.lng,
+            utc_offset_seconds: forecast.utc_offset_seconds,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DayScore {
+    pub date: NaiveDate,
+    // 0 to 100
+    pub score: f64,
+    pub factors: Vec<FactorScore>,
+    // Start of the best climbing window when hourly data was available
+    pub best_window_start: Option<NaiveDateTime>,
+    pub hours: Vec<HourScore>,
+}
+
+impl DayScore {
+    // Factors that lowered the score, worst first
+    pub fn limiting_factors(&self) -> Vec<&FactorScore> {
+        limiting_factors(&self.factors)
+    }
+}
+
+impl HourScore {
+    pub fn limiting_factors(&self) -> Vec<&FactorScore> {
+        limiting_factors(&self.factors)
+    }
+}
+
+fn limiting_factors(factors: &[FactorScore]) -> Vec<&FactorScore> {
+    let mut limiting: Vec<&FactorScore> = factors.iter().filter(|f| f.score < 0.95).collect();
+    limiting.sort_by(|a, b| b.penalty().total_cmp(&a.penalty()));
+    limiting
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoredDay {
+    pub forecast: DailyForecast,
+    pub score: DayScore,
+    pub rock: Option<RockCondition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FactorWeights {
+    pub temperature: f64,
+    pub precipitation: f64,
+    pub humidity: f64,
+    pub wind: f64,
+    pub sky: f64,
+    pub air_quality: f64,
+}
+
+impl Default for FactorWeights {
+    fn default() -> Self {
+        Self {
+            temperature: 0.25,
+            precipitation: 0.3,
+            humidity: 0.15,
+            wind: 0.15,
+            sky: 0.05,
+            air_quality: 0.1,
+        }
+    }
+}
+
+// Thresholds that define "just right". Everything between the ideal and the
+// limit values falls off linearly.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScoringModel {
+    pub ideal_min_temp_c: f64,
+    pub ideal_max_temp_c: f64,
+    // Degrees outside the ideal band at which temperature scores zero
+    pub temp_tolerance_c: f64,
+    pub ideal_max_wind_kph: f64,
+    pub max_wind_kph: f64,
+    pub ideal_max_humidity: f64,
+    pub max_humidity: f64,
+    // Hourly precipitation that makes climbing impossible
+    pub max_hourly_precip_mm: f64,
+    // Daily total that makes climbing impossible
+    pub max_daily_precip_mm: f64,
+    // Length of the climbing window used to score a day
+    pub window_hours: usize,
+    pub weights: FactorWeights,
+    // Compass bearing the wall faces, set per area by for_area
+    #[serde(default)]
+    pub aspect_deg: Option<f64>,
+}
+
+impl Default for ScoringModel {
+    fn default() -> Self {
+        Self {
+            ideal_min_temp_c: 8.0,
+            ideal_max_temp_c: 20.0,
+            temp_tolerance_c: 12.0,
+            ideal_max_wind_kph: 15.0,
+            max_wind_kph: 45.0,
+            ideal_max_humidity: 50.0,
+            max_humidity: 95.0,
+            max_hourly_precip_mm: 2.0,
+            max_daily_precip_mm: 10.0,
+            window_hours: 4,
+            weights: FactorWeights::default(),
+            aspect_deg: None,
+        }
+    }
+}
+
+// 1.0 at or below `good`, 0.0 at or above `bad`
+fn ramp(value: f64, good: f64, bad: f64) -> f64 {
+    if bad <= good {
+        return if value <= good { 1.0 } else { 0.0 };
+    }
+    (1.0 - (value - good) / (bad - good)).clamp(0.0, 1.0)
+}
+
+// Weighted geometric mean scaled to 0-100
+pub fn combine(factors: &[FactorScore]) -> f64 {
+    let total_weight: f64 = factors.iter().map(|f| f.weight).sum();
+    if total_weight <= 0.0 {
+        return 0.0;
+    }
+    let log_sum: f64 = factors
+        .iter()
+        .map(|f| f.weight * f.score.max(MIN_FACTOR_SCORE).ln())
+        .sum();
+    100.0 * (log_sum / total_weight).exp()
+}
+
+impl ScoringModel {
+    // Tunes the default model to what is climbed at the area
+    pub fn for_area(area: &Area) -> Self {
+        let mut model = Self {
+            aspect_deg: area.aspect_deg,
+            ..Self::default()
+        };
+        let Some(climbing) = &area.climbing else {
+            return model;
+        };
+        // Soft rock must not be climbed wet, so any real rain rules the day out
+        if climbing.rock_type.is_some_and(|rock| rock.is_soft()) {
+            model.max_hourly_precip_mm = 0.5;
+            model.max_daily_precip_mm = 2.0;
+            model.weights.precipitation *= 1.5;
+        }
+        // Boulderers chase friction, which is best in cool, dry air
+        if climbing.share(Discipline::Boulder) > 0.5 {
+            model.ideal_min_temp_c -= 4.0;
+            model.ideal_max_temp_c -= 4.0;
+            model.weights.humidity *= 1.5;
+        }
+        // Long alpine and trad days are spent exposed on the wall
+        if climbing.share(Discipline::Alpine) + climbing.share(Discipline::Trad) > 0.5 {
+            model.ideal_max_wind_kph -= 5.0;
+        }
+        model
+    }
+
+    pub fn temperature(&self, temp_c: f64) -> FactorScore {
+        let reading = Reading::Temperature(Temperature::from_celsius(temp_c));
+        let temp = reading.describe(Units::Metric);
+        let (score, reason) = if temp_c < self.ideal_min_temp_c {
+            (
+                ramp(self.ideal_min_temp_c - temp_c, 0.0, self.temp_tolerance_c),
+                format!("{} is colder than the ideal band", temp),
+            )
+        } else if temp_c > self.ideal_max_temp_c {
+            (
+                ramp(temp_c - self.ideal_max_temp_c, 0.0, self.temp_tolerance_c),
+                format!("{} is warmer than the ideal band", temp),
+            )
+        } else {
+            (1.0, format!("{} is just right", temp))
+        };
+        FactorScore {
+            factor: Factor::Temperature,
+            score,
+            weight: self.weights.temperature,
+            reason,
+            reading: Some(reading),
+        }
+    }
+
+    pub fn precipitation(
+        &self,
+        precip_mm: f64,
+        chance_of_rain: Option<f64>,
+        max_mm: f64,
+    ) -> FactorScore {
+        let amount = ramp(precip_mm, 0.0, max_mm);
+        // A high chance of rain is a risk even when the expected amount is small
+        let chance = chance_of_rain.map(|c| ramp(c, 20.0, 100.0)).unwrap_or(1.0);
+        let score = amount.min(0.5 + 0.5 * chance);
+        let reading = (precip_mm > 0.0).then(|| Reading::Precipitation(Length::from_mm(precip_mm)));
+        let reason = match (reading, chance_of_rain) {
+            (Some(reading), _) => format!(
+                "{} of precipitation expected",
+                reading.describe(Units::Metric)
+            ),
+            (None, Some(c)) if c > 20.0 => format!("{:.0}% chance of rain", c),
+            _ => "dry".to_string(),
+        };
+        FactorScore {
+            factor: Factor::Precipitation,
+            score,
+            weight: self.weights.precipitation,
+            reason,
+            reading,
+        }
+    }
+
+    pub fn humidity(&self, humidity: f64) -> FactorScore {
+        let score = ramp(humidity, self.ideal_max_humidity, self.max_humidity);
+        let reason = if score < 1.0 {
+            format!("{:.0}% humidity will make holds greasy", humidity)
+        } else {
+            format!("{:.0}% humidity", humidity)
+        };
+        FactorScore {
+            factor: Factor::Humidity,
+            score,
+            weight: self.weights.humidity,
+            reason,
+            reading: None,
+        }
+    }
+
+    pub fn wind(&self, wind_kph: f64, gust_kph: Option<f64>) -> FactorScore {
+        // Gusts matter more than the sustained speed on exposed walls
+        let effective = gust_kph.map_or(wind_kph, |gust| wind_kph.max(gust * 0.75));
+        let score = ramp(effective, self.ideal_max_wind_kph, self.max_wind_kph);
+        let reading = (score < 1.0).then(|| Reading::Speed(Speed::from_kph(effective)));
+        let reason = match reading {
+            Some(reading) => format!("wind up to {}", reading.describe(Units::Metric)),
+            None => "light wind".to_string(),
+        };
+        FactorScore {
+            factor: Factor::Wind,
+            score,
+            weight: self.weights.wind,
+            reason,
+            reading,
+        }
+    }
+
+    pub fn sky(&self, cloud: f64, temp_c: f64) -> FactorScore {
+        // Sun is welcome when it is cold, cloud is welcome when it is hot
+        let (score, reason) = if temp_c < self.ideal_min_temp_c {
+            (
+                1.0 - 0.5 * cloud / 100.0,
+                format!("{:.0}% cloud cover on a cold day", cloud),
+            )
+        } else if temp_c > self.ideal_max_temp_c {
+            (
+                0.5 + 0.5 * cloud / 100.0,
+                format!("{:.0}% cloud cover on a hot day", cloud),
+            )
+        } else {
+            (
+                1.0 - 0.2 * cloud / 100.0,
+                format!("{:.0}% cloud cover", cloud),
+            )
+        };
+        FactorScore {
+            factor: Factor::Sky,
+            score: score.clamp(0.0, 1.0),
+            weight: self.weights.sky,
+            reason,
+            reading: None,
+        }
+    }
+
+    pub fn air_quality(&self, us_epa_index: Option<u8>) -> FactorScore {
+        let (score, reason) = match us_epa_index {
+            None => (1.0, "no air quality data".to_string()),
+            Some(0..=2) => (1.0, "good air quality".to_string()),
+            Some(3) => (0.7, "air unhealthy for sensitive groups".to_string()),
+            Some(4) => (0.4, "unhealthy air quality".to_string()),
+            Some(5) => (0.15, "very unhealthy air quality".to_string()),
+            Some(_) => (0.0, "hazardous air quality".to_string()),
+        };
+        FactorScore {
+            factor: Factor::AirQuality,
+            score,
+            weight: self.weights.air_quality,
+            reason,
+            reading: None,
+        }
+    }
+
+    pub fn score_hour(&self, hour: &Observation) -> HourScore {
+        self.score_hour_in(hour, None)
+    }
+
+    // Score an hour with the sun on or off the wall changing how warm it feels
+    pub fn score_hour_in(&self, hour: &Observation, sun: Option<WallSun>) -> HourScore {
+        let air_temp = hour.feels_like.unwrap_or(hour.temp).celsius();
+        let sunshine = 1.0 - hour.cloud / 100.0;
+        let (temp_c, on_wall) = match sun {
+            Some(WallSun {
+                exposure: Exposure::Sun,
+                incidence,
+            }) => (
+                air_temp + SUN_WARMING_C * incidence * sunshine,
+                " with the wall in sun",
+            ),
+            Some(WallSun {
+                exposure: Exposure::Shade,
+                ..
+            }) => (
+                air_temp - SHADE_COOLING_C * sunshine,
+                " with the wall in shade",
+            ),
+            _ => (air_temp, ""),
+        };
+        let mut temperature = self.temperature(temp_c);
+        temperature.reason.push_str(on_wall);
+
+        let factors = vec![
+            temperature,
+            self.precipitation(
+                hour.precip.mm(),
+                hour.chance_of_rain,
+                self.max_hourly_precip_mm,
+            ),
+            self.humidity(hour.humidity),
+            self.wind(hour.wind.kph(), hour.gust.map(Speed::kph)),
+            self.sky(hour.cloud, temp_c),
+            self.air_quality(hour.us_epa_index),
+        ];
+        HourScore {
+            time: hour.time,
+            score: combine(&factors),
+            factors,
+            exposure: sun.map(|sun| sun.exposure),
+        }
+    }
+
+    fn is_daylight(day: &DailyForecast, hour: &Observation) -> bool {
+        if let Some(is_day) = hour.is_day {
+            return is_day;
+        }
+        match (day.sunrise, day.sunset) {
+            (Some(sunrise), Some(sunset)) => {
+                hour.time.time() >= sunrise && hour.time.time() <= sunset
+            }
+            _ => (7..=19).contains(&hour.time.hour()),
+        }
+    }
+
+    // Score from the daily aggregates only, for providers without hourly data
+    fn score_day_aggregate(&self, day: &DailyForecast) -> Vec<FactorScore> {
+        vec![
+            self.temperature(day.max_temp.celsius()),
+            self.precipitation(
+                day.total_precip.mm(),
+                day.chance_of_rain,
+                self.max_daily_precip_mm,
+            ),
+            self.humidity(day.avg_humidity.unwrap_or(self.ideal_max_humidity)),
+            self.wind(day.max_wind.kph(), None),
+            self.air_quality(day.us_epa_index),
+        ]
+    }
+
+    pub fn score_day(&self, day: &DailyForecast) -> DayScore {
+        self.score_day_at(day, None)
+    }
+
+    // Wall sun is only scored when the site is known
+    pub fn score_day_at(&self, day: &DailyForecast, site: Option<Site>) -> DayScore {
+        let hours: Vec<HourScore> = day
+            .hours
+            .iter()
+            .filter(|hour| Self::is_daylight(day, hour))
+            .map(|hour| {
+                let sun = self.aspect_deg.zip(site).map(|(aspect, site)| {
+                    solar::wall_sun(
+                        aspect,
+                        solar::sun_position_local(
+                            site.lat,
+                            site.lng,
+                            hour.time,
+                            site.utc_offset_seconds,
+                        ),
+                    )
+                });
+                self.score_hour_in(hour, sun)
+            })
+            .collect();
+
+        let window = self.window_hours.max(1).min(hours.len());
+        let best_window = (window > 0)
+            .then(|| {
+                hours
+                    .windows(window)
+                    .max_by(|a, b| window_score(a).total_cmp(&window_score(b)))
+            })
+            .flatten();
+
+        let mut factors = match best_window {
+            Some(best) => average_factors(best),
+            None => self.score_day_aggregate(day),
+        };
+
+        // Rain outside the climbing window still leaves the rock wet
+        let daily_precip = self.precipitation(
+            day.total_precip.mm(),
+            day.chance_of_rain,
+            self.max_daily_precip_mm,
+        );
+        if let Some(precip) = factors
+            .iter_mut()
+            .find(|f| f.factor == Factor::Precipitation)
+        {
+            if daily_precip.score < precip.score {
+                *precip = daily_precip;
+            }
+        }
+
+        DayScore {
+            date: day.date,
+            score: combine(&factors),
+            factors,
+            best_window_start: best_window.and_then(|best| best.first()).map(|h| h.time),
+            hours,
+        }
+    }
+
+    // Wet rock rules out the climbing window until it dries, and soft rock the whole day
+    pub fn apply_rock_condition(&self, day: &mut DayScore, rock: &RockCondition) {
+        let score = match (rock.do_not_climb, rock.wet_until, day.best_window_start) {
+            (true, _, _) => 0.0,
+            (false, None, _) => return,
+            (false, Some(wet_until), Some(start)) => {
+                let window: Vec<&HourScore> = day
+                    .hours
+                    .iter()
+                    .skip_while(|hour| hour.time < start)
+                    .take(self.window_hours.max(1))
+                    .collect();
+                let dry = window.iter().filter(|hour| hour.time >= wet_until).count();
+                dry as f64 / window.len().max(1) as f64
+            }
+            (false, Some(_), None) => 0.5,
+        };
+        day.factors.push(FactorScore {
+            factor: Factor::Wetness,
+            score,
+            weight: WETNESS_WEIGHT,
+            reason: rock.reason.clone(),
+            reading: rock.reading,
+        });
+        day.score = combine(&day.factors);
+        // Climbing soaked soft rock breaks holds, however good the weather is
+        if rock.do_not_climb {
+            day.score = 0.0;
+        }
+    }
+
+    pub fn score_forecast(&self, forecast: &AreaForecast) -> Vec<ScoredDay> {
+        forecast
+            .days
+            .iter()
+            .map(|day| ScoredDay {
+                forecast: day.clone(),
+                score: self.score_day_at(day, Some(Site::of(forecast))),
+                rock: None,
+            })
+            .collect()
+    }
+}
+
+fn window_score(hours: &[HourScore]) -> f64 {
+    hours.iter().map(|h| h.score).sum::<f64>() / hours.len() as f64
+}
+
+// Average each factor over a window, keeping the reason from the worst hour
+fn average_factors(hours: &[HourScore]) -> Vec<FactorScore> {
+    let first = match hours.first() {
+        Some(first) => first,
+        None => return Vec::new(),
+    };
+    first
+        .factors
+        .iter()
+        .map(|template| {
+            let matching: Vec<&FactorScore> = hours
+                .iter()
+                .flat_map(|h| h.factors.iter())
+                .filter(|f| f.factor == template.factor)
+                .collect();
+            let score = matching.iter().map(|f| f.score).sum::<f64>() / matching.len() as f64;
+            let worst = matching
+                .iter()
+                .min_by(|a, b| a.score.total_cmp(&b.score))
+                .unwrap_or(&template);
+            FactorScore {
+                factor: template.factor,
+                score,
+                weight: template.weight,
+                reason: worst.reason.clone(),
+                reading: worst.reading,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Length, Temperature};
+
+    fn factor(score: f64, weight: f64) -> FactorScore {
+        FactorScore {
+            factor: Factor::Temperature,
+            score,
+            weight,
+            reason: String::new(),
+            reading: None,
+        }
+    }
+
+    fn hour(date: NaiveDate, hour: u32, precip_mm: f64) -> Observation {
+        Observation {
+            time: date.and_hms_opt(hour, 0, 0).unwrap(),
+            temp: Temperature::from_celsius(15.0),
+            feels_like: None,
+            humidity: 40.0,
+            wind: Speed::from_kph(5.0),
+            gust: None,
+            wind_degree: None,
+            precip: Length::from_mm(precip_mm),
+            snow: None,
+            cloud: 0.0,
+            chance_of_rain: None,
+            uv: None,
+            is_day: Some((7..=19).contains(&hour)),
+            condition: None,
+            us_epa_index: None,
+        }
+    }
+
+    #[test]
+    fn combine_is_a_weighted_geometric_mean() {
+        assert!((combine(&[factor(1.0, 0.5), factor(1.0, 0.5)]) - 100.0).abs() < 1e-9);
+        // sqrt(1.0 * 0.25)
+        assert!((combine(&[factor(1.0, 1.0), factor(0.25, 1.0)]) - 50.0).abs() < 1e-9);
+        // 0.25^0.75 with the heavier weight on the bad factor
+        let expected = 100.0 * 0.25f64.powf(0.75);
+        assert!((combine(&[factor(1.0, 1.0), factor(0.25, 3.0)]) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn soaked_soft_rock_is_ruled_out() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 10).unwrap();
+        let hours: Vec<Observation> = (0..24).map(|h| hour(date, h, 0.0)).collect();
+        let day = DailyForecast::from_hours(date, hours).unwrap();
+        let model = ScoringModel::default();
+        let mut score = model.score_day(&day);
+        assert!(score.score > 50.0);
+
+        model.apply_rock_condition(
+            &mut score,
+            &RockCondition {
+                date,
+                wet_until: None,
+                do_not_climb: true,
+                reason: "sandstone is still soaked".to_string(),
+                reading: None,
+            },
+        );
+        assert_eq!(score.score, 0.0);
+        assert!(score
+            .factors
+            .iter()
+            .any(|f| f.factor == Factor::Wetness && f.reason == "sandstone is still soaked"));
+    }
+
+    #[test]
+    fn wet_rock_counts_when_precipitation_is_not_weighted() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 10).unwrap();
+        let hours: Vec<Observation> = (0..24).map(|h| hour(date, h, 0.0)).collect();
+        let day = DailyForecast::from_hours(date, hours).unwrap();
+        let model = ScoringModel {
+            weights: FactorWeights {
+                precipitation: 0.0,
+                ..FactorWeights::default()
+            },
+            ..ScoringModel::default()
+        };
+        let mut score = model.score_day(&day);
+        let dry = score.score;
+
+        model.apply_rock_condition(
+            &mut score,
+            &RockCondition {
+                date,
+                wet_until: date.and_hms_opt(23, 0, 0),
+                do_not_climb: false,
+                reason: "wet until 23:00".to_string(),
+                reading: None,
+            },
+        );
+        assert!(score.score < dry / 2.0);
+    }
+
+    #[test]
+    fn reasons_are_restated_in_the_clients_units() {
+        let wind = ScoringModel::default().wind(40.0, None);
+        assert_eq!(wind.reason, "wind up to 40km/h");
+        assert_eq!(wind.in_units(Units::Imperial).reason, "wind up to 25mph");
+        // Scores stored before readings were kept are left as they are
+        let legacy = FactorScore {
+            reading: None,
+            ..wind.clone()
+        };
+        assert_eq!(legacy.in_units(Units::Imperial).reason, wind.reason);
+    }
+
+    #[test]
+    fn combine_floors_zero_scores() {
+        assert!((combine(&[factor(0.0, 1.0)]) - 100.0 * MIN_FACTOR_SCORE).abs() < 1e-9);
+        assert_eq!(combine(&[factor(1.0, 0.0)]), 0.0);
+        assert_eq!(combine(&[]), 0.0);
+    }
+
+    #[test]
+    fn ramp_falls_off_linearly() {
+        assert_eq!(ramp(5.0, 10.0, 20.0), 1.0);
+        assert_eq!(ramp(15.0, 10.0, 20.0), 0.5);
+        assert_eq!(ramp(25.0, 10.0, 20.0), 0.0);
+        // A limit at or below the ideal is a hard cut
+        assert_eq!(ramp(10.0, 10.0, 10.0), 1.0);
+        assert_eq!(ramp(10.1, 10.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn temperature_outside_the_band_falls_to_zero_at_the_tolerance() {
+        let model = ScoringModel::default();
+        assert_eq!(model.temperature(14.0).score, 1.0);
+        assert_eq!(model.temperature(2.0).score, 0.5);
+        assert_eq!(model.temperature(-4.0).score, 0.0);
+        assert_eq!(model.temperature(26.0).score, 0.5);
+    }
+
+    #[test]
+    fn wind_scores_gusts_at_three_quarters() {
+        let model = ScoringModel::default();
+        assert_eq!(model.wind(10.0, None).score, 1.0);
+        // A 40km/h gust counts as 30km/h, halfway from 15 to 45
+        assert_eq!(model.wind(10.0, Some(40.0)).score, 0.5);
+    }
+
+    #[test]
+    fn best_window_is_the_dry_afternoon() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 10).unwrap();
+        let hours: Vec<Observation> = (0..24)
+            .map(|h| hour(date, h, if (13..=16).contains(&h) { 0.0 } else { 3.0 }))
+            .collect();
+        let day = DailyForecast::from_hours(date, hours).unwrap();
+        let score = ScoringModel::default().score_day(&day);
+
+        assert_eq!(score.best_window_start, date.and_hms_opt(13, 0, 0));
+        // Only daylight hours are scored
+        assert_eq!(score.hours.len(), 13);
+        let precip = score
+            .factors
+            .iter()
+            .find(|f| f.factor == Factor::Precipitation)
+            .unwrap();
+        // The day's total rain still leaves the rock wet
+        assert_eq!(precip.score, 0.0);
+    }
+
+    #[test]
+    fn days_without_hours_use_the_aggregates() {
+        let date = NaiveDate::from_ymd_opt(2023, 6, 10).unwrap();
+        let mut day = DailyForecast::from_hours(date, vec![hour(date, 12, 0.0)]).unwrap();
+        day.hours.clear();
+        let score = ScoringModel::default().score_day(&day);
+
+        assert_eq!(score.best_window_start, None);
+        assert!(score.hours.is_empty());
+        assert!(score.score > 95.0);
+    }
+}
@@ -0,0 +1,240 @@
+use chrono::{Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+// Position of the sun as seen from a point on the ground
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunPosition {
+    // Degrees above the horizon, negative at night
+    pub elevation_deg: f64,
+    // Compass bearing, 0 is north and 90 is east
+    pub azimuth_deg: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Exposure {
+    Sun,
+    Shade,
+    Night,
+}
+
+// Whether the sun reaches a wall and how squarely it hits it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WallSun {
+    pub exposure: Exposure,
+    // Cosine of the angle between the sun and the wall face, 0.0 unless in sun
+    pub incidence: f64,
+}
+
+// NOAA's solar position equations, good to well under a degree for dates near the present
+pub fn sun_position(lat: f64, lng: f64, utc: NaiveDateTime) -> SunPosition {
+    let julian_day = utc.and_utc().timestamp() as f64 / 86_400.0 + 2_440_587.5;
+    let t = (julian_day - 2_451_545.0) / 36_525.0;
+
+    let mean_long = (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35999.05029 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_long = (mean_long + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+
+    let mean_obliquity =
+        23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_long.sin()).asin();
+
+    // Equation of time in minutes
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_long.to_radians();
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    let minutes = utc.hour() as f64 * 60.0 + utc.minute() as f64 + utc.second() as f64 / 60.0;
+    let true_solar_minutes = (minutes + equation_of_time + 4.0 * lng).rem_euclid(1440.0);
+    let hour_angle = (true_solar_minutes / 4.0 - 180.0).to_radians();
+
+    let lat = lat.to_radians();
+    let zenith = (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos())
+        .clamp(-1.0, 1.0)
+        .acos();
+    // Measured from south towards west, then turned into a bearing from north
+    let azimuth = hour_angle
+        .sin()
+        .atan2(hour_angle.cos() * lat.sin() - declination.tan() * lat.cos());
+
+    SunPosition {
+        elevation_deg: 90.0 - zenith.to_degrees(),
+        azimuth_deg: (azimuth.to_degrees() + 180.0).rem_euclid(360.0),
+    }
+}
+
+// Same as sun_position for a local wall clock time at the given UTC offset
+pub fn sun_position_local(
+    lat: f64,
+    lng: f64,
+    local: NaiveDateTime,
+    utc_offset_seconds: i32,
+) -> SunPosition {
+    sun_position(
+        lat,
+        lng,
+        local - Duration::seconds(utc_offset_seconds as i64),
+    )
+}
+
+// A vertical wall facing aspect_deg is in sun while the sun is up and in front of it
+pub fn wall_sun(aspect_deg: f64, sun: SunPosition) -> WallSun {
+    if sun.elevation_deg <= 0.0 {
+        return WallSun {
+            exposure: Exposure::Night,
+            incidence: 0.0,
+        };
+    }
+    let off_axis =
+        ((sun.azimuth_deg - aspect_deg).rem_euclid(360.0) + 180.0).rem_euclid(360.0) - 180.0;
+    if off_axis.abs() < 90.0 {
+        WallSun {
+            exposure: Exposure::Sun,
+            incidence: off_axis.to_radians().cos() * sun.elevation_deg.to_radians().cos(),
+        }
+    } else {
+        WallSun {
+            exposure: Exposure::Shade,
+            incidence: 0.0,
+        }
+    }
+}
+
+const COMPASS_POINTS: [&str; 16] = [
+    "n", "nne", "ne", "ene", "e", "ese", "se", "sse", "s", "ssw", "sw", "wsw", "w", "wnw", "nw",
+    "nnw",
+];
+
+// Bearing of a compass point like "SW" or "south-west"
+pub fn bearing_from_compass(text: &str) -> Option<f64> {
+    let text = text.trim().to_lowercase();
+    let abbreviated: String = text
+        .split(|c: char| c == '-' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| match word {
+            "north" => "n",
+            "south" => "s",
+            "east" => "e",
+            "west" => "w",
+            "northeast" => "ne",
+            "northwest" => "nw",
+            "southeast" => "se",
+            "southwest" => "sw",
+            other => other,
+        })
+        .collect();
+    COMPASS_POINTS
+        .iter()
+        .position(|point| *point == abbreviated)
+        .map(|i| i as f64 * 22.5)
+}
+
+// Finds phrases like "south-facing", "faces west" or "north east facing" in free text
+pub fn aspect_from_description(description: &str) -> Option<f64> {
+    let text = description.to_lowercase().replace('-', " ");
+    let words: Vec<&str> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    // Two word directions first so "north east facing" is not read as east
+    words.iter().enumerate().find_map(|(i, word)| match *word {
+        "facing" => (1..=2)
+            .rev()
+            .filter_map(|n| Some(words.get(i.checked_sub(n)?..i)?.join(" ")))
+            .find_map(|before| bearing_from_compass(&before)),
+        "faces" => (1..=2)
+            .rev()
+            .filter_map(|n| Some(words.get(i + 1..i + 1 + n)?.join(" ")))
+            .find_map(|after| bearing_from_compass(&after)),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const GREENWICH: (f64, f64) = (51.4769, 0.0);
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 0.5,
+            "{} is not within half a degree of {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn noon_elevation_follows_the_declination() {
+        // 90 - latitude + 23.44 at the June solstice, - 23.44 in December
+        let june = sun_position(GREENWICH.0, GREENWICH.1, utc(6, 21, 12, 0));
+        close(june.elevation_deg, 61.96);
+        close(june.azimuth_deg, 179.2);
+        let december = sun_position(GREENWICH.0, GREENWICH.1, utc(12, 21, 12, 0));
+        close(december.elevation_deg, 15.08);
+        close(december.azimuth_deg, 180.5);
+    }
+
+    #[test]
+    fn solstice_sunrise_is_north_of_east() {
+        // Sunrise in London on the solstice is at 04:43 BST, bearing 49 degrees
+        let sunrise = sun_position(GREENWICH.0, GREENWICH.1, utc(6, 21, 3, 43));
+        close(sunrise.elevation_deg, -0.8);
+        close(sunrise.azimuth_deg, 49.0);
+    }
+
+    #[test]
+    fn southern_hemisphere_noon_sun_is_north() {
+        // 12:00 in Sydney is 02:00 UTC in June
+        let sun = sun_position_local(-33.8688, 151.2093, utc(6, 21, 12, 0), 10 * 3600);
+        close(sun.elevation_deg, 32.69);
+        assert!(sun.azimuth_deg > 358.0 || sun.azimuth_deg < 2.0);
+    }
+
+    #[test]
+    fn walls_facing_the_sun_are_in_sun() {
+        let noon = sun_position(GREENWICH.0, GREENWICH.1, utc(6, 21, 12, 0));
+        let south = wall_sun(180.0, noon);
+        assert_eq!(south.exposure, Exposure::Sun);
+        assert!((south.incidence - noon.elevation_deg.to_radians().cos()).abs() < 0.01);
+        assert_eq!(wall_sun(0.0, noon).exposure, Exposure::Shade);
+
+        let night = sun_position(GREENWICH.0, GREENWICH.1, utc(6, 21, 0, 0));
+        assert_eq!(wall_sun(180.0, night).exposure, Exposure::Night);
+    }
+
+    #[test]
+    fn reads_compass_points_and_descriptions() {
+        assert_eq!(bearing_from_compass("SW"), Some(225.0));
+        assert_eq!(bearing_from_compass("south-west"), Some(225.0));
+        assert_eq!(bearing_from_compass("nnw"), Some(337.5));
+        assert_eq!(bearing_from_compass("up"), None);
+        assert_eq!(
+            aspect_from_description("A north east facing slab above the river"),
+            Some(45.0)
+        );
+        assert_eq!(aspect_from_description("The crag faces west."), Some(270.0));
+        assert_eq!(aspect_from_description("Steep and sunny"), None);
+    }
+}
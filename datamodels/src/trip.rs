@@ -0,0 +1,248 @@
+use crate::geo::haversine_km;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreSource {
+    Forecast,
+    // Past the forecast window, the month's typical score
+    Climatology,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CragDay {
+    pub score: f64,
+    pub source: ScoreSource,
+}
+
+// A crag the planner may visit and how good each trip day looks there
+#[derive(Debug, Clone)]
+pub struct TripCrag {
+    pub lat: f64,
+    pub lng: f64,
+    // One entry per trip day, None when nothing is known for the day
+    pub days: Vec<Option<CragDay>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TripOptions {
+    pub max_daily_km: f64,
+    // Days where no reachable crag scores at least this are rest days
+    pub rest_below: f64,
+    // Score given up per km driven, so equal plans prefer less driving
+    pub km_cost: f64,
+}
+
+impl Default for TripOptions {
+    fn default() -> Self {
+        Self {
+            max_daily_km: 200.0,
+            rest_below: 40.0,
+            km_cost: 0.02,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TripStop {
+    Climb {
+        crag: usize,
+        drive_km: f64,
+        day: CragDay,
+    },
+    // Resting at a crag, or at the start when crag is None. Rest days can still be
+    // used to drive somewhere better.
+    Rest {
+        crag: Option<usize>,
+        drive_km: f64,
+    },
+}
+
+// Picks where to be each day so the summed score is as high as possible without
+// driving more than max_daily_km on any day. Locations are the start and every crag,
+// and each day's best total at every location only depends on the day before, so
+// this is a plain dynamic program over days and locations.
+pub fn plan_trip(
+    start_lat: f64,
+    start_lng: f64,
+    crags: &[TripCrag],
+    trip_days: usize,
+    options: &TripOptions,
+) -> Vec<TripStop> {
+    // Location 0 is the start, crag i is location i + 1
+    let points: Vec<(f64, f64)> = std::iter::once((start_lat, start_lng))
+        .chain(crags.iter().map(|crag| (crag.lat, crag.lng)))
+        .collect();
+    let distance: Vec<Vec<f64>> = points
+        .iter()
+        .map(|a| {
+            points
+                .iter()
+                .map(|b| haversine_km(a.0, a.1, b.0, b.1))
+                .collect()
+        })
+        .collect();
+    let climbable = |day: usize, loc: usize| -> Option<CragDay> {
+        let crag_day = crags.get(loc.checked_sub(1)?)?.days.get(day).copied()??;
+        (crag_day.score >= options.rest_below).then_some(crag_day)
+    };
+
+    // best[loc] is the best total ending the previous day at loc, None if unreachable
+    let mut best: Vec<Option<f64>> = vec![None; points.len()];
+    best[0] = Some(0.0);
+    // came_from[day][loc] is the previous location and whether the day was climbed
+    let mut came_from: Vec<Vec<Option<(usize, bool)>>> = Vec::with_capacity(trip_days);
+    for day in 0..trip_days {
+        let mut next: Vec<Option<f64>> = vec![None; points.len()];
+        let mut from: Vec<Option<(usize, bool)>> = vec![None; points.len()];
+        for to in 0..points.len() {
+            let gain = climbable(day, to).map(|crag_day| crag_day.score);
+            for (prev, total) in best.iter().enumerate() {
+                let Some(total) = total else { continue };
+                let km = distance[prev][to];
+                if km > options.max_daily_km {
+                    continue;
+                }
+                let options_here = [Some((0.0, false)), gain.map(|gain| (gain, true))];
+                for (gain, climbed) in options_here.into_iter().flatten() {
+                    let candidate = total + gain - km * options.km_cost;
+                    if next[to].is_none_or(|current| candidate > current) {
+                        next[to] = Some(candidate);
+                        from[to] = Some((prev, climbed));
+                    }
+                }
+            }
+        }
+        best = next;
+        came_from.push(from);
+    }
+
+    let Some(mut loc) = best
+        .iter()
+        .enumerate()
+        .filter_map(|(loc, total)| total.map(|total| (loc, total)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(loc, _)| loc)
+    else {
+        return Vec::new();
+    };
+
+    let mut stops = Vec::with_capacity(trip_days);
+    for day in (0..trip_days).rev() {
+        let Some((prev, climbed)) = came_from[day][loc] else {
+            break;
+        };
+        let drive_km = distance[prev][loc];
+        stops.push(match climbable(day, loc) {
+            Some(crag_day) if climbed => TripStop::Climb {
+                crag: loc - 1,
+                drive_km,
+                day: crag_day,
+            },
+            _ => TripStop::Rest {
+                crag: loc.checked_sub(1),
+                drive_km,
+            },
+        });
+        loc = prev;
+    }
+    stops.reverse();
+    stops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: (f64, f64) = (45.0, 6.0);
+
+    fn crag(lat: f64, lng: f64, scores: &[f64]) -> TripCrag {
+        TripCrag {
+            lat,
+            lng,
+            days: scores
+                .iter()
+                .map(|score| {
+                    Some(CragDay {
+                        score: *score,
+                        source: ScoreSource::Forecast,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn climbed(stops: &[TripStop]) -> Vec<Option<usize>> {
+        stops
+            .iter()
+            .map(|stop| match stop {
+                TripStop::Climb { crag, .. } => Some(*crag),
+                TripStop::Rest { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_bad_day_is_forced_to_rest() {
+        // About 11km north of the start
+        let crags = [crag(45.1, 6.0, &[80.0, 20.0, 80.0])];
+        let stops = plan_trip(START.0, START.1, &crags, 3, &TripOptions::default());
+
+        assert_eq!(climbed(&stops), vec![Some(0), None, Some(0)]);
+        assert!(matches!(
+            stops[1],
+            TripStop::Rest {
+                crag: Some(0),
+                drive_km
+            } if drive_km == 0.0
+        ));
+        match stops[0] {
+            TripStop::Climb { drive_km, .. } => assert!((drive_km - 11.1).abs() < 0.1),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn crags_out_of_reach_are_never_visited() {
+        // About 150km away with a 100km daily limit
+        let crags = [crag(46.35, 6.0, &[90.0, 90.0])];
+        let options = TripOptions {
+            max_daily_km: 100.0,
+            ..TripOptions::default()
+        };
+        let stops = plan_trip(START.0, START.1, &crags, 2, &options);
+
+        assert_eq!(
+            stops,
+            vec![
+                TripStop::Rest {
+                    crag: None,
+                    drive_km: 0.0
+                };
+                2
+            ]
+        );
+    }
+
+    #[test]
+    fn far_crags_are_reached_through_nearer_ones() {
+        // The far crag is two 89km legs away with a 100km daily limit
+        let near = crag(45.8, 6.0, &[60.0, 10.0, 10.0]);
+        let far = crag(46.6, 6.0, &[95.0, 95.0, 95.0]);
+        let options = TripOptions {
+            max_daily_km: 100.0,
+            ..TripOptions::default()
+        };
+        let stops = plan_trip(START.0, START.1, &[near, far], 3, &options);
+
+        assert_eq!(climbed(&stops), vec![Some(0), Some(1), Some(1)]);
+    }
+
+    #[test]
+    fn equal_crags_prefer_less_driving() {
+        let crags = [crag(45.5, 6.0, &[70.0]), crag(45.1, 6.0, &[70.0])];
+        let stops = plan_trip(START.0, START.1, &crags, 1, &TripOptions::default());
+
+        assert_eq!(climbed(&stops), vec![Some(1)]);
+    }
+}
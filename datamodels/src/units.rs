@@ -0,0 +1,351 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const KPH_PER_MS: f64 = 3.6;
+const MPH_PER_MS: f64 = 3600.0 / 1609.344;
+const M_PER_INCH: f64 = 0.0254;
+const M_PER_FOOT: f64 = 0.3048;
+const M_PER_MILE: f64 = 1609.344;
+
+// Converting through SI leaves float noise like 10.000000000000002 behind, nothing we
+// measure needs more than a millionth
+fn tidy(val: f64) -> f64 {
+    (val * 1e6).round() / 1e6
+}
+
+// Air temperature, held in degrees Celsius
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[serde(transparent)]
+pub struct Temperature(f64);
+
+impl Temperature {
+    pub fn from_celsius(celsius: f64) -> Self {
+        Self(celsius)
+    }
+
+    pub fn from_fahrenheit(fahrenheit: f64) -> Self {
+        Self((fahrenheit - 32.0) * 5.0 / 9.0)
+    }
+
+    pub fn celsius(self) -> f64 {
+        self.0
+    }
+
+    pub fn fahrenheit(self) -> f64 {
+        self.0 * 9.0 / 5.0 + 32.0
+    }
+
+    // This temperature made warmer, or colder when delta_c is negative
+    pub fn shifted(self, delta_c: f64) -> Self {
+        Self(self.0 + delta_c)
+    }
+}
+
+// Wind speed, held, written and read in metres per second
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Speed(f64);
+
+impl Speed {
+    pub fn from_ms(ms: f64) -> Self {
+        Self(ms)
+    }
+
+    pub fn from_kph(kph: f64) -> Self {
+        Self(kph / KPH_PER_MS)
+    }
+
+    pub fn from_mph(mph: f64) -> Self {
+        Self(mph / MPH_PER_MS)
+    }
+
+    pub fn ms(self) -> f64 {
+        self.0
+    }
+
+    pub fn kph(self) -> f64 {
+        self.0 * KPH_PER_MS
+    }
+
+    pub fn mph(self) -> f64 {
+        self.0 * MPH_PER_MS
+    }
+}
+
+impl Serialize for Speed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(tidy(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Speed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Self::from_ms)
+    }
+}
+
+// Rain, snow, elevations and distances, held, written and read in metres
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Length(f64);
+
+impl Length {
+    pub fn from_metres(m: f64) -> Self {
+        Self(m)
+    }
+
+    pub fn from_mm(mm: f64) -> Self {
+        Self(mm / 1000.0)
+    }
+
+    pub fn from_cm(cm: f64) -> Self {
+        Self(cm / 100.0)
+    }
+
+    pub fn from_km(km: f64) -> Self {
+        Self(km * 1000.0)
+    }
+
+    pub fn from_inches(inches: f64) -> Self {
+        Self(inches * M_PER_INCH)
+    }
+
+    pub fn metres(self) -> f64 {
+        self.0
+    }
+
+    pub fn mm(self) -> f64 {
+        self.0 * 1000.0
+    }
+
+    pub fn cm(self) -> f64 {
+        self.0 * 100.0
+    }
+
+    pub fn km(self) -> f64 {
+        self.0 / 1000.0
+    }
+
+    pub fn inches(self) -> f64 {
+        self.0 / M_PER_INCH
+    }
+
+    pub fn feet(self) -> f64 {
+        self.0 / M_PER_FOOT
+    }
+
+    pub fn miles(self) -> f64 {
+        self.0 / M_PER_MILE
+    }
+}
+
+impl std::ops::Add for Length {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+impl std::iter::Sum for Length {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|len| len.0).sum())
+    }
+}
+
+impl Serialize for Length {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(tidy(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Length {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Self::from_metres)
+    }
+}
+
+// For #[serde(with)] on speeds climbers write themselves, which are given in km/h
+pub mod kph {
+    use super::{tidy, Speed};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(speed: &Speed, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(tidy(speed.kph()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Speed, D::Error> {
+        f64::deserialize(deserializer).map(Speed::from_kph)
+    }
+
+    pub mod option {
+        use super::{tidy, Speed};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            speed: &Option<Speed>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match speed {
+                Some(speed) => serializer.serialize_some(&tidy(speed.kph())),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Speed>, D::Error> {
+            Option::<f64>::deserialize(deserializer).map(|kph| kph.map(Speed::from_kph))
+        }
+    }
+}
+
+// Unit system for values shown to a client. Quantities are stored once, this only
+// decides what they are converted to on the way out.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+// What each kind of value in a response is measured in
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct UnitLabels {
+    pub temperature: &'static str,
+    pub speed: &'static str,
+    pub precipitation: &'static str,
+    pub snow: &'static str,
+    pub distance: &'static str,
+    pub elevation: &'static str,
+}
+
+impl Units {
+    pub fn labels(self) -> UnitLabels {
+        match self {
+            Self::Metric => UnitLabels {
+                temperature: "°C",
+                speed: "km/h",
+                precipitation: "mm",
+                snow: "cm",
+                distance: "km",
+                elevation: "m",
+            },
+            Self::Imperial => UnitLabels {
+                temperature: "°F",
+                speed: "mph",
+                precipitation: "in",
+                snow: "in",
+                distance: "mi",
+                elevation: "ft",
+            },
+        }
+    }
+
+    pub fn temperature(self, temp: Temperature) -> f64 {
+        tidy(match self {
+            Self::Metric => temp.celsius(),
+            Self::Imperial => temp.fahrenheit(),
+        })
+    }
+
+    pub fn speed(self, speed: Speed) -> f64 {
+        tidy(match self {
+            Self::Metric => speed.kph(),
+            Self::Imperial => speed.mph(),
+        })
+    }
+
+    pub fn precipitation(self, precip: Length) -> f64 {
+        tidy(match self {
+            Self::Metric => precip.mm(),
+            Self::Imperial => precip.inches(),
+        })
+    }
+
+    pub fn snow(self, snow: Length) -> f64 {
+        tidy(match self {
+            Self::Metric => snow.cm(),
+            Self::Imperial => snow.inches(),
+        })
+    }
+
+    pub fn distance(self, distance: Length) -> f64 {
+        tidy(match self {
+            Self::Metric => distance.km(),
+            Self::Imperial => distance.miles(),
+        })
+    }
+
+    pub fn elevation(self, elevation: Length) -> f64 {
+        tidy(match self {
+            Self::Metric => elevation.metres(),
+            Self::Imperial => elevation.feet(),
+        })
+    }
+}
+
+// A value quoted in a score's reason, kept so the reason can be put in the client's units
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Reading {
+    Temperature(Temperature),
+    Speed(Speed),
+    Precipitation(Length),
+}
+
+impl Reading {
+    pub fn describe(self, units: Units) -> String {
+        let labels = units.labels();
+        match self {
+            Self::Temperature(temp) => {
+                format!("{:.0}{}", units.temperature(temp), labels.temperature)
+            }
+            Self::Speed(speed) => format!("{:.0}{}", units.speed(speed), labels.speed),
+            // A tenth of an inch is a lot of rain, so inches get another place
+            Self::Precipitation(precip) => match units {
+                Units::Metric => {
+                    format!("{:.1}{}", units.precipitation(precip), labels.precipitation)
+                }
+                Units::Imperial => {
+                    format!("{:.2}{}", units.precipitation(precip), labels.precipitation)
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantities_are_written_in_si() {
+        assert_eq!(
+            serde_json::to_string(&Speed::from_kph(36.0)).unwrap(),
+            "10.0"
+        );
+        assert_eq!(
+            serde_json::to_string(&Length::from_mm(2.5)).unwrap(),
+            "0.0025"
+        );
+        let speed: Speed = serde_json::from_str("10.0").unwrap();
+        assert!((speed.kph() - 36.0).abs() < 1e-9);
+        let len: Length = serde_json::from_str("0.0025").unwrap();
+        assert!((len.mm() - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn responses_convert_to_the_unit_system() {
+        assert_eq!(
+            Units::Metric.temperature(Temperature::from_celsius(25.0)),
+            25.0
+        );
+        assert_eq!(
+            Units::Imperial.temperature(Temperature::from_celsius(25.0)),
+            77.0
+        );
+        assert_eq!(Units::Imperial.speed(Speed::from_kph(16.09344)), 10.0);
+        assert_eq!(Units::Imperial.precipitation(Length::from_mm(25.4)), 1.0);
+        assert_eq!(Units::Metric.snow(Length::from_mm(25.0)), 2.5);
+        assert_eq!(Units::Imperial.distance(Length::from_km(1.609344)), 1.0);
+    }
+}
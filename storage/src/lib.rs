@@ -0,0 +1,399 @@
+mod memory;
+mod mongo;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use datamodels::{
+    api_key::ApiKey,
+    archive::{ArchivedObservation, ObservationKind},
+    climate::Climatology,
+    geo::GeoPoint,
+    profile::Profile,
+    Area, AreaForecast, AreaWeather,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, sync::Arc};
+
+pub use memory::MemoryStorage;
+pub use mongo::{MongoConfig, MongoStorage};
+
+#[derive(Debug)]
+pub enum StorageError {
+    Config(String),
+    Mongo(mongodb::error::Error),
+    NotFound(String),
+    InvalidFilter(String),
+    InvalidArea(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(msg) => write!(f, "invalid storage config: {}", msg),
+            Self::Mongo(err) => write!(f, "mongo error: {}", err),
+            Self::NotFound(msg) => write!(f, "not found: {}", msg),
+            Self::InvalidFilter(msg) => write!(f, "invalid filter: {}", msg),
+            Self::InvalidArea(msg) => write!(f, "invalid area: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<mongodb::error::Error> for StorageError {
+    fn from(err: mongodb::error::Error) -> Self {
+        Self::Mongo(err)
+    }
+}
+
+const DEFAULT_ARCHIVE_RETENTION_DAYS: u32 = 365;
+
+// The area as it should be stored, refusing coordinates that are off the map
+pub(crate) fn located(area: &Area) -> Result<Area, StorageError> {
+    area.located()
+        .map_err(|err| StorageError::InvalidArea(format!("{}: {}", area.area_name, err)))
+}
+
+pub(crate) fn env_u32(key: &str) -> Result<Option<u32>, StorageError> {
+    match std::env::var(key) {
+        Ok(val) => val
+            .parse()
+            .map(Some)
+            .map_err(|_| StorageError::Config(format!("{} must be a number", key))),
+        Err(_) => Ok(None),
+    }
+}
+
+// How long archived observations are kept, from ARCHIVE_RETENTION_DAYS
+pub fn archive_retention_days() -> Result<u32, StorageError> {
+    Ok(env_u32("ARCHIVE_RETENTION_DAYS")?.unwrap_or(DEFAULT_ARCHIVE_RETENTION_DAYS))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NearbyArea {
+    #[serde(flatten)]
+    pub area: Area,
+    pub distance_km: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lng: f64,
+    pub max_lat: f64,
+    pub max_lng: f64,
+}
+
+impl BoundingBox {
+    // A box whose min_lng is east of max_lng wraps across the antimeridian
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min_lng > self.max_lng
+    }
+
+    pub fn contains(&self, lat: f64, lng: f64) -> bool {
+        let lng_inside = if self.crosses_antimeridian() {
+            lng >= self.min_lng || lng <= self.max_lng
+        } else {
+            lng >= self.min_lng && lng <= self.max_lng
+        };
+        lat >= self.min_lat && lat <= self.max_lat && lng_inside
+    }
+}
+
+// Criteria are combined with AND, an empty filter matches every area
+#[derive(Debug, Clone, Default)]
+pub struct AreaFilter {
+    // Regular expression matched against area_name
+    pub name_pattern: Option<String>,
+    pub bbox: Option<BoundingBox>,
+}
+
+#[async_trait]
+pub trait AreaRepository: Send + Sync {
+    async fn all_areas(&self) -> Result<Vec<Area>, StorageError>;
+
+    async fn find_areas_by_name(&self, area_name: &str) -> Result<Vec<Area>, StorageError>;
+
+    // Areas within radius_km of the point, nearest first
+    async fn areas_near(
+        &self,
+        point: GeoPoint,
+        radius_km: f64,
+    ) -> Result<Vec<NearbyArea>, StorageError>;
+
+    // Closest area to the point that is at most max_km away
+    async fn nearest_area(
+        &self,
+        point: GeoPoint,
+        max_km: f64,
+    ) -> Result<Option<Area>, StorageError> {
+        Ok(self
+            .areas_near(point, max_km)
+            .await?
+            .into_iter()
+            .next()
+            .map(|nearby| nearby.area))
+    }
+
+    // Closest crag, forecasts are stored per crag rather than per wall
+    async fn nearest_crag(
+        &self,
+        point: GeoPoint,
+        max_km: f64,
+    ) -> Result<Option<Area>, StorageError> {
+        Ok(self
+            .areas_near(point, max_km)
+            .await?
+            .into_iter()
+            .find(|nearby| nearby.area.is_crag)
+            .map(|nearby| nearby.area))
+    }
+
+    async fn area_by_uuid(&self, uuid: &str) -> Result<Option<Area>, StorageError>;
+
+    // Crags in the region, including the region itself when it is a crag
+    async fn crags_within(&self, region_uuid: &str) -> Result<Vec<Area>, StorageError>;
+
+    async fn insert_areas(&self, areas: &[Area]) -> Result<usize, StorageError>;
+
+    // Insert or replace an area matched on its uuid, or on its name and coordinates
+    // for areas without one
+    async fn upsert_area(&self, area: &Area) -> Result<(), StorageError>;
+
+    // Record that a sync run starting at seen_at still found these areas upstream
+    async fn mark_areas_seen(
+        &self,
+        uuids: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), StorageError>;
+
+    // Delete and return synced areas that no sync run since `since` has seen
+    async fn remove_areas_not_seen_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Area>, StorageError>;
+
+    async fn delete_all_areas(&self) -> Result<u64, StorageError>;
+
+    async fn find_areas(&self, filter: &AreaFilter) -> Result<Vec<Area>, StorageError>;
+
+    async fn delete_areas(&self, filter: &AreaFilter) -> Result<u64, StorageError>;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncStats {
+    pub fetched: u64,
+    pub inserted: u64,
+    pub updated: u64,
+    pub renamed: u64,
+    pub unchanged: u64,
+    pub deleted: u64,
+}
+
+// Progress of an incremental sync so an interrupted run resumes where it stopped
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SyncCheckpoint {
+    pub name: String,
+    pub run_started_at: DateTime<Utc>,
+    // Upstream ids still to fetch in the current run
+    pub pending: Vec<String>,
+    // Set once the current run has walked everything
+    pub completed_at: Option<DateTime<Utc>>,
+    pub last_completed_at: Option<DateTime<Utc>>,
+    pub stats: SyncStats,
+}
+
+#[async_trait]
+pub trait SyncCheckpointRepository: Send + Sync {
+    async fn load_checkpoint(&self, name: &str) -> Result<Option<SyncCheckpoint>, StorageError>;
+
+    async fn save_checkpoint(&self, checkpoint: &SyncCheckpoint) -> Result<(), StorageError>;
+}
+
+#[async_trait]
+pub trait WeatherRepository: Send + Sync {
+    // Insert or replace the forecast for an area and date
+    async fn upsert_weather(&self, weather: &AreaWeather) -> Result<(), StorageError>;
+
+    async fn weather_for_area(
+        &self,
+        area: &Area,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AreaWeather>, StorageError>;
+
+    async fn weather_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AreaWeather>, StorageError>;
+
+    async fn delete_weather_before(&self, date: NaiveDate) -> Result<u64, StorageError>;
+}
+
+// One series in the archive: an area, the provider and the kind of observation
+pub(crate) type ArchiveKey = (Option<String>, String, u64, u64, String, ObservationKind);
+
+pub(crate) fn archive_key(archived: &ArchivedObservation) -> ArchiveKey {
+    (
+        archived.area_uuid.clone(),
+        archived.area_name.clone(),
+        archived.lat.to_bits(),
+        archived.lng.to_bits(),
+        archived.provider.clone(),
+        archived.kind,
+    )
+}
+
+#[async_trait]
+pub trait ObservationRepository: Send + Sync {
+    // Append observations that are newer than what is already archived for their
+    // area, provider and kind, so refetching the same hours adds nothing
+    async fn append_observations(
+        &self,
+        observations: &[ArchivedObservation],
+    ) -> Result<usize, StorageError>;
+
+    // Oldest first
+    async fn observations_for_area(
+        &self,
+        area: &Area,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ArchivedObservation>, StorageError>;
+}
+
+// Monthly climate summaries, expensive to build so computed once and kept
+#[async_trait]
+pub trait ClimatologyRepository: Send + Sync {
+    async fn climatology_for_area(&self, area: &Area) -> Result<Option<Climatology>, StorageError>;
+
+    // Replaces any earlier summary for the same area
+    async fn save_climatology(&self, climatology: &Climatology) -> Result<(), StorageError>;
+}
+
+#[async_trait]
+pub trait ProfileRepository: Send + Sync {
+    async fn profile_by_id(&self, id: &str) -> Result<Option<Profile>, StorageError>;
+
+    // Insert or replace the profile with the same id
+    async fn save_profile(&self, profile: &Profile) -> Result<(), StorageError>;
+}
+
+// Ids for new profiles, ObjectId hex so they sort by creation time in either backend
+pub fn new_profile_id() -> String {
+    mongodb::bson::oid::ObjectId::new().to_hex()
+}
+
+#[async_trait]
+pub trait ApiKeyRepository: Send + Sync {
+    async fn api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError>;
+
+    async fn all_api_keys(&self) -> Result<Vec<ApiKey>, StorageError>;
+
+    async fn insert_api_key(&self, key: &ApiKey) -> Result<(), StorageError>;
+
+    // Marks the key revoked and returns it, None when there is no such key
+    async fn revoke_api_key(
+        &self,
+        id: &str,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<Option<ApiKey>, StorageError>;
+
+    // Adds to the key's usage counters
+    async fn record_usage(
+        &self,
+        id: &str,
+        requests: u64,
+        rate_limited: u64,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), StorageError>;
+}
+
+// Ids for new api keys, made the same way as profile ids
+pub fn new_api_key_id() -> String {
+    mongodb::bson::oid::ObjectId::new().to_hex()
+}
+
+// A provider response shared between api instances until it expires
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedForecast {
+    pub key: String,
+    // Days the forecast was fetched for, it can serve requests for fewer
+    pub days: u8,
+    pub forecast: AreaForecast,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ForecastCacheRepository: Send + Sync {
+    // The entry for the key if it has not expired yet
+    async fn cached_forecast(&self, key: &str) -> Result<Option<CachedForecast>, StorageError>;
+
+    async fn save_cached_forecast(&self, cached: &CachedForecast) -> Result<(), StorageError>;
+}
+
+// The repositories a binary needs, all backed by the same store
+#[derive(Clone)]
+pub struct Repositories {
+    pub areas: Arc<dyn AreaRepository>,
+    pub weather: Arc<dyn WeatherRepository>,
+    pub checkpoints: Arc<dyn SyncCheckpointRepository>,
+    pub observations: Arc<dyn ObservationRepository>,
+    pub climatology: Arc<dyn ClimatologyRepository>,
+    pub profiles: Arc<dyn ProfileRepository>,
+    pub api_keys: Arc<dyn ApiKeyRepository>,
+    // Only a database can share cached forecasts between processes
+    pub forecast_cache: Option<Arc<dyn ForecastCacheRepository>>,
+}
+
+impl Repositories {
+    pub fn mongo(storage: MongoStorage) -> Self {
+        Self {
+            areas: Arc::new(storage.clone()),
+            weather: Arc::new(storage.clone()),
+            checkpoints: Arc::new(storage.clone()),
+            observations: Arc::new(storage.clone()),
+            climatology: Arc::new(storage.clone()),
+            profiles: Arc::new(storage.clone()),
+            api_keys: Arc::new(storage.clone()),
+            forecast_cache: Some(Arc::new(storage)),
+        }
+    }
+
+    pub fn memory(storage: MemoryStorage) -> Self {
+        Self {
+            areas: Arc::new(storage.clone()),
+            weather: Arc::new(storage.clone()),
+            checkpoints: Arc::new(storage.clone()),
+            observations: Arc::new(storage.clone()),
+            climatology: Arc::new(storage.clone()),
+            profiles: Arc::new(storage.clone()),
+            api_keys: Arc::new(storage),
+            forecast_cache: None,
+        }
+    }
+
+    // Picks the backend from STORAGE: mongo (default) or memory. The memory backend
+    // starts empty unless AREAS_SEED_FILE points at a json array of areas. Both keep
+    // archived observations for ARCHIVE_RETENTION_DAYS.
+    pub async fn from_env() -> Result<Self, StorageError> {
+        let backend = std::env::var("STORAGE").unwrap_or_else(|_| "mongo".to_string());
+        match backend.as_str() {
+            "mongo" => Ok(Self::mongo(MongoStorage::from_env().await?)),
+            "memory" => {
+                let storage = match std::env::var("AREAS_SEED_FILE") {
+                    Ok(path) => MemoryStorage::from_seed_file(&path)?,
+                    Err(_) => MemoryStorage::new(),
+                };
+                Ok(Self::memory(
+                    storage.with_archive_retention(archive_retention_days()?),
+                ))
+            }
+            other => Err(StorageError::Config(format!(
+                "unknown STORAGE {}, expected mongo or memory",
+                other
+            ))),
+        }
+    }
+}
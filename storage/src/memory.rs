@@ -0,0 +1,940 @@
+use crate::{
+    archive_key, located, ApiKeyRepository, AreaFilter, AreaRepository, BoundingBox,
+    ClimatologyRepository, NearbyArea, ObservationRepository, ProfileRepository, StorageError,
+    SyncCheckpoint, SyncCheckpointRepository, WeatherRepository,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use datamodels::{
+    api_key::ApiKey, archive::ArchivedObservation, climate::Climatology, geo::GeoPoint,
+    profile::Profile, Area, AreaWeather,
+};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+// Same identity the mongo unique index uses: area name, coordinates and date
+type WeatherKey = (String, u64, u64, NaiveDate);
+
+fn weather_key(weather: &AreaWeather) -> WeatherKey {
+    (
+        weather.area_name.clone(),
+        weather.lat.to_bits(),
+        weather.lng.to_bits(),
+        weather.date,
+    )
+}
+
+fn same_area(a: &Area, b: &Area) -> bool {
+    match (&a.uuid, &b.uuid) {
+        (Some(a_uuid), Some(b_uuid)) => a_uuid == b_uuid,
+        (None, None) => {
+            a.area_name == b.area_name
+                && a.metadata.lat == b.metadata.lat
+                && a.metadata.lng == b.metadata.lng
+        }
+        _ => false,
+    }
+}
+
+struct AreaMatcher {
+    name: Option<Regex>,
+    bbox: Option<BoundingBox>,
+}
+
+impl AreaMatcher {
+    fn new(filter: &AreaFilter) -> Result<Self, StorageError> {
+        let name = match &filter.name_pattern {
+            Some(pattern) => Some(
+                Regex::new(pattern).map_err(|err| StorageError::InvalidFilter(err.to_string()))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            name,
+            bbox: filter.bbox,
+        })
+    }
+
+    fn matches(&self, area: &Area) -> bool {
+        let name_matches = self
+            .name
+            .as_ref()
+            .is_none_or(|name| name.is_match(&area.area_name));
+        let bbox_matches = self
+            .bbox
+            .is_none_or(|bbox| bbox.contains(area.metadata.lat, area.metadata.lng));
+        name_matches && bbox_matches
+    }
+}
+
+// Keeps everything in process memory, for tests and local development without a database.
+// Clones share the same data.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    areas: Arc<RwLock<Vec<Area>>>,
+    // When a sync run last saw each area uuid
+    seen: Arc<RwLock<HashMap<String, DateTime<Utc>>>>,
+    weather: Arc<RwLock<BTreeMap<WeatherKey, AreaWeather>>>,
+    checkpoints: Arc<RwLock<HashMap<String, SyncCheckpoint>>>,
+    observations: Arc<RwLock<Vec<ArchivedObservation>>>,
+    // Archived observations older than this are dropped on the next append
+    archive_retention: Option<Duration>,
+    climatology: Arc<RwLock<Vec<Climatology>>>,
+    profiles: Arc<RwLock<HashMap<String, Profile>>>,
+    api_keys: Arc<RwLock<Vec<ApiKey>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Checked and located like inserted areas, one bad area rejects the lot
+    pub fn with_areas(areas: Vec<Area>) -> Result<Self, StorageError> {
+        let areas = areas
+            .iter()
+            .map(located)
+            .collect::<Result<Vec<Area>, StorageError>>()?;
+        Ok(Self {
+            areas: Arc::new(RwLock::new(areas)),
+            ..Self::default()
+        })
+    }
+
+    pub fn with_archive_retention(self, days: u32) -> Self {
+        Self {
+            archive_retention: Some(Duration::days(days as i64)),
+            ..self
+        }
+    }
+
+    // Loads a json array of areas, e.g. a dump of the mongo areas collection
+    pub fn from_seed_file(path: &str) -> Result<Self, StorageError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| StorageError::Config(format!("could not read {}: {}", path, err)))?;
+        let areas: Vec<Area> = serde_json::from_str(&contents)
+            .map_err(|err| StorageError::Config(format!("could not parse {}: {}", path, err)))?;
+        Self::with_areas(areas)
+    }
+
+    fn read_areas(&self) -> RwLockReadGuard<'_, Vec<Area>> {
+        self.areas.read().expect("memory storage lock poisoned")
+    }
+
+    fn write_areas(&self) -> RwLockWriteGuard<'_, Vec<Area>> {
+        self.areas.write().expect("memory storage lock poisoned")
+    }
+
+    fn read_weather(&self) -> RwLockReadGuard<'_, BTreeMap<WeatherKey, AreaWeather>> {
+        self.weather.read().expect("memory storage lock poisoned")
+    }
+
+    fn write_weather(&self) -> RwLockWriteGuard<'_, BTreeMap<WeatherKey, AreaWeather>> {
+        self.weather.write().expect("memory storage lock poisoned")
+    }
+}
+
+#[async_trait]
+impl AreaRepository for MemoryStorage {
+    async fn all_areas(&self) -> Result<Vec<Area>, StorageError> {
+        Ok(self.read_areas().clone())
+    }
+
+    async fn find_areas_by_name(&self, area_name: &str) -> Result<Vec<Area>, StorageError> {
+        Ok(self
+            .read_areas()
+            .iter()
+            .filter(|area| area.area_name == area_name)
+            .cloned()
+            .collect())
+    }
+
+    async fn areas_near(
+        &self,
+        point: GeoPoint,
+        radius_km: f64,
+    ) -> Result<Vec<NearbyArea>, StorageError> {
+        let mut nearby: Vec<NearbyArea> = self
+            .read_areas()
+            .iter()
+            .map(|area| NearbyArea {
+                distance_km: point.distance_km(area.metadata.lat, area.metadata.lng),
+                area: area.clone(),
+            })
+            .filter(|nearby| nearby.distance_km <= radius_km)
+            .collect();
+        nearby.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+        Ok(nearby)
+    }
+
+    async fn area_by_uuid(&self, uuid: &str) -> Result<Option<Area>, StorageError> {
+        Ok(self
+            .read_areas()
+            .iter()
+            .find(|area| area.uuid.as_deref() == Some(uuid))
+            .cloned())
+    }
+
+    async fn crags_within(&self, region_uuid: &str) -> Result<Vec<Area>, StorageError> {
+        Ok(self
+            .read_areas()
+            .iter()
+            .filter(|area| area.is_crag && area.is_within(region_uuid))
+            .cloned()
+            .collect())
+    }
+
+    async fn insert_areas(&self, areas: &[Area]) -> Result<usize, StorageError> {
+        let areas = areas
+            .iter()
+            .map(located)
+            .collect::<Result<Vec<Area>, StorageError>>()?;
+        let count = areas.len();
+        self.write_areas().extend(areas);
+        Ok(count)
+    }
+
+    async fn upsert_area(&self, area: &Area) -> Result<(), StorageError> {
+        let area = located(area)?;
+        let mut areas = self.write_areas();
+        match areas.iter_mut().find(|existing| same_area(existing, &area)) {
+            Some(existing) => *existing = area,
+            None => areas.push(area),
+        }
+        Ok(())
+    }
+
+    async fn delete_all_areas(&self) -> Result<u64, StorageError> {
+        let mut areas = self.write_areas();
+        let count = areas.len() as u64;
+        areas.clear();
+        Ok(count)
+    }
+
+    async fn mark_areas_seen(
+        &self,
+        uuids: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let mut seen = self.seen.write().expect("memory storage lock poisoned");
+        for uuid in uuids {
+            seen.insert(uuid.clone(), seen_at);
+        }
+        Ok(())
+    }
+
+    async fn remove_areas_not_seen_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Area>, StorageError> {
+        let seen = self.seen.read().expect("memory storage lock poisoned");
+        let mut areas = self.write_areas();
+        let (stale, fresh): (Vec<Area>, Vec<Area>) = areas.drain(..).partition(|area| {
+            area.uuid
+                .as_ref()
+                .and_then(|uuid| seen.get(uuid))
+                .is_some_and(|seen_at| *seen_at < since)
+        });
+        *areas = fresh;
+        Ok(stale)
+    }
+
+    async fn find_areas(&self, filter: &AreaFilter) -> Result<Vec<Area>, StorageError> {
+        let matcher = AreaMatcher::new(filter)?;
+        Ok(self
+            .read_areas()
+            .iter()
+            .filter(|area| matcher.matches(area))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_areas(&self, filter: &AreaFilter) -> Result<u64, StorageError> {
+        let matcher = AreaMatcher::new(filter)?;
+        let mut areas = self.write_areas();
+        let before = areas.len();
+        areas.retain(|area| !matcher.matches(area));
+        Ok((before - areas.len()) as u64)
+    }
+}
+
+#[async_trait]
+impl SyncCheckpointRepository for MemoryStorage {
+    async fn load_checkpoint(&self, name: &str) -> Result<Option<SyncCheckpoint>, StorageError> {
+        Ok(self
+            .checkpoints
+            .read()
+            .expect("memory storage lock poisoned")
+            .get(name)
+            .cloned())
+    }
+
+    async fn save_checkpoint(&self, checkpoint: &SyncCheckpoint) -> Result<(), StorageError> {
+        self.checkpoints
+            .write()
+            .expect("memory storage lock poisoned")
+            .insert(checkpoint.name.clone(), checkpoint.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ObservationRepository for MemoryStorage {
+    async fn append_observations(
+        &self,
+        observations: &[ArchivedObservation],
+    ) -> Result<usize, StorageError> {
+        let mut archive = self
+            .observations
+            .write()
+            .expect("memory storage lock poisoned");
+        let mut latest: HashMap<_, DateTime<Utc>> = HashMap::new();
+        for archived in archive.iter() {
+            let newest = latest
+                .entry(archive_key(archived))
+                .or_insert(archived.observed_at);
+            *newest = (*newest).max(archived.observed_at);
+        }
+
+        let fresh: Vec<ArchivedObservation> = observations
+            .iter()
+            .filter(|archived| {
+                latest
+                    .get(&archive_key(archived))
+                    .is_none_or(|newest| archived.observed_at > *newest)
+            })
+            .cloned()
+            .collect();
+        let count = fresh.len();
+        archive.extend(fresh);
+        if let Some(retention) = self.archive_retention {
+            let cutoff = Utc::now() - retention;
+            archive.retain(|archived| archived.observed_at >= cutoff);
+        }
+        Ok(count)
+    }
+
+    async fn observations_for_area(
+        &self,
+        area: &Area,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ArchivedObservation>, StorageError> {
+        let mut found: Vec<ArchivedObservation> = self
+            .observations
+            .read()
+            .expect("memory storage lock poisoned")
+            .iter()
+            .filter(|archived| {
+                archived.is_for(area) && archived.observed_at >= from && archived.observed_at <= to
+            })
+            .cloned()
+            .collect();
+        found.sort_by_key(|archived| archived.observed_at);
+        Ok(found)
+    }
+}
+
+fn same_climatology_area(
+    climatology: &Climatology,
+    area_uuid: Option<&str>,
+    area_name: &str,
+    lat: f64,
+    lng: f64,
+) -> bool {
+    match area_uuid {
+        Some(uuid) => climatology.area_uuid.as_deref() == Some(uuid),
+        None => {
+            climatology.area_name == area_name && climatology.lat == lat && climatology.lng == lng
+        }
+    }
+}
+
+#[async_trait]
+impl ClimatologyRepository for MemoryStorage {
+    async fn climatology_for_area(&self, area: &Area) -> Result<Option<Climatology>, StorageError> {
+        Ok(self
+            .climatology
+            .read()
+            .expect("memory storage lock poisoned")
+            .iter()
+            .find(|climatology| {
+                same_climatology_area(
+                    climatology,
+                    area.uuid.as_deref(),
+                    &area.area_name,
+                    area.metadata.lat,
+                    area.metadata.lng,
+                )
+            })
+            .cloned())
+    }
+
+    async fn save_climatology(&self, climatology: &Climatology) -> Result<(), StorageError> {
+        let mut stored = self
+            .climatology
+            .write()
+            .expect("memory storage lock poisoned");
+        stored.retain(|existing| {
+            !same_climatology_area(
+                existing,
+                climatology.area_uuid.as_deref(),
+                &climatology.area_name,
+                climatology.lat,
+                climatology.lng,
+            )
+        });
+        stored.push(climatology.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProfileRepository for MemoryStorage {
+    async fn profile_by_id(&self, id: &str) -> Result<Option<Profile>, StorageError> {
+        Ok(self
+            .profiles
+            .read()
+            .expect("memory storage lock poisoned")
+            .get(id)
+            .cloned())
+    }
+
+    async fn save_profile(&self, profile: &Profile) -> Result<(), StorageError> {
+        self.profiles
+            .write()
+            .expect("memory storage lock poisoned")
+            .insert(profile.id.clone(), profile.clone());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for MemoryStorage {
+    async fn api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        Ok(self
+            .api_keys
+            .read()
+            .expect("memory storage lock poisoned")
+            .iter()
+            .find(|key| key.key_hash == key_hash)
+            .cloned())
+    }
+
+    async fn all_api_keys(&self) -> Result<Vec<ApiKey>, StorageError> {
+        Ok(self
+            .api_keys
+            .read()
+            .expect("memory storage lock poisoned")
+            .clone())
+    }
+
+    async fn insert_api_key(&self, key: &ApiKey) -> Result<(), StorageError> {
+        self.api_keys
+            .write()
+            .expect("memory storage lock poisoned")
+            .push(key.clone());
+        Ok(())
+    }
+
+    async fn revoke_api_key(
+        &self,
+        id: &str,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<Option<ApiKey>, StorageError> {
+        let mut keys = self.api_keys.write().expect("memory storage lock poisoned");
+        Ok(keys.iter_mut().find(|key| key.id == id).map(|key| {
+            key.revoked_at.get_or_insert(revoked_at);
+            key.clone()
+        }))
+    }
+
+    async fn record_usage(
+        &self,
+        id: &str,
+        requests: u64,
+        rate_limited: u64,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        let mut keys = self.api_keys.write().expect("memory storage lock poisoned");
+        if let Some(key) = keys.iter_mut().find(|key| key.id == id) {
+            key.usage.requests += requests;
+            key.usage.rate_limited += rate_limited;
+            key.usage.last_used_at = Some(used_at);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WeatherRepository for MemoryStorage {
+    async fn upsert_weather(&self, weather: &AreaWeather) -> Result<(), StorageError> {
+        self.write_weather()
+            .insert(weather_key(weather), weather.clone());
+        Ok(())
+    }
+
+    async fn weather_for_area(
+        &self,
+        area: &Area,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AreaWeather>, StorageError> {
+        Ok(self
+            .read_weather()
+            .values()
+            .filter(|weather| {
+                weather.area_name == area.area_name
+                    && weather.lat == area.metadata.lat
+                    && weather.lng == area.metadata.lng
+                    && weather.date >= from
+                    && weather.date <= to
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn weather_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AreaWeather>, StorageError> {
+        Ok(self
+            .read_weather()
+            .values()
+            .filter(|weather| weather.date >= from && weather.date <= to)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_weather_before(&self, date: NaiveDate) -> Result<u64, StorageError> {
+        let mut weather = self.write_weather();
+        let before = weather.len();
+        weather.retain(|_, val| val.date >= date);
+        Ok((before - weather.len()) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use datamodels::{
+        archive::ObservationKind,
+        scoring::DayScore,
+        units::{Length, Speed, Temperature},
+        DailyForecast, Metadata, Observation,
+    };
+
+    fn area(uuid: Option<&str>, name: &str, lat: f64, lng: f64) -> Area {
+        Area {
+            uuid: uuid.map(str::to_string),
+            area_name: name.to_string(),
+            metadata: Metadata { lat, lng },
+            location: None,
+            parent_uuid: None,
+            ancestors: Vec::new(),
+            path_tokens: Vec::new(),
+            is_leaf: true,
+            is_crag: true,
+            climbing: None,
+            aspect_deg: None,
+        }
+    }
+
+    fn crag_in(uuid: &str, name: &str, lat: f64, lng: f64, region: &str) -> Area {
+        Area {
+            ancestors: vec![region.to_string()],
+            ..area(Some(uuid), name, lat, lng)
+        }
+    }
+
+    fn weather(area: &Area, date: NaiveDate, score: f64) -> AreaWeather {
+        AreaWeather {
+            uuid: area.uuid.clone(),
+            area_name: area.area_name.clone(),
+            lat: area.metadata.lat,
+            lng: area.metadata.lng,
+            date,
+            provider: "test".to_string(),
+            fetched_at: Utc::now(),
+            forecast: DailyForecast {
+                date,
+                max_temp: Temperature::from_celsius(15.0),
+                min_temp: Temperature::from_celsius(5.0),
+                avg_temp: Temperature::from_celsius(10.0),
+                max_wind: Speed::from_kph(10.0),
+                total_precip: Length::default(),
+                total_snow: Length::default(),
+                avg_humidity: None,
+                chance_of_rain: None,
+                uv: None,
+                condition: None,
+                us_epa_index: None,
+                sunrise: None,
+                sunset: None,
+                hours: Vec::new(),
+            },
+            score: DayScore {
+                date,
+                score,
+                factors: Vec::new(),
+                best_window_start: None,
+                hours: Vec::new(),
+            },
+            rock: None,
+            site: None,
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 6, day).unwrap()
+    }
+
+    fn observed(
+        area: &Area,
+        kind: ObservationKind,
+        observed_at: DateTime<Utc>,
+    ) -> ArchivedObservation {
+        ArchivedObservation {
+            area_uuid: area.uuid.clone(),
+            area_name: area.area_name.clone(),
+            lat: area.metadata.lat,
+            lng: area.metadata.lng,
+            provider: "test".to_string(),
+            kind,
+            observed_at,
+            fetched_at: observed_at,
+            observation: Observation {
+                time: observed_at.naive_utc(),
+                temp: Temperature::from_celsius(15.0),
+                feels_like: None,
+                humidity: 50.0,
+                wind: Speed::from_kph(0.0),
+                gust: None,
+                wind_degree: None,
+                precip: Length::default(),
+                snow: None,
+                cloud: 100.0,
+                chance_of_rain: None,
+                uv: None,
+                is_day: Some(true),
+                condition: None,
+                us_epa_index: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn upsert_area_replaces_by_uuid() {
+        let storage = MemoryStorage::new();
+        storage
+            .upsert_area(&Area {
+                aspect_deg: Some(180.0),
+                ..area(Some("a"), "Old name", 49.7, -123.1)
+            })
+            .await
+            .unwrap();
+        storage
+            .upsert_area(&area(Some("a"), "New name", 49.8, -123.2))
+            .await
+            .unwrap();
+
+        let areas = storage.all_areas().await.unwrap();
+        assert_eq!(areas.len(), 1);
+        assert_eq!(areas[0].area_name, "New name");
+        assert_eq!(areas[0].aspect_deg, None);
+        // Stored areas get their GeoJSON location
+        assert!(areas[0].location.is_some());
+    }
+
+    #[tokio::test]
+    async fn upsert_area_without_uuid_matches_name_and_coordinates() {
+        let storage = MemoryStorage::new();
+        storage
+            .upsert_area(&area(None, "Smoke Bluffs", 49.7, -123.1))
+            .await
+            .unwrap();
+        storage
+            .upsert_area(&area(None, "Smoke Bluffs", 49.7, -123.1))
+            .await
+            .unwrap();
+        storage
+            .upsert_area(&area(None, "Smoke Bluffs", 49.8, -123.1))
+            .await
+            .unwrap();
+        assert_eq!(storage.all_areas().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn upsert_area_rejects_bad_coordinates() {
+        let storage = MemoryStorage::new();
+        let result = storage
+            .upsert_area(&area(Some("a"), "Nowhere", 91.0, 0.0))
+            .await;
+        assert!(matches!(result, Err(StorageError::InvalidArea(_))));
+        assert!(storage.all_areas().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn seeded_areas_are_located_and_checked() {
+        let storage =
+            MemoryStorage::with_areas(vec![area(Some("a"), "Wrapped", 49.7, 236.9)]).unwrap();
+        let areas = storage.read_areas();
+        assert!((areas[0].metadata.lng - -123.1).abs() < 1e-9);
+        assert!(areas[0].location.is_some());
+        drop(areas);
+
+        let result = MemoryStorage::with_areas(vec![
+            area(Some("a"), "Fine", 49.7, -123.1),
+            area(Some("b"), "Nowhere", 91.0, 0.0),
+        ]);
+        assert!(matches!(result, Err(StorageError::InvalidArea(_))));
+    }
+
+    #[tokio::test]
+    async fn upsert_weather_replaces_the_same_area_and_date() {
+        let storage = MemoryStorage::new();
+        let crag = area(Some("a"), "Smoke Bluffs", 49.7, -123.1);
+        storage
+            .upsert_weather(&weather(&crag, date(1), 40.0))
+            .await
+            .unwrap();
+        storage
+            .upsert_weather(&weather(&crag, date(1), 80.0))
+            .await
+            .unwrap();
+        storage
+            .upsert_weather(&weather(&crag, date(2), 60.0))
+            .await
+            .unwrap();
+
+        let days = storage
+            .weather_for_area(&crag, date(1), date(2))
+            .await
+            .unwrap();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].score.score, 80.0);
+        assert_eq!(days[1].score.score, 60.0);
+    }
+
+    #[tokio::test]
+    async fn weather_for_area_keeps_to_the_area_and_range() {
+        let storage = MemoryStorage::new();
+        let crag = area(Some("a"), "Smoke Bluffs", 49.7, -123.1);
+        let other = area(Some("b"), "Smoke Bluffs", 49.8, -123.1);
+        for day in 1..=3 {
+            storage
+                .upsert_weather(&weather(&crag, date(day), 50.0))
+                .await
+                .unwrap();
+        }
+        storage
+            .upsert_weather(&weather(&other, date(2), 50.0))
+            .await
+            .unwrap();
+
+        let days = storage
+            .weather_for_area(&crag, date(2), date(3))
+            .await
+            .unwrap();
+        assert_eq!(
+            days.iter().map(|day| day.date).collect::<Vec<_>>(),
+            vec![date(2), date(3)]
+        );
+        assert_eq!(
+            storage
+                .weather_between(date(2), date(2))
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(storage.delete_weather_before(date(2)).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn areas_near_are_nearest_first_within_the_radius() {
+        let storage = MemoryStorage::with_areas(vec![
+            area(Some("far"), "Far", 50.0, -123.0),
+            area(Some("near"), "Near", 49.71, -123.1),
+            area(Some("away"), "Away", 45.0, -120.0),
+        ])
+        .unwrap();
+        let point = GeoPoint::new(49.7, -123.1).unwrap();
+
+        let nearby = storage.areas_near(point, 100.0).await.unwrap();
+        let uuids: Vec<_> = nearby
+            .iter()
+            .map(|nearby| nearby.area.uuid.as_deref().unwrap())
+            .collect();
+        assert_eq!(uuids, vec!["near", "far"]);
+        assert!(nearby[0].distance_km < nearby[1].distance_km);
+    }
+
+    #[tokio::test]
+    async fn nearest_crag_skips_nearer_walls() {
+        let storage = MemoryStorage::with_areas(vec![
+            Area {
+                is_crag: false,
+                ..area(Some("wall"), "Wall", 49.7, -123.1)
+            },
+            area(Some("crag"), "Crag", 49.72, -123.1),
+        ])
+        .unwrap();
+        let point = GeoPoint::new(49.7, -123.1).unwrap();
+
+        let crag = storage.nearest_crag(point, 10.0).await.unwrap().unwrap();
+        assert_eq!(crag.uuid.as_deref(), Some("crag"));
+        assert_eq!(
+            storage
+                .nearest_area(point, 10.0)
+                .await
+                .unwrap()
+                .unwrap()
+                .area_name,
+            "Wall"
+        );
+        assert!(storage.nearest_crag(point, 1.0).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn crags_within_includes_the_region_itself() {
+        let storage = MemoryStorage::with_areas(vec![
+            area(Some("region"), "Squamish", 49.7, -123.1),
+            crag_in("a", "Smoke Bluffs", 49.7, -123.1, "region"),
+            Area {
+                is_crag: false,
+                ..crag_in("b", "Penny Lane", 49.7, -123.1, "region")
+            },
+            crag_in("c", "Elsewhere", 49.7, -123.1, "other"),
+        ])
+        .unwrap();
+
+        let mut uuids: Vec<_> = storage
+            .crags_within("region")
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|area| area.uuid)
+            .collect();
+        uuids.sort();
+        assert_eq!(uuids, vec!["a", "region"]);
+    }
+
+    #[tokio::test]
+    async fn remove_areas_not_seen_since_only_drops_stale_synced_areas() {
+        let storage = MemoryStorage::with_areas(vec![
+            area(Some("fresh"), "Fresh", 49.7, -123.1),
+            area(Some("stale"), "Stale", 49.7, -123.1),
+            area(Some("never-synced"), "Never synced", 49.7, -123.1),
+            area(None, "Imported", 49.7, -123.1),
+        ])
+        .unwrap();
+        let last_run = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+        let this_run = last_run + Duration::days(1);
+        storage
+            .mark_areas_seen(&["stale".to_string()], last_run)
+            .await
+            .unwrap();
+        storage
+            .mark_areas_seen(&["fresh".to_string()], this_run)
+            .await
+            .unwrap();
+
+        let mut removed: Vec<_> = storage
+            .remove_areas_not_seen_since(this_run)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|area| area.area_name)
+            .collect();
+        removed.sort();
+        assert_eq!(removed, vec!["Stale"]);
+        let mut kept: Vec<_> = storage
+            .all_areas()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|area| area.area_name)
+            .collect();
+        kept.sort();
+        assert_eq!(kept, vec!["Fresh", "Imported", "Never synced"]);
+    }
+
+    #[tokio::test]
+    async fn append_observations_only_adds_hours_newer_than_the_archive() {
+        let storage = MemoryStorage::new();
+        let crag = area(Some("a"), "Smoke Bluffs", 49.7, -123.1);
+        let noon = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+        let first = [
+            observed(&crag, ObservationKind::PastHour, noon - Duration::hours(1)),
+            observed(&crag, ObservationKind::PastHour, noon),
+            observed(&crag, ObservationKind::Current, noon),
+        ];
+        assert_eq!(storage.append_observations(&first).await.unwrap(), 3);
+
+        // The next fetch repeats the past hours it already reported
+        let second = [
+            observed(&crag, ObservationKind::PastHour, noon),
+            observed(&crag, ObservationKind::PastHour, noon + Duration::hours(1)),
+            observed(&crag, ObservationKind::Current, noon + Duration::hours(1)),
+        ];
+        assert_eq!(storage.append_observations(&second).await.unwrap(), 2);
+
+        // Another area's series is tracked separately
+        let other = area(Some("b"), "Murrin", 49.6, -123.2);
+        let third = [observed(&other, ObservationKind::PastHour, noon)];
+        assert_eq!(storage.append_observations(&third).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn append_observations_drops_those_past_the_retention() {
+        let storage = MemoryStorage::new().with_archive_retention(30);
+        let crag = area(Some("a"), "Smoke Bluffs", 49.7, -123.1);
+        let now = Utc::now();
+        storage
+            .append_observations(&[
+                observed(&crag, ObservationKind::PastHour, now - Duration::days(31)),
+                observed(&crag, ObservationKind::PastHour, now - Duration::days(1)),
+            ])
+            .await
+            .unwrap();
+
+        let kept = storage
+            .observations_for_area(&crag, now - Duration::days(60), now)
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].observed_at, now - Duration::days(1));
+    }
+
+    #[tokio::test]
+    async fn observations_for_area_keeps_to_the_area_and_range_in_order() {
+        let storage = MemoryStorage::new();
+        let crag = area(Some("a"), "Smoke Bluffs", 49.7, -123.1);
+        let other = area(Some("b"), "Murrin", 49.6, -123.2);
+        let noon = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+        storage
+            .append_observations(&[
+                observed(&crag, ObservationKind::Current, noon + Duration::hours(2)),
+                observed(&crag, ObservationKind::PastHour, noon - Duration::hours(3)),
+                observed(&crag, ObservationKind::PastHour, noon),
+                observed(&other, ObservationKind::PastHour, noon),
+            ])
+            .await
+            .unwrap();
+
+        let found: Vec<_> = storage
+            .observations_for_area(&crag, noon - Duration::hours(1), noon + Duration::hours(2))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|archived| archived.observed_at)
+            .collect();
+        assert_eq!(found, vec![noon, noon + Duration::hours(2)]);
+    }
+}
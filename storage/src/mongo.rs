@@ -0,0 +1,866 @@
+use crate::{
+    archive_key, archive_retention_days, env_u32, located, ApiKeyRepository, ArchiveKey,
+    AreaFilter, AreaRepository, CachedForecast, ClimatologyRepository, ForecastCacheRepository,
+    NearbyArea, ObservationRepository, ProfileRepository, StorageError, SyncCheckpoint,
+    SyncCheckpointRepository, WeatherRepository,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
+use datamodels::{
+    api_key::ApiKey,
+    archive::{ArchivedObservation, ObservationKind},
+    climate::Climatology,
+    geo::GeoPoint,
+    profile::Profile,
+    Area, AreaForecast, AreaWeather, Observation,
+};
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{self, doc, Document},
+    options::{
+        ClientOptions, CreateCollectionOptions, FindOneOptions, FindOptions, IndexOptions,
+        ReplaceOptions, TimeseriesGranularity, TimeseriesOptions, UpdateOptions,
+    },
+    Collection, Database, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Clone, Debug)]
+pub struct MongoConfig {
+    pub connection_string: String,
+    pub database: String,
+    pub areas_collection: String,
+    pub weather_collection: String,
+    pub sync_collection: String,
+    pub archive_collection: String,
+    pub archive_retention_days: u32,
+    pub climatology_collection: String,
+    pub profile_collection: String,
+    pub api_key_collection: String,
+    pub forecast_cache_collection: String,
+    pub max_pool_size: Option<u32>,
+    pub min_pool_size: Option<u32>,
+}
+
+impl MongoConfig {
+    // Reads MONGO and DATABASE (required), AREAS_COLLECTION, WEATHER_COLLECTION,
+    // SYNC_COLLECTION, ARCHIVE_COLLECTION, ARCHIVE_RETENTION_DAYS, CLIMATOLOGY_COLLECTION,
+    // PROFILE_COLLECTION, API_KEY_COLLECTION, FORECAST_CACHE_COLLECTION, MONGO_MAX_POOL_SIZE
+    // and MONGO_MIN_POOL_SIZE
+    pub fn from_env() -> Result<Self, StorageError> {
+        let connection_string = std::env::var("MONGO")
+            .map_err(|_| StorageError::Config("MONGO must be set as an env var".to_string()))?;
+        let database = std::env::var("DATABASE")
+            .map_err(|_| StorageError::Config("DATABASE must be set as an env var".to_string()))?;
+
+        Ok(Self {
+            connection_string,
+            database,
+            areas_collection: std::env::var("AREAS_COLLECTION")
+                .unwrap_or_else(|_| "areas".to_string()),
+            weather_collection: std::env::var("WEATHER_COLLECTION")
+                .unwrap_or_else(|_| "area_weather".to_string()),
+            sync_collection: std::env::var("SYNC_COLLECTION")
+                .unwrap_or_else(|_| "sync_checkpoints".to_string()),
+            archive_collection: std::env::var("ARCHIVE_COLLECTION")
+                .unwrap_or_else(|_| "observations".to_string()),
+            archive_retention_days: archive_retention_days()?,
+            climatology_collection: std::env::var("CLIMATOLOGY_COLLECTION")
+                .unwrap_or_else(|_| "climatology".to_string()),
+            profile_collection: std::env::var("PROFILE_COLLECTION")
+                .unwrap_or_else(|_| "profiles".to_string()),
+            api_key_collection: std::env::var("API_KEY_COLLECTION")
+                .unwrap_or_else(|_| "api_keys".to_string()),
+            forecast_cache_collection: std::env::var("FORECAST_CACHE_COLLECTION")
+                .unwrap_or_else(|_| "forecast_cache".to_string()),
+            max_pool_size: env_u32("MONGO_MAX_POOL_SIZE")?,
+            min_pool_size: env_u32("MONGO_MIN_POOL_SIZE")?,
+        })
+    }
+}
+
+// Area fields that are skipped when serialized as None
+const OPTIONAL_AREA_FIELDS: [&str; 5] =
+    ["uuid", "location", "parent_uuid", "climbing", "aspect_deg"];
+
+fn area_filter_document(filter: &AreaFilter) -> Document {
+    let mut document = Document::new();
+    if let Some(pattern) = &filter.name_pattern {
+        document.insert("area_name", doc! {"$regex": pattern});
+    }
+    if let Some(bbox) = &filter.bbox {
+        document.insert(
+            "metadata.lat",
+            doc! {"$gte": bbox.min_lat, "$lte": bbox.max_lat},
+        );
+        if bbox.crosses_antimeridian() {
+            document.insert(
+                "$or",
+                vec![
+                    doc! {"metadata.lng": {"$gte": bbox.min_lng}},
+                    doc! {"metadata.lng": {"$lte": bbox.max_lng}},
+                ],
+            );
+        } else {
+            document.insert(
+                "metadata.lng",
+                doc! {"$gte": bbox.min_lng, "$lte": bbox.max_lng},
+            );
+        }
+    }
+    document
+}
+
+fn to_bson_datetime(time: DateTime<Utc>) -> bson::DateTime {
+    bson::DateTime::from_millis(time.timestamp_millis())
+}
+
+// Times inside documents written with serde are RFC 3339 strings, updates must match
+fn serde_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn from_bson_datetime(time: bson::DateTime) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(time.timestamp_millis()).unwrap_or_default()
+}
+
+// Time series collections group documents by a meta field and need a BSON date as
+// their time field, so archived observations are stored in this shape
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ArchiveMeta {
+    uuid: Option<String>,
+    area_name: String,
+    lat: f64,
+    lng: f64,
+    provider: String,
+    kind: ObservationKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct ObservationDocument {
+    observed_at: bson::DateTime,
+    area: ArchiveMeta,
+    fetched_at: bson::DateTime,
+    observation: Observation,
+}
+
+// Stored with a BSON date so a TTL index can drop expired entries
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CachedForecastDocument {
+    key: String,
+    days: u8,
+    forecast: AreaForecast,
+    expires_at: bson::DateTime,
+}
+
+impl From<&CachedForecast> for CachedForecastDocument {
+    fn from(cached: &CachedForecast) -> Self {
+        Self {
+            key: cached.key.clone(),
+            days: cached.days,
+            forecast: cached.forecast.clone(),
+            expires_at: to_bson_datetime(cached.expires_at),
+        }
+    }
+}
+
+impl From<CachedForecastDocument> for CachedForecast {
+    fn from(document: CachedForecastDocument) -> Self {
+        Self {
+            key: document.key,
+            days: document.days,
+            forecast: document.forecast,
+            expires_at: from_bson_datetime(document.expires_at),
+        }
+    }
+}
+
+impl From<&ArchivedObservation> for ObservationDocument {
+    fn from(archived: &ArchivedObservation) -> Self {
+        Self {
+            observed_at: to_bson_datetime(archived.observed_at),
+            area: ArchiveMeta {
+                uuid: archived.area_uuid.clone(),
+                area_name: archived.area_name.clone(),
+                lat: archived.lat,
+                lng: archived.lng,
+                provider: archived.provider.clone(),
+                kind: archived.kind,
+            },
+            fetched_at: to_bson_datetime(archived.fetched_at),
+            observation: archived.observation.clone(),
+        }
+    }
+}
+
+impl From<ObservationDocument> for ArchivedObservation {
+    fn from(document: ObservationDocument) -> Self {
+        Self {
+            area_uuid: document.area.uuid,
+            area_name: document.area.area_name,
+            lat: document.area.lat,
+            lng: document.area.lng,
+            provider: document.area.provider,
+            kind: document.area.kind,
+            observed_at: from_bson_datetime(document.observed_at),
+            fetched_at: from_bson_datetime(document.fetched_at),
+            observation: document.observation,
+        }
+    }
+}
+
+fn archive_area_filter(uuid: Option<&str>, area_name: &str, lat: f64, lng: f64) -> Document {
+    match uuid {
+        Some(uuid) => doc! {"area.uuid": uuid},
+        None => doc! {"area.area_name": area_name, "area.lat": lat, "area.lng": lng},
+    }
+}
+
+// Cheap to clone, the underlying client shares one connection pool
+#[derive(Clone)]
+pub struct MongoStorage {
+    db: Database,
+    config: MongoConfig,
+}
+
+impl MongoStorage {
+    pub async fn connect(config: MongoConfig) -> Result<Self, StorageError> {
+        let mut options = ClientOptions::parse(&config.connection_string).await?;
+        options.max_pool_size = config.max_pool_size.or(options.max_pool_size);
+        options.min_pool_size = config.min_pool_size.or(options.min_pool_size);
+        let client = mongodb::Client::with_options(options)?;
+
+        let storage = Self {
+            db: client.database(&config.database),
+            config,
+        };
+        storage.ensure_indexes().await?;
+        Ok(storage)
+    }
+
+    pub async fn from_env() -> Result<Self, StorageError> {
+        Self::connect(MongoConfig::from_env()?).await
+    }
+
+    fn areas(&self) -> Collection<Area> {
+        self.db.collection(&self.config.areas_collection)
+    }
+
+    fn weather(&self) -> Collection<AreaWeather> {
+        self.db.collection(&self.config.weather_collection)
+    }
+
+    fn checkpoints(&self) -> Collection<SyncCheckpoint> {
+        self.db.collection(&self.config.sync_collection)
+    }
+
+    fn climatology(&self) -> Collection<Climatology> {
+        self.db.collection(&self.config.climatology_collection)
+    }
+
+    fn profiles(&self) -> Collection<Profile> {
+        self.db.collection(&self.config.profile_collection)
+    }
+
+    fn api_keys(&self) -> Collection<ApiKey> {
+        self.db.collection(&self.config.api_key_collection)
+    }
+
+    fn forecast_cache(&self) -> Collection<CachedForecastDocument> {
+        self.db.collection(&self.config.forecast_cache_collection)
+    }
+
+    fn archive(&self) -> Collection<ObservationDocument> {
+        self.db.collection(&self.config.archive_collection)
+    }
+
+    // Creates the archive as a time series collection whose documents expire after the
+    // retention period, or updates the retention of an existing one
+    async fn ensure_archive(&self) -> Result<(), StorageError> {
+        let name = &self.config.archive_collection;
+        let retention_secs = self.config.archive_retention_days as u64 * 86_400;
+        let existing = self.db.list_collection_names(doc! {"name": name}).await?;
+        if existing.is_empty() {
+            let timeseries = TimeseriesOptions::builder()
+                .time_field("observed_at".to_string())
+                .meta_field(Some("area".to_string()))
+                .granularity(Some(TimeseriesGranularity::Hours))
+                .build();
+            let options = CreateCollectionOptions::builder()
+                .timeseries(timeseries)
+                .expire_after_seconds(std::time::Duration::from_secs(retention_secs))
+                .build();
+            self.db.create_collection(name, options).await?;
+        } else {
+            self.db
+                .run_command(
+                    doc! {"collMod": name, "expireAfterSeconds": retention_secs as i64},
+                    None,
+                )
+                .await?;
+        }
+        let index = IndexModel::builder()
+            .keys(doc! {"area.uuid": 1, "area.area_name": 1, "observed_at": -1})
+            .build();
+        self.archive().create_index(index, None).await?;
+        Ok(())
+    }
+
+    async fn ensure_indexes(&self) -> Result<(), StorageError> {
+        // One document per area and forecast date
+        let weather_index = IndexModel::builder()
+            .keys(doc! {"area_name": 1, "lat": 1, "lng": 1, "date": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.weather().create_index(weather_index, None).await?;
+
+        let area_index = IndexModel::builder().keys(doc! {"area_name": 1}).build();
+        self.areas().create_index(area_index, None).await?;
+
+        // Synced areas are keyed on their OpenBeta uuid, older documents have none
+        let uuid_index = IndexModel::builder()
+            .keys(doc! {"uuid": 1})
+            .options(
+                IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! {"uuid": {"$type": "string"}})
+                    .build(),
+            )
+            .build();
+        self.areas().create_index(uuid_index, None).await?;
+
+        // Multikey index for finding everything below a region
+        let ancestors_index = IndexModel::builder().keys(doc! {"ancestors": 1}).build();
+        self.areas().create_index(ancestors_index, None).await?;
+
+        // Backfill GeoJSON locations for areas stored before they existed
+        self.areas()
+            .update_many(
+                doc! {"location": {"$exists": false}},
+                vec![doc! {
+                    "$set": {
+                        "location": {
+                            "type": "Point",
+                            "coordinates": ["$metadata.lng", "$metadata.lat"],
+                        },
+                    },
+                }],
+                None,
+            )
+            .await?;
+        let location_index = IndexModel::builder()
+            .keys(doc! {"location": "2dsphere"})
+            .build();
+        self.areas().create_index(location_index, None).await?;
+
+        let profile_index = IndexModel::builder()
+            .keys(doc! {"id": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.profiles().create_index(profile_index, None).await?;
+
+        for field in ["id", "key_hash"] {
+            let key_index = IndexModel::builder()
+                .keys(doc! {field: 1})
+                .options(IndexOptions::builder().unique(true).build())
+                .build();
+            self.api_keys().create_index(key_index, None).await?;
+        }
+
+        let cache_key_index = IndexModel::builder()
+            .keys(doc! {"key": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.forecast_cache()
+            .create_index(cache_key_index, None)
+            .await?;
+        // Mongo drops cached forecasts once expires_at has passed
+        let cache_expiry_index = IndexModel::builder()
+            .keys(doc! {"expires_at": 1})
+            .options(
+                IndexOptions::builder()
+                    .expire_after(std::time::Duration::ZERO)
+                    .build(),
+            )
+            .build();
+        self.forecast_cache()
+            .create_index(cache_expiry_index, None)
+            .await?;
+
+        self.ensure_archive().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AreaRepository for MongoStorage {
+    async fn all_areas(&self) -> Result<Vec<Area>, StorageError> {
+        Ok(self.areas().find(None, None).await?.try_collect().await?)
+    }
+
+    async fn find_areas_by_name(&self, area_name: &str) -> Result<Vec<Area>, StorageError> {
+        Ok(self
+            .areas()
+            .find(doc! {"area_name": area_name}, None)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn areas_near(
+        &self,
+        point: GeoPoint,
+        radius_km: f64,
+    ) -> Result<Vec<NearbyArea>, StorageError> {
+        let pipeline = [
+            doc! {
+                "$geoNear": {
+                    "near": {"type": "Point", "coordinates": [point.lng(), point.lat()]},
+                    "distanceField": "distance_m",
+                    "maxDistance": radius_km * 1000.0,
+                    "spherical": true,
+                },
+            },
+            doc! {
+                "$set": {"distance_km": {"$divide": ["$distance_m", 1000.0]}},
+            },
+        ];
+        let documents: Vec<Document> = self
+            .areas()
+            .aggregate(pipeline, None)
+            .await?
+            .try_collect()
+            .await?;
+        documents
+            .into_iter()
+            .map(|document| {
+                bson::from_document(document)
+                    .map_err(|err| StorageError::Mongo(mongodb::error::Error::from(err)))
+            })
+            .collect()
+    }
+
+    async fn area_by_uuid(&self, uuid: &str) -> Result<Option<Area>, StorageError> {
+        Ok(self.areas().find_one(doc! {"uuid": uuid}, None).await?)
+    }
+
+    async fn crags_within(&self, region_uuid: &str) -> Result<Vec<Area>, StorageError> {
+        let filter = doc! {
+            "is_crag": true,
+            "$or": [{"uuid": region_uuid}, {"ancestors": region_uuid}],
+        };
+        Ok(self.areas().find(filter, None).await?.try_collect().await?)
+    }
+
+    async fn insert_areas(&self, areas: &[Area]) -> Result<usize, StorageError> {
+        if areas.is_empty() {
+            return Ok(0);
+        }
+        let areas = areas
+            .iter()
+            .map(located)
+            .collect::<Result<Vec<Area>, StorageError>>()?;
+        Ok(self
+            .areas()
+            .insert_many(areas, None)
+            .await?
+            .inserted_ids
+            .len())
+    }
+
+    async fn upsert_area(&self, area: &Area) -> Result<(), StorageError> {
+        let area = &located(area)?;
+        let filter = match &area.uuid {
+            Some(uuid) => doc! {"uuid": uuid},
+            None => doc! {
+                "area_name": &area.area_name,
+                "metadata.lat": area.metadata.lat,
+                "metadata.lng": area.metadata.lng,
+            },
+        };
+        let fields = bson::to_document(area)
+            .map_err(|err| StorageError::Mongo(mongodb::error::Error::from(err)))?;
+        // Optional fields are left out when they are None, unset them so a stored value
+        // doesn't outlive the area losing it
+        let unset: Document = OPTIONAL_AREA_FIELDS
+            .iter()
+            .filter(|field| !fields.contains_key(**field))
+            .map(|field| (field.to_string(), bson::Bson::String(String::new())))
+            .collect();
+        // Set the fields rather than replacing so last_seen_at survives
+        let mut update = doc! {"$set": fields};
+        if !unset.is_empty() {
+            update.insert("$unset", unset);
+        }
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.areas().update_one(filter, update, options).await?;
+        Ok(())
+    }
+
+    async fn mark_areas_seen(
+        &self,
+        uuids: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        if uuids.is_empty() {
+            return Ok(());
+        }
+        self.areas()
+            .update_many(
+                doc! {"uuid": {"$in": uuids}},
+                doc! {"$set": {"last_seen_at": bson::DateTime::from_millis(seen_at.timestamp_millis())}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn remove_areas_not_seen_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Area>, StorageError> {
+        // Only synced areas can be judged stale, others were added some other way
+        let filter = doc! {
+            "uuid": {"$type": "string"},
+            "last_seen_at": {"$lt": bson::DateTime::from_millis(since.timestamp_millis())},
+        };
+        let stale: Vec<Area> = self
+            .areas()
+            .find(filter.clone(), None)
+            .await?
+            .try_collect()
+            .await?;
+        self.areas().delete_many(filter, None).await?;
+        Ok(stale)
+    }
+
+    async fn delete_all_areas(&self) -> Result<u64, StorageError> {
+        Ok(self.areas().delete_many(doc! {}, None).await?.deleted_count)
+    }
+
+    async fn find_areas(&self, filter: &AreaFilter) -> Result<Vec<Area>, StorageError> {
+        Ok(self
+            .areas()
+            .find(area_filter_document(filter), None)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn delete_areas(&self, filter: &AreaFilter) -> Result<u64, StorageError> {
+        Ok(self
+            .areas()
+            .delete_many(area_filter_document(filter), None)
+            .await?
+            .deleted_count)
+    }
+}
+
+#[async_trait]
+impl SyncCheckpointRepository for MongoStorage {
+    async fn load_checkpoint(&self, name: &str) -> Result<Option<SyncCheckpoint>, StorageError> {
+        Ok(self
+            .checkpoints()
+            .find_one(doc! {"name": name}, None)
+            .await?)
+    }
+
+    async fn save_checkpoint(&self, checkpoint: &SyncCheckpoint) -> Result<(), StorageError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.checkpoints()
+            .replace_one(doc! {"name": &checkpoint.name}, checkpoint, options)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ObservationRepository for MongoStorage {
+    async fn append_observations(
+        &self,
+        observations: &[ArchivedObservation],
+    ) -> Result<usize, StorageError> {
+        // Latest archived time per area, provider and kind, looked up once each
+        let mut latest: HashMap<ArchiveKey, Option<DateTime<Utc>>> = HashMap::new();
+        let mut fresh = Vec::new();
+        for archived in observations {
+            let mut filter = archive_area_filter(
+                archived.area_uuid.as_deref(),
+                &archived.area_name,
+                archived.lat,
+                archived.lng,
+            );
+            filter.insert("area.provider", &archived.provider);
+            filter.insert(
+                "area.kind",
+                bson::to_bson(&archived.kind)
+                    .map_err(|err| StorageError::Mongo(mongodb::error::Error::from(err)))?,
+            );
+            let key = archive_key(archived);
+            let newest = match latest.get(&key) {
+                Some(newest) => *newest,
+                None => {
+                    let options = FindOneOptions::builder()
+                        .sort(doc! {"observed_at": -1})
+                        .build();
+                    let newest = self
+                        .archive()
+                        .find_one(filter.clone(), options)
+                        .await?
+                        .map(|document| from_bson_datetime(document.observed_at));
+                    latest.insert(key, newest);
+                    newest
+                }
+            };
+            if newest.is_none_or(|newest| archived.observed_at > newest) {
+                fresh.push(ObservationDocument::from(archived));
+            }
+        }
+        if fresh.is_empty() {
+            return Ok(0);
+        }
+        Ok(self
+            .archive()
+            .insert_many(fresh, None)
+            .await?
+            .inserted_ids
+            .len())
+    }
+
+    async fn observations_for_area(
+        &self,
+        area: &Area,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<ArchivedObservation>, StorageError> {
+        let mut filter = archive_area_filter(
+            area.uuid.as_deref(),
+            &area.area_name,
+            area.metadata.lat,
+            area.metadata.lng,
+        );
+        filter.insert(
+            "observed_at",
+            doc! {"$gte": to_bson_datetime(from), "$lte": to_bson_datetime(to)},
+        );
+        let options = FindOptions::builder().sort(doc! {"observed_at": 1}).build();
+        let documents: Vec<ObservationDocument> = self
+            .archive()
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(documents
+            .into_iter()
+            .map(ArchivedObservation::from)
+            .collect())
+    }
+}
+
+fn climatology_filter(uuid: Option<&str>, area_name: &str, lat: f64, lng: f64) -> Document {
+    match uuid {
+        Some(uuid) => doc! {"area_uuid": uuid},
+        None => doc! {"area_name": area_name, "lat": lat, "lng": lng},
+    }
+}
+
+#[async_trait]
+impl ClimatologyRepository for MongoStorage {
+    async fn climatology_for_area(&self, area: &Area) -> Result<Option<Climatology>, StorageError> {
+        let filter = climatology_filter(
+            area.uuid.as_deref(),
+            &area.area_name,
+            area.metadata.lat,
+            area.metadata.lng,
+        );
+        Ok(self.climatology().find_one(filter, None).await?)
+    }
+
+    async fn save_climatology(&self, climatology: &Climatology) -> Result<(), StorageError> {
+        let filter = climatology_filter(
+            climatology.area_uuid.as_deref(),
+            &climatology.area_name,
+            climatology.lat,
+            climatology.lng,
+        );
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.climatology()
+            .replace_one(filter, climatology, options)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProfileRepository for MongoStorage {
+    async fn profile_by_id(&self, id: &str) -> Result<Option<Profile>, StorageError> {
+        Ok(self.profiles().find_one(doc! {"id": id}, None).await?)
+    }
+
+    async fn save_profile(&self, profile: &Profile) -> Result<(), StorageError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.profiles()
+            .replace_one(doc! {"id": &profile.id}, profile, options)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ApiKeyRepository for MongoStorage {
+    async fn api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>, StorageError> {
+        Ok(self
+            .api_keys()
+            .find_one(doc! {"key_hash": key_hash}, None)
+            .await?)
+    }
+
+    async fn all_api_keys(&self) -> Result<Vec<ApiKey>, StorageError> {
+        let options = FindOptions::builder().sort(doc! {"created_at": 1}).build();
+        Ok(self
+            .api_keys()
+            .find(None, options)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn insert_api_key(&self, key: &ApiKey) -> Result<(), StorageError> {
+        self.api_keys().insert_one(key, None).await?;
+        Ok(())
+    }
+
+    async fn revoke_api_key(
+        &self,
+        id: &str,
+        revoked_at: DateTime<Utc>,
+    ) -> Result<Option<ApiKey>, StorageError> {
+        // Keep the first revocation time if the key was already revoked
+        self.api_keys()
+            .update_one(
+                doc! {"id": id, "revoked_at": {"$exists": false}},
+                doc! {"$set": {"revoked_at": serde_time(revoked_at)}},
+                None,
+            )
+            .await?;
+        Ok(self.api_keys().find_one(doc! {"id": id}, None).await?)
+    }
+
+    async fn record_usage(
+        &self,
+        id: &str,
+        requests: u64,
+        rate_limited: u64,
+        used_at: DateTime<Utc>,
+    ) -> Result<(), StorageError> {
+        self.api_keys()
+            .update_one(
+                doc! {"id": id},
+                doc! {
+                    "$inc": {
+                        "usage.requests": requests as i64,
+                        "usage.rate_limited": rate_limited as i64,
+                    },
+                    "$set": {"usage.last_used_at": serde_time(used_at)},
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ForecastCacheRepository for MongoStorage {
+    async fn cached_forecast(&self, key: &str) -> Result<Option<CachedForecast>, StorageError> {
+        // The TTL monitor only runs once a minute, so check the expiry here too
+        let filter = doc! {"key": key, "expires_at": {"$gt": to_bson_datetime(Utc::now())}};
+        Ok(self
+            .forecast_cache()
+            .find_one(filter, None)
+            .await?
+            .map(CachedForecast::from))
+    }
+
+    async fn save_cached_forecast(&self, cached: &CachedForecast) -> Result<(), StorageError> {
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.forecast_cache()
+            .replace_one(
+                doc! {"key": &cached.key},
+                CachedForecastDocument::from(cached),
+                options,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WeatherRepository for MongoStorage {
+    async fn upsert_weather(&self, weather: &AreaWeather) -> Result<(), StorageError> {
+        let filter = doc! {
+            "area_name": &weather.area_name,
+            "lat": weather.lat,
+            "lng": weather.lng,
+            "date": weather.date.format(DATE_FORMAT).to_string(),
+        };
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.weather().replace_one(filter, weather, options).await?;
+        Ok(())
+    }
+
+    async fn weather_for_area(
+        &self,
+        area: &Area,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AreaWeather>, StorageError> {
+        // Dates are stored as zero padded strings so they compare chronologically
+        let filter = doc! {
+            "area_name": &area.area_name,
+            "lat": area.metadata.lat,
+            "lng": area.metadata.lng,
+            "date": {
+                "$gte": from.format(DATE_FORMAT).to_string(),
+                "$lte": to.format(DATE_FORMAT).to_string(),
+            },
+        };
+        Ok(self
+            .weather()
+            .find(filter, None)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn weather_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AreaWeather>, StorageError> {
+        let filter = doc! {
+            "date": {
+                "$gte": from.format(DATE_FORMAT).to_string(),
+                "$lte": to.format(DATE_FORMAT).to_string(),
+            },
+        };
+        Ok(self
+            .weather()
+            .find(filter, None)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    async fn delete_weather_before(&self, date: NaiveDate) -> Result<u64, StorageError> {
+        let filter = doc! {"date": {"$lt": date.format(DATE_FORMAT).to_string()}};
+        Ok(self
+            .weather()
+            .delete_many(filter, None)
+            .await?
+            .deleted_count)
+    }
+}
@@ -0,0 +1,153 @@
+use clap::Parser;
+use datamodels::geo::Latitude;
+use dotenv::dotenv;
+use std::io::{self, BufRead, Write};
+use storage::{AreaFilter, BoundingBox, Repositories};
+
+/// Remove areas from the storage STORAGE selects
+#[derive(Parser, Debug)]
+struct Args {
+    /// Remove every area
+    #[arg(long, conflicts_with_all = ["name", "bbox"])]
+    all: bool,
+
+    /// Only remove areas whose name matches this regular expression
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Only remove areas inside min_lat,min_lng,max_lat,max_lng
+    #[arg(long, value_parser = parse_bbox, allow_hyphen_values = true)]
+    bbox: Option<BoundingBox>,
+
+    /// Report what would be removed without deleting anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Skip the confirmation prompt
+    #[arg(long)]
+    yes: bool,
+}
+
+fn parse_bbox(val: &str) -> Result<BoundingBox, String> {
+    let parts = val
+        .split(',')
+        .map(|part| part.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|err| format!("bbox values must be numbers: {}", err))?;
+    let [min_lat, min_lng, max_lat, max_lng] = parts[..] else {
+        return Err("bbox must be min_lat,min_lng,max_lat,max_lng".to_string());
+    };
+    for lat in [min_lat, max_lat] {
+        Latitude::new(lat).map_err(|err| format!("bbox {}", err))?;
+    }
+    // Longitudes are not wrapped here, a box across the antimeridian can't be expressed
+    for lng in [min_lng, max_lng] {
+        if !(-180.0..=180.0).contains(&lng) {
+            return Err(format!(
+                "bbox lng must be between -180 and 180, got {}",
+                lng
+            ));
+        }
+    }
+    if min_lat > max_lat {
+        return Err("bbox min_lat must not be greater than max_lat".to_string());
+    }
+    if min_lng > max_lng {
+        return Err("bbox min_lng must not be greater than max_lng".to_string());
+    }
+    Ok(BoundingBox {
+        min_lat,
+        min_lng,
+        max_lat,
+        max_lng,
+    })
+}
+
+fn confirm(count: usize) -> io::Result<bool> {
+    print!("Remove {} areas? [y/N] ", count);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Load env variables
+    dotenv().ok();
+
+    let args = Args::parse();
+    if !args.all && args.name.is_none() && args.bbox.is_none() {
+        return Err("Pass --all, --name or --bbox to choose which areas to remove".into());
+    }
+
+    let filter = AreaFilter {
+        name_pattern: args.name,
+        bbox: args.bbox,
+    };
+
+    let storage = Repositories::from_env().await?.areas;
+    let matching = storage.find_areas(&filter).await?;
+
+    if args.dry_run {
+        for area in &matching {
+            println!(
+                "{} | {} | {}",
+                area.area_name, area.metadata.lat, area.metadata.lng
+            );
+        }
+        println!("Dry run: {} areas would be removed", matching.len());
+        return Ok(());
+    }
+
+    if matching.is_empty() {
+        println!("No areas match");
+        return Ok(());
+    }
+
+    if !args.yes && !confirm(matching.len())? {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let deleted = storage.delete_areas(&filter).await?;
+    println!("Removed {} areas", deleted);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bboxes_are_read_in_lat_lng_order() {
+        let bbox = parse_bbox("49.6, -123.3, 49.8, -123.0").unwrap();
+        assert_eq!(bbox.min_lat, 49.6);
+        assert_eq!(bbox.min_lng, -123.3);
+        assert_eq!(bbox.max_lat, 49.8);
+        assert_eq!(bbox.max_lng, -123.0);
+    }
+
+    #[test]
+    fn bboxes_need_four_numbers() {
+        assert!(parse_bbox("49.6,-123.3,49.8").is_err());
+        assert!(parse_bbox("49.6,-123.3,49.8,-123.0,1").is_err());
+        assert!(parse_bbox("49.6,west,49.8,-123.0").is_err());
+    }
+
+    #[test]
+    fn bboxes_must_be_on_the_map() {
+        // Longitude first by mistake
+        assert!(parse_bbox("-123.3,49.6,-123.0,49.8").is_err());
+        assert!(parse_bbox("49.6,-190.0,49.8,-123.0").is_err());
+        assert!(parse_bbox("NaN,-123.3,49.8,-123.0").is_err());
+        assert!(parse_bbox("-90,-180,90,180").is_ok());
+    }
+
+    #[test]
+    fn bboxes_must_run_from_min_to_max() {
+        assert!(parse_bbox("49.8,-123.3,49.6,-123.0").is_err());
+        assert!(parse_bbox("49.6,-123.0,49.8,-123.3").is_err());
+        assert!(parse_bbox("49.6,-123.3,49.6,-123.3").is_ok());
+    }
+}
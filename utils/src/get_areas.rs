@@ -1,24 +1,390 @@
-use datamodels::Area;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use datamodels::{
+    climbing::{ClimbingInfo, Discipline, GradeBands, RockType},
+    geo::CoordError,
+    solar, Area, Metadata,
+};
 use dotenv::dotenv;
 use gql_client::{Client, ClientConfig};
-use mongodb::options::ClientOptions;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use storage::{AreaRepository, Repositories, StorageError, SyncCheckpoint, SyncStats};
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct Area {
-    pub area_name: String,
-    pub metadata: Metadata,
+const CHECKPOINT_NAME: &str = "openbeta_areas";
+// Most coordinates the Open-Meteo elevation api takes in one request
+const ELEVATION_BATCH: usize = 100;
+
+/// Sync areas from OpenBeta, only writing what changed since the last run
+#[derive(Parser, Debug)]
+struct Args {
+    /// Areas fetched per request
+    #[arg(long, default_value_t = 50)]
+    page_size: usize,
+
+    /// Start a fresh run instead of resuming an interrupted one
+    #[arg(long)]
+    restart: bool,
+
+    /// Look up missing elevations from the Open-Meteo elevation api
+    #[arg(long)]
+    elevation: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct AreaRef {
+    uuid: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LeafFlag {
+    leaf: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChildRef {
+    uuid: String,
+    metadata: LeafFlag,
+}
+
+#[derive(Deserialize, Debug)]
+struct DisciplineCount {
+    total: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct ByDiscipline {
+    sport: Option<DisciplineCount>,
+    trad: Option<DisciplineCount>,
+    boulder: Option<DisciplineCount>,
+    tr: Option<DisciplineCount>,
+    aid: Option<DisciplineCount>,
+    alpine: Option<DisciplineCount>,
+    mixed: Option<DisciplineCount>,
+    ice: Option<DisciplineCount>,
+    deepwatersolo: Option<DisciplineCount>,
+}
+
+impl ByDiscipline {
+    fn counts(&self) -> BTreeMap<Discipline, u32> {
+        [
+            (Discipline::Sport, &self.sport),
+            (Discipline::Trad, &self.trad),
+            (Discipline::Boulder, &self.boulder),
+            (Discipline::TopRope, &self.tr),
+            (Discipline::Aid, &self.aid),
+            (Discipline::Alpine, &self.alpine),
+            (Discipline::Mixed, &self.mixed),
+            (Discipline::Ice, &self.ice),
+            (Discipline::DeepWaterSolo, &self.deepwatersolo),
+        ]
+        .into_iter()
+        .filter_map(|(discipline, count)| {
+            count
+                .as_ref()
+                .filter(|count| count.total > 0)
+                .map(|count| (discipline, count.total))
+        })
+        .collect()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Aggregate {
+    by_discipline: ByDiscipline,
+    by_grade_band: GradeBands,
+}
+
+#[derive(Deserialize, Debug)]
+struct Content {
+    description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenBetaMetadata {
+    lat: f64,
+    lng: f64,
+    leaf: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Countries {
+    countries: Vec<AreaRef>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct OpenBetaArea {
+    uuid: String,
+    // Asked for by its snake case name, unlike the other fields
+    #[serde(rename = "area_name")]
+    area_name: String,
+    metadata: OpenBetaMetadata,
+    // Ids from the country down to and including this area
+    ancestors: Vec<String>,
+    path_tokens: Vec<String>,
+    children: Vec<ChildRef>,
+    total_climbs: u32,
+    aggregate: Option<Aggregate>,
+    content: Option<Content>,
+}
+
+impl OpenBetaArea {
+    // A parent of only leaves is a crag and its leaves are walls within it. A leaf
+    // whose parent is not a crag stands on its own, so is its own crag.
+    fn is_crag(&self, parent_is_crag: bool) -> bool {
+        if self.metadata.leaf {
+            !parent_is_crag
+        } else {
+            !self.children.is_empty() && self.children.iter().all(|child| child.metadata.leaf)
+        }
+    }
+
+    // Fails when OpenBeta has coordinates that are off the map
+    fn to_area(&self, parent_is_crag: bool) -> Result<Area, CoordError> {
+        let mut ancestors = self.ancestors.clone();
+        if ancestors.last() == Some(&self.uuid) {
+            ancestors.pop();
+        }
+        Area {
+            uuid: Some(self.uuid.clone()),
+            area_name: self.area_name.clone(),
+            metadata: Metadata {
+                lat: self.metadata.lat,
+                lng: self.metadata.lng,
+            },
+            location: None,
+            parent_uuid: ancestors.last().cloned(),
+            ancestors,
+            path_tokens: self.path_tokens.clone(),
+            is_leaf: self.metadata.leaf,
+            is_crag: self.is_crag(parent_is_crag),
+            climbing: Some(self.climbing_info()),
+            aspect_deg: self
+                .content
+                .as_ref()
+                .and_then(|content| content.description.as_deref())
+                .and_then(solar::aspect_from_description),
+        }
+        .located()
+    }
+
+    fn climbing_info(&self) -> ClimbingInfo {
+        let description = self
+            .content
+            .as_ref()
+            .and_then(|content| content.description.as_deref())
+            .map(str::trim)
+            .filter(|description| !description.is_empty());
+        ClimbingInfo {
+            total_climbs: self.total_climbs,
+            by_discipline: self
+                .aggregate
+                .as_ref()
+                .map(|aggregate| aggregate.by_discipline.counts())
+                .unwrap_or_default(),
+            grade_bands: self
+                .aggregate
+                .as_ref()
+                .map(|aggregate| aggregate.by_grade_band.clone())
+                .unwrap_or_default(),
+            rock_type: description.and_then(RockType::from_description),
+            description: description.map(str::to_string),
+            elevation_m: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ElevationResponse {
+    elevation: Vec<f64>,
+}
+
+struct ElevationClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl ElevationClient {
+    // Uses OPEN_METEO_URL, the same setting as the open-meteo weather provider
+    fn from_env() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: std::env::var("OPEN_METEO_URL")
+                .unwrap_or_else(|_| "https://api.open-meteo.com/v1".to_string()),
+        }
+    }
+
+    // Fill in the elevation of areas that do not have one yet
+    async fn fill(&self, areas: &mut [Area]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut missing: Vec<&mut Area> = areas
+            .iter_mut()
+            .filter(|area| area.elevation_m().is_none())
+            .collect();
+        for batch in missing.chunks_mut(ELEVATION_BATCH) {
+            let join = |coord: fn(&Area) -> f64| {
+                batch
+                    .iter()
+                    .map(|area| coord(area).to_string())
+                    .collect::<Vec<String>>()
+                    .join(",")
+            };
+            let latitude = join(|area| area.metadata.lat);
+            let longitude = join(|area| area.metadata.lng);
+            let response: ElevationResponse = self
+                .client
+                .get(format!("{}/elevation", self.base_url))
+                .query(&[("latitude", latitude), ("longitude", longitude)])
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            for (area, elevation_m) in batch.iter_mut().zip(response.elevation) {
+                if let Some(climbing) = area.climbing.as_mut() {
+                    climbing.elevation_m = Some(elevation_m);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn query<T: for<'de> Deserialize<'de>>(
+    client: &Client,
+    query: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    match client.query::<T>(query).await {
+        Ok(Some(val)) => Ok(val),
+        Ok(None) => Err("OpenBeta returned no data".into()),
+        Err(err) => Err(err.to_string().into()),
+    }
+}
+
+const DISCIPLINE_FIELDS: &str = "sport { total } trad { total } boulder { total } \
+    tr { total } aid { total } alpine { total } mixed { total } ice { total } \
+    deepwatersolo { total }";
+
+// One request for a page of areas, each aliased so they come back keyed by position
+fn page_query(uuids: &[String]) -> String {
+    let fields = uuids
+        .iter()
+        .enumerate()
+        .map(|(i, uuid)| {
+            format!(
+                "a{}: area(uuid: {}) {{ uuid area_name ancestors pathTokens totalClimbs \
+                 metadata {{ lat lng leaf }} children {{ uuid metadata {{ leaf }} }} \
+                 content {{ description }} \
+                 aggregate {{ byDiscipline {{ {} }} \
+                 byGradeBand {{ unknown beginner intermediate advanced expert }} }} }}",
+                i,
+                serde_json::to_string(uuid).expect("strings always serialize"),
+                DISCIPLINE_FIELDS,
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    format!("query get_areas {{\n{}\n}}", fields)
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct Metadata {
-    pub lat: f64,
-    pub lng: f64,
+// An unfinished run is picked up again unless a fresh one is asked for
+fn should_resume(checkpoint: &SyncCheckpoint, restart: bool) -> bool {
+    checkpoint.completed_at.is_none() && !restart
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct Data {
+// A run walking the tree again from the countries
+fn new_run(
+    countries: Vec<String>,
+    previous: Option<SyncCheckpoint>,
+    started_at: DateTime<Utc>,
+) -> SyncCheckpoint {
+    SyncCheckpoint {
+        name: CHECKPOINT_NAME.to_string(),
+        run_started_at: started_at,
+        pending: countries,
+        completed_at: None,
+        last_completed_at: previous.and_then(|p| p.last_completed_at),
+        stats: SyncStats::default(),
+    }
+}
+
+// Turn a fetched page into the areas to store and the uuids that were seen, queueing
+// the children of every area for a later page
+fn read_page(
+    page: &[String],
+    results: &HashMap<String, Option<OpenBetaArea>>,
+    stored: &HashMap<String, Area>,
+    stats: &mut SyncStats,
+    pending: &mut VecDeque<String>,
+) -> (Vec<String>, Vec<Area>) {
+    let mut seen = Vec::new();
+    let mut areas = Vec::new();
+    for (i, uuid) in page.iter().enumerate() {
+        let Some(fetched) = results.get(&format!("a{}", i)).and_then(Option::as_ref) else {
+            println!("Area {} disappeared during the sync, skipping", uuid);
+            continue;
+        };
+        stats.fetched += 1;
+        pending.extend(fetched.children.iter().map(|child| child.uuid.clone()));
+
+        // Parents are always fetched in an earlier page than their children
+        let parent_is_crag = fetched
+            .ancestors
+            .iter()
+            .rev()
+            .find(|uuid| **uuid != fetched.uuid)
+            .and_then(|parent| stored.get(parent))
+            .is_some_and(|parent| parent.is_crag);
+        // Left unseen, so a copy stored before it went bad is removed at the end
+        let mut area = match fetched.to_area(parent_is_crag) {
+            Ok(area) => area,
+            Err(err) => {
+                println!("Skipping {}: {}", fetched.path_tokens.join(" / "), err);
+                continue;
+            }
+        };
+        seen.push(fetched.uuid.clone());
+        // Keep the elevation looked up earlier while the area has not moved
+        if let (Some(existing), Some(climbing)) =
+            (stored.get(&fetched.uuid), area.climbing.as_mut())
+        {
+            if existing.metadata == area.metadata {
+                climbing.elevation_m = existing.elevation_m();
+            }
+        }
+        areas.push(area);
+    }
+    (seen, areas)
+}
+
+// Write the areas that changed since they were stored, counting what happened to each
+async fn store_areas(
+    repo: &dyn AreaRepository,
+    stored: &mut HashMap<String, Area>,
+    stats: &mut SyncStats,
     areas: Vec<Area>,
+) -> Result<(), StorageError> {
+    for area in areas {
+        let uuid = area.uuid.clone().expect("synced areas have a uuid");
+        match stored.get(&uuid) {
+            Some(existing) if *existing == area => {
+                stats.unchanged += 1;
+                continue;
+            }
+            Some(existing) => {
+                if existing.area_name != area.area_name {
+                    println!("Renamed {} to {}", existing.path(), area.path());
+                    stats.renamed += 1;
+                }
+                stats.updated += 1;
+            }
+            None => stats.inserted += 1,
+        }
+        repo.upsert_area(&area).await?;
+        stored.insert(uuid, area);
+    }
+    Ok(())
 }
 
 #[tokio::main]
@@ -26,17 +392,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load env variables
     dotenv().ok();
 
-    // Get areas from openbeta
-    let query = r#"query get_areas {
-        areas {
-          area_name
-            metadata {
-              lat
-              lng
-            }
-          }
-        
-      }"#;
+    let args = Args::parse();
+    if args.page_size == 0 {
+        return Err("--page-size must be at least 1".into());
+    }
 
     let client_config = ClientConfig {
         endpoint: "https://api.openbeta.io".to_string(),
@@ -45,32 +404,343 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         proxy: None,
     };
     let gql_client = Client::new_with_config(client_config);
+    let elevation = args.elevation.then(ElevationClient::from_env);
+    let repos = Repositories::from_env().await?;
 
-    let response = match gql_client.query::<Data>(query).await {
-        Ok(option) => match option {
-            Some(val) => Ok(val),
-            None => Err("Error1".to_string()),
-        },
-        Err(val) => Err(val.to_string()),
-    }?;
-
-    // Connect to mongodb so we can write data to it
-    let connection_string = std::env::var("MONGO").expect("MONGO must be set as an env var.");
-    let database: String = std::env::var("DATABASE").expect("test");
-    let mongo_client_options = ClientOptions::parse(connection_string).await?;
-    // Get handle
-    let mongo_client = mongodb::Client::with_options(mongo_client_options)?;
-
-    let db = mongo_client.database(&database);
-    let collection = db.collection::<Area>("areas");
-    collection.insert_many(&response.areas, None).await?;
-
-    // for area in &response.areas {
-    //     println!(
-    //         "{} | {} | {}",
-    //         area.area_name, area.metadata.lat, area.metadata.lng
-    //     );
-    // }
+    // Resume an interrupted run, otherwise walk the area tree again from the countries
+    let previous = repos.checkpoints.load_checkpoint(CHECKPOINT_NAME).await?;
+    let mut checkpoint = match previous {
+        Some(checkpoint) if should_resume(&checkpoint, args.restart) => {
+            println!(
+                "Resuming sync started at {}, {} areas left in the queue",
+                checkpoint.run_started_at,
+                checkpoint.pending.len()
+            );
+            checkpoint
+        }
+        previous => {
+            let countries: Countries =
+                query(&gql_client, "query get_countries { countries { uuid } }").await?;
+            new_run(
+                countries.countries.into_iter().map(|c| c.uuid).collect(),
+                previous,
+                Utc::now(),
+            )
+        }
+    };
+
+    let mut stored: HashMap<String, Area> = repos
+        .areas
+        .all_areas()
+        .await?
+        .into_iter()
+        .filter_map(|area| Some((area.uuid.clone()?, area)))
+        .collect();
+
+    let mut pending: VecDeque<String> = checkpoint.pending.drain(..).collect();
+    while !pending.is_empty() {
+        let page: Vec<String> = pending.drain(..args.page_size.min(pending.len())).collect();
+        let results: HashMap<String, Option<OpenBetaArea>> =
+            query(&gql_client, &page_query(&page)).await?;
+
+        let (seen, mut areas) = read_page(
+            &page,
+            &results,
+            &stored,
+            &mut checkpoint.stats,
+            &mut pending,
+        );
+        if let Some(elevation) = &elevation {
+            elevation.fill(&mut areas).await?;
+        }
+
+        store_areas(
+            repos.areas.as_ref(),
+            &mut stored,
+            &mut checkpoint.stats,
+            areas,
+        )
+        .await?;
+        repos
+            .areas
+            .mark_areas_seen(&seen, checkpoint.run_started_at)
+            .await?;
 
+        // Save progress after every page so a failed run picks up from here
+        checkpoint.pending = pending.iter().cloned().collect();
+        repos.checkpoints.save_checkpoint(&checkpoint).await?;
+        println!(
+            "Synced {} areas, {} queued",
+            checkpoint.stats.fetched,
+            pending.len()
+        );
+    }
+
+    // Never treat an empty walk as every area having been deleted upstream
+    if checkpoint.stats.fetched == 0 {
+        return Err("OpenBeta returned no areas, not removing anything".into());
+    }
+
+    let removed = repos
+        .areas
+        .remove_areas_not_seen_since(checkpoint.run_started_at)
+        .await?;
+    for area in &removed {
+        println!("Removed {}", area.path());
+    }
+    checkpoint.stats.deleted = removed.len() as u64;
+
+    let completed_at = Utc::now();
+    checkpoint.completed_at = Some(completed_at);
+    checkpoint.last_completed_at = Some(completed_at);
+    repos.checkpoints.save_checkpoint(&checkpoint).await?;
+
+    let stats = &checkpoint.stats;
+    println!(
+        "Done: {} fetched, {} inserted, {} updated ({} renamed), {} unchanged, {} removed",
+        stats.fetched, stats.inserted, stats.updated, stats.renamed, stats.unchanged, stats.deleted
+    );
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+    use storage::MemoryStorage;
+
+    // Shaped like an aliased area in the page query's response
+    fn fetched(
+        uuid: &str,
+        ancestors: &[&str],
+        leaf: bool,
+        children: &[(&str, bool)],
+    ) -> OpenBetaArea {
+        serde_json::from_value(json!({
+            "uuid": uuid,
+            "area_name": uuid.to_uppercase(),
+            "ancestors": ancestors.iter().chain([&uuid]).collect::<Vec<_>>(),
+            "pathTokens": ancestors.iter().chain([&uuid]).map(|a| a.to_uppercase()).collect::<Vec<_>>(),
+            "totalClimbs": 12,
+            "metadata": {"lat": 49.7, "lng": -123.1, "leaf": leaf},
+            "children": children
+                .iter()
+                .map(|(uuid, leaf)| json!({"uuid": uuid, "metadata": {"leaf": leaf}}))
+                .collect::<Vec<_>>(),
+            "content": {"description": "South-facing granite slabs"},
+            "aggregate": {
+                "byDiscipline": {"sport": {"total": 8}, "trad": {"total": 4}},
+                "byGradeBand": {"unknown": 0, "beginner": 4, "intermediate": 6, "advanced": 2, "expert": 0}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn page(areas: Vec<OpenBetaArea>) -> (Vec<String>, HashMap<String, Option<OpenBetaArea>>) {
+        let uuids = areas.iter().map(|area| area.uuid.clone()).collect();
+        let results = areas
+            .into_iter()
+            .enumerate()
+            .map(|(i, area)| (format!("a{}", i), Some(area)))
+            .collect();
+        (uuids, results)
+    }
+
+    fn checkpoint(completed: bool) -> SyncCheckpoint {
+        let started = Utc.with_ymd_and_hms(2023, 6, 1, 0, 0, 0).unwrap();
+        SyncCheckpoint {
+            name: CHECKPOINT_NAME.to_string(),
+            run_started_at: started,
+            pending: vec!["left".to_string()],
+            completed_at: completed.then_some(started + Duration::hours(1)),
+            last_completed_at: Some(started - Duration::days(1)),
+            stats: SyncStats::default(),
+        }
+    }
+
+    #[test]
+    fn openbeta_disciplines_map_to_ours() {
+        let by_discipline: ByDiscipline = serde_json::from_value(json!({
+            "sport": {"total": 8},
+            "tr": {"total": 3},
+            "deepwatersolo": {"total": 2},
+            "boulder": {"total": 0},
+            "ice": null
+        }))
+        .unwrap();
+        assert_eq!(
+            by_discipline.counts(),
+            BTreeMap::from([
+                (Discipline::Sport, 8),
+                (Discipline::TopRope, 3),
+                (Discipline::DeepWaterSolo, 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn areas_without_aggregates_have_no_counts() {
+        let mut area = fetched("crag", &["region"], false, &[("wall", true)]);
+        area.aggregate = None;
+        area.content = None;
+        let climbing = area.climbing_info();
+        assert!(climbing.by_discipline.is_empty());
+        assert_eq!(climbing.grade_bands, GradeBands::default());
+        assert_eq!(climbing.rock_type, None);
+        assert_eq!(climbing.description, None);
+    }
+
+    #[test]
+    fn a_parent_of_only_leaves_is_a_crag() {
+        let crag = fetched(
+            "crag",
+            &["region"],
+            false,
+            &[("wall", true), ("slab", true)],
+        );
+        assert!(crag.is_crag(false));
+        let region = fetched("region", &[], false, &[("crag", false), ("boulder", true)]);
+        assert!(!region.is_crag(false));
+        let empty = fetched("empty", &["region"], false, &[]);
+        assert!(!empty.is_crag(false));
+    }
+
+    #[test]
+    fn a_leaf_is_only_a_crag_outside_one() {
+        let leaf = fetched("wall", &["region", "crag"], true, &[]);
+        assert!(!leaf.is_crag(true));
+        assert!(leaf.is_crag(false));
+    }
+
+    #[test]
+    fn areas_are_read_from_the_page_response() {
+        let area = fetched("crag", &["country", "region"], false, &[("wall", true)])
+            .to_area(false)
+            .unwrap();
+        assert_eq!(area.area_name, "CRAG");
+        assert_eq!(area.parent_uuid.as_deref(), Some("region"));
+        assert_eq!(area.ancestors, vec!["country", "region"]);
+        assert_eq!(area.path(), "COUNTRY / REGION / CRAG");
+        assert!(area.is_crag);
+        let climbing = area.climbing.unwrap();
+        assert_eq!(climbing.total_climbs, 12);
+        assert_eq!(climbing.by_discipline.get(&Discipline::Sport), Some(&8));
+        assert_eq!(climbing.grade_bands.intermediate, 6);
+        assert_eq!(area.aspect_deg, Some(180.0));
+    }
+
+    #[test]
+    fn pages_queue_children_and_skip_what_cannot_be_stored() {
+        let stored: HashMap<String, Area> =
+            [fetched("crag", &["region"], false, &[("wall", true)])]
+                .into_iter()
+                .map(|area| (area.uuid.clone(), area.to_area(false).unwrap()))
+                .collect();
+        let mut off_the_map = fetched("lost", &["region"], true, &[]);
+        off_the_map.metadata.lat = 123.0;
+        let (mut uuids, mut results) = page(vec![
+            fetched("wall", &["region", "crag"], true, &[]),
+            fetched("other", &["region"], false, &[("boulder", true)]),
+            off_the_map,
+        ]);
+        // Deleted upstream between being queued and being fetched
+        uuids.push("gone".to_string());
+        results.insert("a3".to_string(), None);
+        let mut stats = SyncStats::default();
+        let mut pending = VecDeque::new();
+
+        let (seen, areas) = read_page(&uuids, &results, &stored, &mut stats, &mut pending);
+
+        assert_eq!(stats.fetched, 3);
+        assert_eq!(pending, vec!["boulder".to_string()]);
+        assert_eq!(seen, vec!["wall".to_string(), "other".to_string()]);
+        assert_eq!(areas.len(), 2);
+        // The wall sits in a stored crag, so is not one itself
+        assert!(!areas[0].is_crag);
+        assert!(areas[1].is_crag);
+    }
+
+    #[test]
+    fn pages_keep_elevations_of_areas_that_did_not_move() {
+        let mut known = fetched("crag", &["region"], false, &[("wall", true)])
+            .to_area(false)
+            .unwrap();
+        known.climbing.as_mut().unwrap().elevation_m = Some(1200.0);
+        let stored = HashMap::from([("crag".to_string(), known)]);
+        let (uuids, results) = page(vec![fetched("crag", &["region"], false, &[("wall", true)])]);
+
+        let (_, areas) = read_page(
+            &uuids,
+            &results,
+            &stored,
+            &mut SyncStats::default(),
+            &mut VecDeque::new(),
+        );
+        assert_eq!(areas[0].elevation_m(), Some(1200.0));
+    }
+
+    #[tokio::test]
+    async fn only_changed_areas_are_written() {
+        let repo = MemoryStorage::new();
+        let mut stored = HashMap::new();
+        let mut stats = SyncStats::default();
+        let crag = fetched("crag", &["region"], false, &[("wall", true)])
+            .to_area(false)
+            .unwrap();
+        let wall = fetched("wall", &["region", "crag"], true, &[])
+            .to_area(true)
+            .unwrap();
+        store_areas(
+            &repo,
+            &mut stored,
+            &mut stats,
+            vec![crag.clone(), wall.clone()],
+        )
+        .await
+        .unwrap();
+
+        let renamed = Area {
+            area_name: "The Crag".to_string(),
+            ..crag
+        };
+        store_areas(&repo, &mut stored, &mut stats, vec![renamed, wall])
+            .await
+            .unwrap();
+
+        assert_eq!(stats.inserted, 2);
+        assert_eq!(stats.updated, 1);
+        assert_eq!(stats.renamed, 1);
+        assert_eq!(stats.unchanged, 1);
+        let names: Vec<String> = repo
+            .all_areas()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|area| area.area_name)
+            .collect();
+        assert!(names.contains(&"The Crag".to_string()));
+        assert_eq!(names.len(), 2);
+    }
+
+    #[test]
+    fn unfinished_runs_resume_unless_restarted() {
+        assert!(should_resume(&checkpoint(false), false));
+        assert!(!should_resume(&checkpoint(false), true));
+        assert!(!should_resume(&checkpoint(true), false));
+    }
+
+    #[test]
+    fn new_runs_start_from_the_countries_and_keep_the_last_completion() {
+        let previous = checkpoint(true);
+        let last_completed_at = previous.last_completed_at;
+        let started = Utc::now();
+        let run = new_run(vec!["canada".to_string()], Some(previous), started);
+
+        assert_eq!(run.pending, vec!["canada".to_string()]);
+        assert_eq!(run.run_started_at, started);
+        assert_eq!(run.completed_at, None);
+        assert_eq!(run.last_completed_at, last_completed_at);
+        assert_eq!(run.stats.fetched, 0);
+    }
+}